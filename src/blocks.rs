@@ -112,6 +112,68 @@ impl Transform {
     }
 }
 
+/// An attribute definition (CAD ATTDEF): a named text field drawn on every instance of
+/// a block, with the value given by the instance
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeDefinition {
+    /// Name instances use to give a value
+    pub tag: String,
+    /// Text drawn when an instance gives no value
+    pub default_value: String,
+    /// Start of the text baseline in block coordinates
+    pub position: (f32, f32),
+    /// Resource name of the font (e.g., "F1"), which must be in the resources of the
+    /// content the instances are drawn into
+    pub font: String,
+    /// Font size in block units
+    pub size: f32,
+    /// Fill color of the text, None to keep the current color
+    pub color: Option<(f32, f32, f32)>,
+}
+
+impl AttributeDefinition {
+    /// Creates an attribute drawn at a position in 10pt text using font "F1"
+    pub fn new(tag: impl Into<String>, default_value: impl Into<String>, x: f32, y: f32) -> Self {
+        AttributeDefinition {
+            tag: tag.into(),
+            default_value: default_value.into(),
+            position: (x, y),
+            font: "F1".to_string(),
+            size: 10.0,
+            color: None,
+        }
+    }
+
+    /// Sets the font resource name and size
+    pub fn with_font(mut self, font: impl Into<String>, size: f32) -> Self {
+        self.font = font.into();
+        self.size = size;
+        self
+    }
+
+    /// Sets the text color
+    pub fn with_color(mut self, r: f32, g: f32, b: f32) -> Self {
+        self.color = Some((r, g, b));
+        self
+    }
+
+    /// Operations drawing the attribute with the given value
+    fn to_operations(&self, value: &str) -> Vec<Operation> {
+        let mut ops = vec![Operation::new("BT", vec![])];
+        if let Some((r, g, b)) = self.color {
+            ops.push(Operation::new("rg", vec![r.into(), g.into(), b.into()]));
+        }
+        ops.push(Operation::new(
+            "Tf",
+            vec![Object::Name(self.font.as_bytes().to_vec()), self.size.into()],
+        ));
+        ops.push(Operation::new("Td", vec![self.position.0.into(), self.position.1.into()]));
+        ops.push(Operation::new("Tj", vec![Object::string_literal(value)]));
+        ops.push(Operation::new("ET", vec![]));
+        ops
+    }
+}
+
 /// Represents a reusable block of PDF content
 #[derive(Debug, Clone)]
 pub struct Block {
@@ -123,6 +185,12 @@ pub struct Block {
     pub bbox: Option<(f32, f32, f32, f32)>,
     /// Optional resources required by this block
     pub resources: Option<Dictionary>,
+    /// Layer (OCG) or membership (OCMD) controlling the visibility of the block's XObject
+    pub optional_content: Option<ObjectId>,
+    /// Text fields filled in per instance, drawn on top of the block's content
+    pub attributes: Vec<AttributeDefinition>,
+    /// Instances of other blocks drawn after this block's operations
+    pub children: Vec<BlockInstance>,
 }
 
 impl Block {
@@ -133,6 +201,9 @@ impl Block {
             operations,
             bbox: None,
             resources: None,
+            optional_content: None,
+            attributes: Vec::new(),
+            children: Vec::new(),
         }
     }
 
@@ -148,6 +219,60 @@ impl Block {
         self
     }
 
+    /// Puts the block's Form XObject on a layer or membership
+    pub fn with_optional_content(mut self, optional_content: ObjectId) -> Self {
+        self.optional_content = Some(optional_content);
+        self
+    }
+
+    /// Adds an attribute definition
+    pub fn with_attribute(mut self, attribute: AttributeDefinition) -> Self {
+        self.attributes.push(attribute);
+        self
+    }
+
+    /// Adds an instance of another block, placed in this block's coordinates
+    pub fn with_child(mut self, instance: BlockInstance) -> Self {
+        self.children.push(instance);
+        self
+    }
+
+    /// Operations drawing the attributes with an instance's values, falling back to
+    /// the defaults; empty values are not drawn
+    fn attribute_operations(&self, instance: &BlockInstance) -> Vec<Operation> {
+        let mut ops = Vec::new();
+        for attribute in &self.attributes {
+            let value = instance
+                .attributes
+                .get(&attribute.tag)
+                .unwrap_or(&attribute.default_value);
+            if !value.is_empty() {
+                ops.extend(attribute.to_operations(value));
+            }
+        }
+        ops
+    }
+
+    /// Attribute operations for text drawn outside the block's XObject, marked with the
+    /// block's layer so it hides along with it. The layer is added to `properties`.
+    fn marked_attribute_operations(&self, instance: &BlockInstance, properties: &mut Dictionary) -> Vec<Operation> {
+        let ops = self.attribute_operations(instance);
+        match self.optional_content {
+            Some(optional_content) if !ops.is_empty() => {
+                let tag = format!("BlkOC{}", optional_content.0);
+                properties.set(tag.clone(), Object::Reference(optional_content));
+                let mut marked = vec![Operation::new(
+                    "BDC",
+                    vec![Object::Name(b"OC".to_vec()), Object::Name(tag.into_bytes())],
+                )];
+                marked.extend(ops);
+                marked.push(Operation::new("EMC", vec![]));
+                marked
+            }
+            _ => ops,
+        }
+    }
+
     /// Adds an operation to the block
     pub fn add_operation(&mut self, op: Operation) {
         self.operations.push(op);
@@ -166,6 +291,8 @@ pub struct BlockInstance {
     pub block_id: String,
     /// The transformation to apply
     pub transform: Transform,
+    /// Attribute values by tag (CAD ATTRIB)
+    pub attributes: HashMap<String, String>,
 }
 
 impl BlockInstance {
@@ -174,6 +301,7 @@ impl BlockInstance {
         BlockInstance {
             block_id: block_id.into(),
             transform,
+            attributes: HashMap::new(),
         }
     }
 
@@ -182,6 +310,7 @@ impl BlockInstance {
         BlockInstance {
             block_id: block_id.into(),
             transform: Transform::translate(x, y),
+            attributes: HashMap::new(),
         }
     }
 
@@ -190,8 +319,15 @@ impl BlockInstance {
         BlockInstance {
             block_id: block_id.into(),
             transform: Transform::translate_scale(x, y, scale),
+            attributes: HashMap::new(),
         }
     }
+
+    /// Sets the value of an attribute of the block
+    pub fn with_attribute(mut self, tag: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.insert(tag.into(), value.into());
+        self
+    }
 }
 
 /// Manager for blocks and their instances
@@ -261,6 +397,15 @@ impl BlockManager {
     /// Renders a block instance directly as operations
     /// This method includes the block's operations wrapped with transformation
     pub fn render_instance(&self, instance: &BlockInstance) -> Vec<Operation> {
+        self.render_nested_instance(instance, &mut Vec::new())
+    }
+
+    /// Renders an instance and the blocks nested in it, skipping a child that would
+    /// repeat one of its ancestors
+    fn render_nested_instance<'a>(&'a self, instance: &'a BlockInstance, ancestors: &mut Vec<&'a str>) -> Vec<Operation> {
+        if ancestors.contains(&instance.block_id.as_str()) {
+            return Vec::new();
+        }
         if let Some(block) = self.blocks.get(&instance.block_id) {
             let mut ops = Vec::new();
             
@@ -272,6 +417,12 @@ impl BlockManager {
             
             // Add block operations
             ops.extend(block.operations.clone());
+            ancestors.push(&instance.block_id);
+            for child in &block.children {
+                ops.extend(self.render_nested_instance(child, ancestors));
+            }
+            ancestors.pop();
+            ops.extend(block.attribute_operations(instance));
             
             // Restore graphics state
             ops.push(Operation::new("Q", vec![]));
@@ -293,17 +444,94 @@ impl BlockManager {
 
     /// Creates Form XObjects for all registered blocks
     /// This allows for more efficient reuse in the PDF
-    pub fn create_xobjects(&mut self, doc: &mut Document) {
-        for (id, block) in &self.blocks {
-            if !self.xobjects.contains_key(id) {
-                let xobject_id = self.create_xobject_for_block(doc, block);
+    ///
+    /// Nested blocks get their XObjects first so parents can refer to them. Blocks in a
+    /// cycle, and blocks containing them, get none, and the cycle is returned as the
+    /// error from [`dependency_order`](Self::dependency_order) once the other blocks are
+    /// written.
+    pub fn create_xobjects(&mut self, doc: &mut Document) -> crate::Result<()> {
+        let mut pending: Vec<String> = self
+            .blocks
+            .keys()
+            .filter(|id| !self.xobjects.contains_key(*id))
+            .cloned()
+            .collect();
+        pending.sort();
+
+        loop {
+            // Blocks whose registered children all have XObjects
+            let ready: Vec<String> = pending
+                .iter()
+                .filter(|id| {
+                    self.blocks[*id].children.iter().all(|child| {
+                        !self.blocks.contains_key(&child.block_id) || self.xobjects.contains_key(&child.block_id)
+                    })
+                })
+                .cloned()
+                .collect();
+            if ready.is_empty() {
+                break;
+            }
+            for id in &ready {
+                let xobject_id = self.create_xobject_for_block(doc, &self.blocks[id])?;
                 self.xobjects.insert(id.clone(), xobject_id);
             }
+            pending.retain(|id| !ready.contains(id));
+        }
+
+        // Whatever is left is stuck behind a cycle
+        if !pending.is_empty() {
+            self.dependency_order()?;
+        }
+        Ok(())
+    }
+
+    /// Block IDs ordered so every block comes after the blocks nested in it
+    ///
+    /// Fails with the blocks of a cycle if a block contains itself, directly or through
+    /// other blocks. Children referring to unregistered blocks are ignored.
+    pub fn dependency_order(&self) -> crate::Result<Vec<String>> {
+        let mut ids: Vec<&str> = self.blocks.keys().map(String::as_str).collect();
+        ids.sort();
+
+        let mut finished = Vec::new();
+        let mut path = Vec::new();
+        for id in ids {
+            self.visit_dependencies(id, &mut path, &mut finished)?;
         }
+        Ok(finished.into_iter().map(str::to_string).collect())
+    }
+
+    /// Depth-first walk behind [`dependency_order`](Self::dependency_order)
+    fn visit_dependencies<'a>(
+        &'a self,
+        id: &'a str,
+        path: &mut Vec<&'a str>,
+        finished: &mut Vec<&'a str>,
+    ) -> crate::Result<()> {
+        if finished.contains(&id) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|ancestor| *ancestor == id) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(id);
+            return Err(format!("Block cycle: {}", cycle.join(" -> ")).into());
+        }
+        let Some(block) = self.blocks.get(id) else {
+            return Ok(());
+        };
+
+        path.push(id);
+        for child in &block.children {
+            self.visit_dependencies(&child.block_id, path, finished)?;
+        }
+        path.pop();
+        finished.push(id);
+        Ok(())
     }
 
     /// Creates a Form XObject for a specific block
-    fn create_xobject_for_block(&self, doc: &mut Document, block: &Block) -> ObjectId {
+    fn create_xobject_for_block(&self, doc: &mut Document, block: &Block) -> crate::Result<ObjectId> {
         let mut dict = dictionary! {
             "Type" => "XObject",
             "Subtype" => "Form",
@@ -316,15 +544,48 @@ impl BlockManager {
             dict.set("BBox", vec![0.into(), 0.into(), 100.into(), 100.into()]);
         }
 
-        // Add resources if provided
-        if let Some(ref resources) = block.resources {
-            dict.set("Resources", resources.clone());
+        // Draw nested blocks through their own XObjects, which carry their own resources;
+        // children's attribute text is drawn here and uses this block's fonts. Child
+        // XObjects are named apart from the block's own.
+        let mut resources = block.resources.clone().unwrap_or_default();
+        let mut operations = block.operations.clone();
+        let mut xobject_dict = Dictionary::new();
+        let mut properties = Dictionary::new();
+        let taken = resources.get(b"XObject").and_then(Object::as_dict).ok();
+        let mut names = (0..)
+            .map(|index| format!("Sub{}", index))
+            .filter(|name| !taken.is_some_and(|taken| taken.has(name.as_bytes())));
+        for child in &block.children {
+            let (Some(&xobject_id), Some(child_block)) =
+                (self.xobjects.get(&child.block_id), self.blocks.get(&child.block_id))
+            else {
+                continue;
+            };
+            let name = names.next().unwrap_or_default();
+            xobject_dict.set(name.clone(), Object::Reference(xobject_id));
+
+            operations.push(Operation::new("q", vec![]));
+            operations.push(child.transform.to_operation());
+            operations.push(Operation::new("Do", vec![Object::Name(name.into_bytes())]));
+            operations.extend(child_block.marked_attribute_operations(child, &mut properties));
+            operations.push(Operation::new("Q", vec![]));
+        }
+
+        add_resources(&mut resources, &dictionary! { "XObject" => xobject_dict, "Properties" => properties })
+            .map_err(|conflict| format!("Block '{}' already defines {}", block.id, conflict))?;
+        if !resources.is_empty() {
+            dict.set("Resources", resources);
+        }
+
+        // Tie every instance to a layer
+        if let Some(optional_content) = block.optional_content {
+            dict.set("OC", Object::Reference(optional_content));
         }
 
         // Create content from operations
-        let content = Content { operations: block.operations.clone() };
-        let stream = Stream::new(dict, content.encode().unwrap());
-        doc.add_object(stream)
+        let content = Content { operations };
+        let stream = Stream::new(dict, content.encode()?);
+        Ok(doc.add_object(stream))
     }
 
     /// Renders instances using Form XObjects (more efficient for repeated content)
@@ -336,6 +597,7 @@ impl BlockManager {
     ) -> Vec<Operation> {
         let mut operations = Vec::new();
         let mut xobject_dict = Dictionary::new();
+        let mut properties = Dictionary::new();
 
         for instance in instances {
             if let Some(&xobject_id) = self.xobjects.get(&instance.block_id) {
@@ -358,6 +620,11 @@ impl BlockManager {
                     vec![Object::Name(name.as_bytes().to_vec())],
                 ));
 
+                // Attribute text differs per instance, so it is drawn outside the XObject
+                if let Some(block) = self.blocks.get(&instance.block_id) {
+                    operations.extend(block.marked_attribute_operations(instance, &mut properties));
+                }
+
                 // Restore graphics state
                 operations.push(Operation::new("Q", vec![]));
             }
@@ -368,6 +635,9 @@ impl BlockManager {
             resources.set("XObject", xobject_dict);
         }
 
+        // Keep layer properties already registered for the page
+        merge_resources(resources, &dictionary! { "Properties" => properties });
+
         operations
     }
 
@@ -379,6 +649,52 @@ impl BlockManager {
     }
 }
 
+/// Adds resource entries from `source` (e.g., fonts under `/Font`) to `target`, keeping
+/// the entries `target` already has; empty categories are skipped
+fn merge_resources(target: &mut Dictionary, source: &Dictionary) {
+    for (category, entries) in source.iter() {
+        match entries.as_dict() {
+            Ok(entries) if !entries.is_empty() => {
+                let mut merged = target.get(category).and_then(Object::as_dict).cloned().unwrap_or_default();
+                for (name, value) in entries.iter() {
+                    if !merged.has(name) {
+                        merged.set(name.clone(), value.clone());
+                    }
+                }
+                target.set(category.clone(), merged);
+            }
+            Ok(_) => {}
+            Err(_) => {
+                if !target.has(category) {
+                    target.set(category.clone(), entries.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Adds resource entries from `source` to `target` like [`merge_resources`], failing with
+/// the `/Category/Name` of an entry `target` already binds to a different value
+fn add_resources(target: &mut Dictionary, source: &Dictionary) -> std::result::Result<(), String> {
+    for (category, entries) in source.iter() {
+        let (Ok(entries), Some(existing)) = (entries.as_dict(), target.get(category).and_then(Object::as_dict).ok())
+        else {
+            continue;
+        };
+        for (name, value) in entries.iter() {
+            if existing.get(name).is_ok_and(|current| current != value) {
+                return Err(format!(
+                    "/{}/{} differently",
+                    String::from_utf8_lossy(category),
+                    String::from_utf8_lossy(name)
+                ));
+            }
+        }
+    }
+    merge_resources(target, source);
+    Ok(())
+}
+
 /// Utility to merge operations from multiple blocks into one
 pub fn merge_blocks(blocks: &[&Block]) -> Vec<Operation> {
     let mut operations = Vec::new();
@@ -3,8 +3,11 @@
 //! This module provides functionality to embed other PDF documents within a PDF being created,
 //! with support for multi-page documents, various layout strategies, and transformations.
 
-use lopdf::{content::Operation, dictionary, Dictionary, Document, Object, Stream};
-use std::collections::HashMap;
+use crate::pages;
+use lopdf::{content::{Content, Operation}, dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::io::{Error, ErrorKind, Result};
 use std::path::Path;
 
@@ -38,6 +41,7 @@ pub enum GridFillOrder {
 }
 
 /// Custom layout strategy for maximum flexibility
+#[allow(unpredictable_function_pointer_comparisons)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct CustomLayoutStrategy {
     /// Function to calculate position for each page
@@ -57,6 +61,10 @@ pub struct EmbedOptions {
     pub rotation: f32,
     /// Opacity (0.0 to 1.0)
     pub opacity: f32,
+    /// Blend mode used when compositing the embedded content
+    pub blend_mode: BlendMode,
+    /// Soft mask applied to the embedded content
+    pub soft_mask: Option<SoftMask>,
     /// Layout strategy for multi-page PDFs
     pub layout: MultiPageLayout,
     /// Maximum width constraint (None for no constraint)
@@ -69,6 +77,121 @@ pub struct EmbedOptions {
     pub clip_bounds: Option<(f32, f32, f32, f32)>, // (x, y, width, height)
     /// Page range to include (None means all pages)
     pub page_range: Option<PageRange>,
+    /// Which page boundary to import as the visible area
+    pub page_box: PageBox,
+    /// Layer (OCG) or membership (OCMD) that controls the visibility of the embedded pages
+    pub optional_content: Option<ObjectId>,
+}
+
+/// Page boundaries defined by ISO 32000-2, 14.11.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PageBox {
+    /// The full physical medium
+    MediaBox,
+    /// The visible region shown by viewers (defaults to the MediaBox)
+    #[default]
+    CropBox,
+    /// The region to clip to in production (defaults to the CropBox)
+    BleedBox,
+    /// The intended finished page (defaults to the CropBox)
+    TrimBox,
+    /// The meaningful content area (defaults to the CropBox)
+    ArtBox,
+}
+
+impl PageBox {
+    /// The page dictionary key for this boundary
+    pub fn as_key(&self) -> &'static [u8] {
+        match self {
+            PageBox::MediaBox => b"MediaBox",
+            PageBox::CropBox => b"CropBox",
+            PageBox::BleedBox => b"BleedBox",
+            PageBox::TrimBox => b"TrimBox",
+            PageBox::ArtBox => b"ArtBox",
+        }
+    }
+}
+
+/// Separable and non-separable blend modes (ISO 32000-2, 11.3.5)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl BlendMode {
+    /// The PDF name used for the `/BM` entry
+    pub fn as_name(&self) -> &'static str {
+        match self {
+            BlendMode::Normal => "Normal",
+            BlendMode::Multiply => "Multiply",
+            BlendMode::Screen => "Screen",
+            BlendMode::Overlay => "Overlay",
+            BlendMode::Darken => "Darken",
+            BlendMode::Lighten => "Lighten",
+            BlendMode::ColorDodge => "ColorDodge",
+            BlendMode::ColorBurn => "ColorBurn",
+            BlendMode::HardLight => "HardLight",
+            BlendMode::SoftLight => "SoftLight",
+            BlendMode::Difference => "Difference",
+            BlendMode::Exclusion => "Exclusion",
+            BlendMode::Hue => "Hue",
+            BlendMode::Saturation => "Saturation",
+            BlendMode::Color => "Color",
+            BlendMode::Luminosity => "Luminosity",
+        }
+    }
+}
+
+/// How a soft mask derives its mask values from the transparency group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoftMaskType {
+    /// Mask values come from the group's alpha
+    Alpha,
+    /// Mask values come from the group's luminosity
+    Luminosity,
+}
+
+/// Soft mask referencing a transparency group Form XObject in the target document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SoftMask {
+    /// The transparency group XObject providing the mask values
+    pub group: ObjectId,
+    /// How the mask values are derived
+    pub subtype: SoftMaskType,
+}
+
+impl SoftMask {
+    /// Creates a luminosity soft mask from a transparency group
+    pub fn luminosity(group: ObjectId) -> Self {
+        SoftMask {
+            group,
+            subtype: SoftMaskType::Luminosity,
+        }
+    }
+
+    /// Creates an alpha soft mask from a transparency group
+    pub fn alpha(group: ObjectId) -> Self {
+        SoftMask {
+            group,
+            subtype: SoftMaskType::Alpha,
+        }
+    }
 }
 
 /// Page range specification
@@ -84,6 +207,49 @@ pub enum PageRange {
     All,
 }
 
+impl PageRange {
+    /// Resolve the range to 0-indexed page numbers.
+    ///
+    /// Fails if the range names a page past the end of the document, or is a `Range`
+    /// whose start comes after its end.
+    pub fn to_indices(&self, total_pages: usize) -> Result<Vec<usize>> {
+        let missing = |page: usize| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Page {} does not exist; the document has {} pages", page, total_pages),
+            )
+        };
+        let pages: Vec<usize> = match self {
+            PageRange::Range(start, end) if start > end => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Page range {}..={} is reversed", start, end),
+                ))
+            }
+            PageRange::Range(_, end) if *end >= total_pages => return Err(missing(*end)),
+            PageRange::Single(page) => vec![*page],
+            PageRange::Range(start, end) => (*start..=*end).collect(),
+            PageRange::Pages(specific) => specific.clone(),
+            PageRange::All => (0..total_pages).collect(),
+        };
+        match pages.iter().find(|&&page| page >= total_pages) {
+            Some(&page) => Err(missing(page)),
+            None => Ok(pages),
+        }
+    }
+
+    /// Resolve the range to 0-indexed page numbers, dropping pages past the end
+    pub(crate) fn clamped_indices(&self, total_pages: usize) -> Vec<usize> {
+        let pages: Vec<usize> = match self {
+            PageRange::Single(page) => vec![*page],
+            PageRange::Range(start, end) => (*start..=*end).take_while(|&p| p < total_pages).collect(),
+            PageRange::Pages(specific) => specific.clone(),
+            PageRange::All => (0..total_pages).collect(),
+        };
+        pages.into_iter().filter(|&p| p < total_pages).collect()
+    }
+}
+
 impl Default for EmbedOptions {
     fn default() -> Self {
         EmbedOptions {
@@ -91,12 +257,16 @@ impl Default for EmbedOptions {
             scale: (1.0, 1.0),
             rotation: 0.0,
             opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            soft_mask: None,
             layout: MultiPageLayout::FirstPageOnly,
             max_width: None,
             max_height: None,
             preserve_aspect_ratio: true,
             clip_bounds: None,
             page_range: None,
+            page_box: PageBox::CropBox,
+            optional_content: None,
         }
     }
 }
@@ -131,6 +301,16 @@ impl EmbedOptions {
         self
     }
 
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn with_soft_mask(mut self, soft_mask: SoftMask) -> Self {
+        self.soft_mask = Some(soft_mask);
+        self
+    }
+
     pub fn with_layout(mut self, layout: MultiPageLayout) -> Self {
         self.layout = layout;
         self
@@ -152,6 +332,16 @@ impl EmbedOptions {
         self
     }
 
+    pub fn with_page_box(mut self, page_box: PageBox) -> Self {
+        self.page_box = page_box;
+        self
+    }
+
+    pub fn with_optional_content(mut self, optional_content: ObjectId) -> Self {
+        self.optional_content = Some(optional_content);
+        self
+    }
+
     pub fn preserve_aspect_ratio(mut self, preserve: bool) -> Self {
         self.preserve_aspect_ratio = preserve;
         self
@@ -163,7 +353,7 @@ impl EmbedOptions {
 pub struct EmbeddedPdfInfo {
     /// Number of pages in the source PDF
     pub page_count: usize,
-    /// Dimensions of each page (width, height)
+    /// Visible dimensions of each page (width, height), after applying the CropBox and `/Rotate`
     pub page_dimensions: Vec<(f32, f32)>,
     /// The embedded PDF's metadata
     pub metadata: HashMap<String, String>,
@@ -176,6 +366,318 @@ pub struct EmbedResult {
     pub operations: Vec<Operation>,
     /// The XObject resources to add to the page's Resources dictionary
     pub xobject_resources: HashMap<String, Object>,
+    /// The ExtGState resources to add to the page's Resources dictionary
+    pub extgstate_resources: HashMap<String, Object>,
+}
+
+impl EmbedResult {
+    /// Adds the XObject and ExtGState resources to a page's resources dictionary,
+    /// keeping any entries that are already present
+    pub fn add_to_resources(&self, resources: &mut Dictionary) {
+        merge_resource_entries(resources, b"XObject", &self.xobject_resources);
+        merge_resource_entries(resources, b"ExtGState", &self.extgstate_resources);
+    }
+}
+
+/// Merge named entries into a resource category sub-dictionary
+fn merge_resource_entries(
+    resources: &mut Dictionary,
+    category: &[u8],
+    entries: &HashMap<String, Object>,
+) {
+    if entries.is_empty() {
+        return;
+    }
+
+    if !matches!(resources.get(category), Ok(Object::Dictionary(_))) {
+        resources.set(category, Dictionary::new());
+    }
+
+    if let Ok(Object::Dictionary(ref mut dict)) = resources.get_mut(category) {
+        for (name, obj) in entries {
+            dict.set(name.as_bytes(), obj.clone());
+        }
+    }
+}
+
+/// Reads a fixed-size array of numbers, such as a BBox or Matrix
+fn read_numbers<const N: usize>(obj: Option<&Object>) -> Option<[f32; N]> {
+    let array = obj?.as_array().ok()?;
+    let mut numbers = [0.0; N];
+    if array.len() != N {
+        return None;
+    }
+    for (number, value) in numbers.iter_mut().zip(array) {
+        *number = value.as_float().ok()?;
+    }
+    Some(numbers)
+}
+
+/// Identity of an ExtGState dictionary, used to reuse identical graphics states
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ExtGStateKey {
+    opacity_bits: u32,
+    blend_mode: BlendMode,
+    soft_mask: Option<SoftMask>,
+}
+
+impl ExtGStateKey {
+    fn from_options(options: &EmbedOptions) -> Option<Self> {
+        if options.opacity >= 1.0
+            && options.blend_mode == BlendMode::Normal
+            && options.soft_mask.is_none()
+        {
+            return None;
+        }
+
+        Some(ExtGStateKey {
+            opacity_bits: options.opacity.clamp(0.0, 1.0).to_bits(),
+            blend_mode: options.blend_mode,
+            soft_mask: options.soft_mask,
+        })
+    }
+
+    fn to_dictionary(self) -> Dictionary {
+        let opacity = f32::from_bits(self.opacity_bits);
+        let mut dict = dictionary! {
+            "Type" => "ExtGState",
+            "CA" => opacity,
+            "ca" => opacity,
+            "BM" => self.blend_mode.as_name(),
+        };
+
+        if let Some(mask) = self.soft_mask {
+            let subtype = match mask.subtype {
+                SoftMaskType::Alpha => "Alpha",
+                SoftMaskType::Luminosity => "Luminosity",
+            };
+            dict.set(
+                "SMask",
+                dictionary! {
+                    "Type" => "Mask",
+                    "S" => subtype,
+                    "G" => Object::Reference(mask.group),
+                },
+            );
+        } else {
+            dict.set("SMask", "None");
+        }
+
+        dict
+    }
+}
+
+/// Resolved boundary and rotation of a source page
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PageGeometry {
+    /// Boundary rectangle (llx, lly, urx, ury) in default user space
+    rect: (f32, f32, f32, f32),
+    /// Clockwise display rotation, one of 0, 90, 180, 270
+    rotate: i64,
+}
+
+impl PageGeometry {
+    /// Size of the page as displayed, with width and height swapped for quarter turns
+    fn dimensions(&self) -> (f32, f32) {
+        let (x0, y0, x1, y1) = self.rect;
+        let (w, h) = (x1 - x0, y1 - y0);
+        if self.rotate % 180 == 0 {
+            (w, h)
+        } else {
+            (h, w)
+        }
+    }
+
+    /// Form matrix mapping the boundary onto (0, 0, width, height) the way a viewer displays it
+    fn matrix(&self) -> [f32; 6] {
+        let (x0, y0, x1, y1) = self.rect;
+        match self.rotate {
+            90 => [0.0, -1.0, 1.0, 0.0, -y0, x1],
+            180 => [-1.0, 0.0, 0.0, -1.0, x1, y1],
+            270 => [0.0, 1.0, -1.0, 0.0, y1, -x0],
+            _ => [1.0, 0.0, 0.0, 1.0, -x0, -y0],
+        }
+    }
+}
+
+/// Summary of an object written to a target, used to check that the target still holds
+/// it before reusing it.
+///
+/// Covers the object's kind, stream length and top-level entries with their names,
+/// integers and references, so it costs the same for a small dictionary as for a large
+/// image; stream data and nested objects are not looked at.
+fn stamp(obj: &Object) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    std::mem::discriminant(obj).hash(&mut hasher);
+    let dict = match obj {
+        Object::Stream(stream) => {
+            stream.content.len().hash(&mut hasher);
+            &stream.dict
+        }
+        Object::Dictionary(dict) => dict,
+        Object::Array(array) => {
+            array.len().hash(&mut hasher);
+            return hasher.finish();
+        }
+        _ => return hasher.finish(),
+    };
+    for (key, value) in dict.iter() {
+        key.hash(&mut hasher);
+        std::mem::discriminant(value).hash(&mut hasher);
+        match value {
+            Object::Name(name) => name.hash(&mut hasher),
+            Object::Integer(value) => value.hash(&mut hasher),
+            Object::Reference(id) => id.hash(&mut hasher),
+            _ => {}
+        }
+    }
+    hasher.finish()
+}
+
+/// Whether `id` in the target still holds an object with the stamp recorded when it was written
+fn holds_written_object(target: &Document, id: ObjectId, written: u64) -> bool {
+    target
+        .get_object(id)
+        .map(|obj| stamp(obj) == written)
+        .unwrap_or(false)
+}
+
+/// Where each source object was copied to in a target document.
+///
+/// Each copy is stamped once written, and only reused while the target holds an object
+/// with the same stamp at its ID. Reusing an embedder with a different target therefore
+/// copies again instead of pointing at unrelated objects that happen to share an ID.
+#[derive(Default)]
+pub(crate) struct ImportMap {
+    /// Target ID and stamp per source object; no stamp while still being written
+    entries: HashMap<ObjectId, (ObjectId, Option<u64>)>,
+}
+
+impl ImportMap {
+    /// The copy of `source_id` in `target`, if it is still there
+    pub(crate) fn get(&self, target: &Document, source_id: ObjectId) -> Option<ObjectId> {
+        let &(target_id, written) = self.entries.get(&source_id)?;
+        let valid = match written {
+            Some(written) => holds_written_object(target, target_id, written),
+            None => target.has_object(target_id),
+        };
+        valid.then_some(target_id)
+    }
+
+    /// Record that `source_id` is being copied to `target_id`
+    pub(crate) fn reserve(&mut self, source_id: ObjectId, target_id: ObjectId) {
+        self.entries.insert(source_id, (target_id, None));
+    }
+
+    /// Stamp the finished copy of `source_id` so later lookups can verify it
+    pub(crate) fn seal(&mut self, target: &Document, source_id: ObjectId) {
+        if let Some((target_id, written)) = self.entries.get_mut(&source_id) {
+            *written = target.get_object(*target_id).ok().map(stamp);
+        }
+    }
+}
+
+/// Copies objects from a source document into a target document.
+///
+/// Every indirect object is written once: the `map` remembers where each source
+/// object went, so shared fonts, images and ICC profiles keep being shared and
+/// cyclic references (Parent links, annotation back-references) terminate.
+pub(crate) struct ObjectImporter<'a> {
+    source: &'a Document,
+    target: &'a mut Document,
+    map: &'a mut ImportMap,
+    /// Source objects that must not be copied; references to them become null
+    excluded: HashSet<ObjectId>,
+}
+
+impl<'a> ObjectImporter<'a> {
+    pub(crate) fn new(
+        source: &'a Document,
+        target: &'a mut Document,
+        map: &'a mut ImportMap,
+    ) -> Self {
+        ObjectImporter {
+            source,
+            target,
+            map,
+            excluded: HashSet::new(),
+        }
+    }
+
+    /// Never copy the given source objects, replacing references to them with null
+    pub(crate) fn excluding(mut self, excluded: HashSet<ObjectId>) -> Self {
+        self.excluded = excluded;
+        self
+    }
+
+    /// Import an object, returning its equivalent in the target document
+    pub(crate) fn import(&mut self, obj: &Object) -> Object {
+        match obj {
+            Object::Reference(ref_id) => self.import_reference(*ref_id),
+            Object::Dictionary(dict) => Object::Dictionary(self.import_dictionary(dict)),
+            Object::Array(array) => {
+                Object::Array(array.iter().map(|item| self.import(item)).collect())
+            }
+            Object::Stream(stream) => {
+                // Streams must be indirect, so a direct stream becomes a new object
+                let new_stream = self.import_stream(stream);
+                Object::Reference(self.target.add_object(new_stream))
+            }
+            // For simple types, just clone
+            _ => obj.clone(),
+        }
+    }
+
+    /// Import an indirect object, reusing the earlier copy when there is one
+    pub(crate) fn import_reference(&mut self, source_id: ObjectId) -> Object {
+        if let Some(target_id) = self.map.get(self.target, source_id) {
+            return Object::Reference(target_id);
+        }
+
+        if self.excluded.contains(&source_id) {
+            return Object::Null;
+        }
+
+        let source_obj = match self.source.get_object(source_id) {
+            Ok(obj) => obj,
+            Err(_) => return Object::Null,
+        };
+
+        // Reserve the target id before descending so cycles resolve to it
+        let target_id = self.target.new_object_id();
+        self.map.reserve(source_id, target_id);
+        self.target.set_object(target_id, Object::Null);
+
+        let copied = match source_obj {
+            Object::Stream(stream) => Object::Stream(self.import_stream(stream)),
+            other => self.import(other),
+        };
+        self.target.set_object(target_id, copied);
+        self.map.seal(self.target, source_id);
+
+        Object::Reference(target_id)
+    }
+
+    fn import_dictionary(&mut self, dict: &Dictionary) -> Dictionary {
+        let mut new_dict = Dictionary::new();
+        for (key, value) in dict.iter() {
+            new_dict.set(key.clone(), self.import(value));
+        }
+        new_dict
+    }
+
+    fn import_stream(&mut self, stream: &Stream) -> Stream {
+        let mut new_stream = stream.clone();
+        new_stream.dict = self.import_dictionary(&stream.dict);
+        new_stream
+    }
+}
+
+/// An outline entry copied from a source document, waiting to be written to the target
+struct OutlineNode {
+    title: Object,
+    dest: Object,
+    children: Vec<OutlineNode>,
 }
 
 /// Manager for embedding PDFs into documents
@@ -184,6 +686,16 @@ pub struct PdfEmbedder {
     loaded_pdfs: HashMap<String, (Document, EmbeddedPdfInfo)>,
     /// Counter for generating unique resource names
     resource_counter: usize,
+    /// ExtGState dictionaries already written, keyed by their contents
+    extgstates: HashMap<ExtGStateKey, (String, ObjectId)>,
+    /// Per source document, where each source object was copied to in the target
+    import_maps: HashMap<String, ImportMap>,
+    /// Pages already imported as Form XObjects, keyed by source, page index and boundary,
+    /// with the stamp of the XObject as written
+    imported_pages: HashMap<(String, usize, PageBox), (ObjectId, u64)>,
+    /// Wrappers carrying an `/OC` entry, keyed by the wrapped XObject and its OCG or OCMD,
+    /// with the stamp of the wrapper as written
+    layered_xobjects: HashMap<(ObjectId, ObjectId), (ObjectId, u64)>,
 }
 
 impl Default for PdfEmbedder {
@@ -197,6 +709,10 @@ impl PdfEmbedder {
         PdfEmbedder {
             loaded_pdfs: HashMap::new(),
             resource_counter: 0,
+            extgstates: HashMap::new(),
+            import_maps: HashMap::new(),
+            imported_pages: HashMap::new(),
+            layered_xobjects: HashMap::new(),
         }
     }
 
@@ -242,6 +758,20 @@ impl PdfEmbedder {
         self.loaded_pdfs.get(identifier).map(|(_, info)| info)
     }
 
+    /// Forget which objects have already been imported.
+    ///
+    /// Imported objects are only reused while the target holds an object with the same
+    /// kind, size and top-level entries at their ID, which tells another target document
+    /// apart in constant time. Nested objects and stream data are not compared, so call
+    /// this after editing imported objects in place or when switching to a target that
+    /// may hold look-alike objects at the same IDs.
+    pub fn clear_import_cache(&mut self) {
+        self.import_maps.clear();
+        self.imported_pages.clear();
+        self.layered_xobjects.clear();
+        self.extgstates.clear();
+    }
+
     /// Embed a PDF into the target document
     /// Returns both the operations and the XObject resources that need to be added to the page
     pub fn embed_pdf(
@@ -250,25 +780,55 @@ impl PdfEmbedder {
         source_identifier: &str,
         options: &EmbedOptions,
     ) -> Result<EmbedResult> {
-        // Get source document
+        // Take the source document out of the cache while importing from it
         let (source_doc, info) = self
             .loaded_pdfs
-            .get(source_identifier)
+            .remove(source_identifier)
             .ok_or_else(|| Error::new(ErrorKind::NotFound, "PDF not loaded"))?;
 
-        // Clone necessary data to avoid borrowing issues
-        let info = info.clone();
-        let source_doc = source_doc.clone();
+        let result =
+            self.embed_loaded_pdf(target_doc, source_identifier, &source_doc, &info, options);
 
+        self.loaded_pdfs
+            .insert(source_identifier.to_string(), (source_doc, info));
+        result
+    }
+
+    /// Embed an already loaded source document into the target document
+    fn embed_loaded_pdf(
+        &mut self,
+        target_doc: &mut Document,
+        source_identifier: &str,
+        source_doc: &Document,
+        info: &EmbeddedPdfInfo,
+        options: &EmbedOptions,
+    ) -> Result<EmbedResult> {
         // Determine which pages to include
         let pages_to_include = self.determine_pages(options, info.page_count);
 
+        // Lay out using the dimensions of the requested page boundary
+        let boxed_info;
+        let info = if options.page_box == PageBox::CropBox {
+            info
+        } else {
+            boxed_info = self.measure_pages(source_doc, info, options.page_box);
+            &boxed_info
+        };
+
         // Calculate layout positions for each page
-        let page_positions = self.calculate_page_positions(&pages_to_include, &info, options);
+        let page_positions = self.calculate_page_positions(&pages_to_include, info, options);
 
         // Generate operations for embedding
         let mut all_operations = Vec::new();
         let mut xobject_resources = HashMap::new();
+        let mut extgstate_resources = HashMap::new();
+
+        // Resolve the graphics state for opacity, blend mode and soft mask
+        let ext_gstate = ExtGStateKey::from_options(options)
+            .map(|key| self.get_or_create_extgstate(target_doc, key));
+        if let Some((ref name, id)) = ext_gstate {
+            extgstate_resources.insert(name.clone(), Object::Reference(id));
+        }
 
         // Apply clipping if specified
         if let Some((clip_x, clip_y, clip_w, clip_h)) = options.clip_bounds {
@@ -287,7 +847,21 @@ impl PdfEmbedder {
             let xobject_name = format!("XO{}", self.resource_counter);
 
             // Import the page as a Form XObject
-            let xobject_ref = self.import_page_as_xobject(target_doc, &source_doc, page_idx)?;
+            let xobject_ref = self.import_page_as_xobject(
+                target_doc,
+                source_identifier,
+                source_doc,
+                page_idx,
+                options.page_box,
+            )?;
+
+            // Put the page on a layer without touching the shared imported XObject
+            let xobject_ref = match (options.optional_content, xobject_ref.as_reference()) {
+                (Some(optional_content), Ok(xobject_id)) => Object::Reference(
+                    self.get_or_create_layered_xobject(target_doc, xobject_id, optional_content),
+                ),
+                _ => xobject_ref,
+            };
 
             // Add to resources map
             xobject_resources.insert(xobject_name.clone(), xobject_ref.clone());
@@ -295,12 +869,10 @@ impl PdfEmbedder {
             // Generate operations to place the XObject
             let page_ops = self.place_xobject(
                 &xobject_name,
-                x,
-                y,
-                scale_x,
-                scale_y,
+                (x, y),
+                (scale_x, scale_y),
                 options.rotation,
-                options.opacity,
+                ext_gstate.as_ref().map(|(name, _)| name.as_str()),
             );
             all_operations.extend(page_ops);
         }
@@ -313,16 +885,649 @@ impl PdfEmbedder {
         Ok(EmbedResult {
             operations: all_operations,
             xobject_resources,
+            extgstate_resources,
         })
     }
 
+    /// Get a Form XObject that draws `xobject_id` and carries an `/OC` entry.
+    ///
+    /// Imported pages are shared between placements, so the layer goes on a small wrapper
+    /// instead of the page itself.
+    fn get_or_create_layered_xobject(
+        &mut self,
+        target_doc: &mut Document,
+        xobject_id: ObjectId,
+        optional_content: ObjectId,
+    ) -> ObjectId {
+        let key = (xobject_id, optional_content);
+        if let Some(&(wrapper_id, written)) = self.layered_xobjects.get(&key) {
+            if holds_written_object(target_doc, wrapper_id, written) {
+                return wrapper_id;
+            }
+        }
+
+        // The wrapper's BBox is the wrapped form's BBox mapped through its Matrix
+        let (bbox, matrix) = match target_doc.get_object(xobject_id).and_then(Object::as_stream) {
+            Ok(stream) => (
+                read_numbers(stream.dict.get(b"BBox").ok()).unwrap_or([0.0; 4]),
+                read_numbers(stream.dict.get(b"Matrix").ok()).unwrap_or([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]),
+            ),
+            Err(_) => ([0.0; 4], [1.0, 0.0, 0.0, 1.0, 0.0, 0.0]),
+        };
+        let corners = [
+            (bbox[0], bbox[1]),
+            (bbox[2], bbox[1]),
+            (bbox[0], bbox[3]),
+            (bbox[2], bbox[3]),
+        ]
+        .map(|(x, y)| {
+            (
+                matrix[0] * x + matrix[2] * y + matrix[4],
+                matrix[1] * x + matrix[3] * y + matrix[5],
+            )
+        });
+        let x0 = corners.iter().map(|c| c.0).fold(f32::INFINITY, f32::min);
+        let y0 = corners.iter().map(|c| c.1).fold(f32::INFINITY, f32::min);
+        let x1 = corners.iter().map(|c| c.0).fold(f32::NEG_INFINITY, f32::max);
+        let y1 = corners.iter().map(|c| c.1).fold(f32::NEG_INFINITY, f32::max);
+
+        let content = Content {
+            operations: vec![Operation::new("Do", vec![Object::Name(b"Fm0".to_vec())])],
+        };
+        let wrapper = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![x0.into(), y0.into(), x1.into(), y1.into()],
+                "Resources" => dictionary! {
+                    "XObject" => dictionary! { "Fm0" => Object::Reference(xobject_id) },
+                },
+                "OC" => Object::Reference(optional_content),
+            },
+            content.encode().unwrap_or_default(),
+        );
+
+        let wrapper_id = target_doc.add_object(wrapper);
+        let written = target_doc.get_object(wrapper_id).map(stamp).unwrap_or_default();
+        self.layered_xobjects.insert(key, (wrapper_id, written));
+        wrapper_id
+    }
+
+    /// Information about a loaded PDF with page dimensions measured on the given boundary
+    pub(crate) fn get_pdf_info_for_box(
+        &self,
+        source_identifier: &str,
+        page_box: PageBox,
+    ) -> Result<EmbeddedPdfInfo> {
+        let (source_doc, info) = self
+            .loaded_pdfs
+            .get(source_identifier)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "PDF not loaded"))?;
+        Ok(self.measure_pages(source_doc, info, page_box))
+    }
+
+    /// Copy of `info` with the page dimensions of the given boundary
+    fn measure_pages(
+        &self,
+        source_doc: &Document,
+        info: &EmbeddedPdfInfo,
+        page_box: PageBox,
+    ) -> EmbeddedPdfInfo {
+        let mut page_dimensions = info.page_dimensions.clone();
+        for (page_num, page_id) in source_doc.get_pages() {
+            if let Some(dimensions) = page_dimensions.get_mut(page_num as usize - 1) {
+                if let Ok(page_dict) = source_doc.get_dictionary(page_id) {
+                    *dimensions = self
+                        .get_page_geometry(page_dict, source_doc, page_box)
+                        .dimensions();
+                }
+            }
+        }
+        EmbeddedPdfInfo {
+            page_dimensions,
+            ..info.clone()
+        }
+    }
+
+    /// Get the ExtGState matching the key, writing a new one into the target if needed
+    fn get_or_create_extgstate(
+        &mut self,
+        target_doc: &mut Document,
+        key: ExtGStateKey,
+    ) -> (String, ObjectId) {
+        let dict = key.to_dictionary();
+
+        // Reuse only if the cached object still holds the same dictionary in this target
+        if let Some((name, id)) = self.extgstates.get(&key) {
+            if let Ok(Object::Dictionary(existing)) = target_doc.get_object(*id) {
+                if *existing == dict {
+                    return (name.clone(), *id);
+                }
+            }
+        }
+
+        self.resource_counter += 1;
+        let name = format!("GS{}", self.resource_counter);
+        let id = target_doc.add_object(dict);
+        self.extgstates.insert(key, (name.clone(), id));
+        (name, id)
+    }
+
+    /// Append whole pages of a loaded PDF to the end of the target document.
+    ///
+    /// Annotations, links and outline entries are carried over. Links and outline entries
+    /// that point at pages outside `range` are dropped. Returns the IDs of the new pages.
+    pub fn append_pages(
+        &mut self,
+        target_doc: &mut Document,
+        source_identifier: &str,
+        range: &PageRange,
+    ) -> Result<Vec<ObjectId>> {
+        let (source_doc, info) = self
+            .loaded_pdfs
+            .get(source_identifier)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "PDF not loaded"))?;
+        let pages = range.to_indices(info.page_count)?;
+
+        let import_map = self
+            .import_maps
+            .entry(source_identifier.to_string())
+            .or_default();
+        Self::append_loaded_pages(target_doc, source_doc, &pages, import_map)
+    }
+
+    /// Merge loaded PDFs, in order, into a new document
+    pub fn merge_pdfs(&self, source_identifiers: &[&str]) -> Result<Document> {
+        let mut merged: Option<Document> = None;
+
+        for source_identifier in source_identifiers {
+            let (source_doc, info) = self
+                .loaded_pdfs
+                .get(*source_identifier)
+                .ok_or_else(|| Error::new(ErrorKind::NotFound, "PDF not loaded"))?;
+            let doc = merged.get_or_insert_with(|| Document::with_version(source_doc.version.as_str()));
+
+            let all_pages: Vec<usize> = (0..info.page_count).collect();
+            Self::append_loaded_pages(doc, source_doc, &all_pages, &mut ImportMap::default())?;
+        }
+
+        let mut doc = merged.unwrap_or_else(|| Document::with_version("1.5"));
+        pages::ensure_page_tree(&mut doc);
+        Ok(doc)
+    }
+
+    /// Split a loaded PDF into one new document per page range
+    pub fn split_pdf(&self, source_identifier: &str, ranges: &[PageRange]) -> Result<Vec<Document>> {
+        let (source_doc, info) = self
+            .loaded_pdfs
+            .get(source_identifier)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "PDF not loaded"))?;
+
+        ranges
+            .iter()
+            .map(|range| {
+                let mut doc = Document::with_version(source_doc.version.as_str());
+                let pages = range.to_indices(info.page_count)?;
+                Self::append_loaded_pages(&mut doc, source_doc, &pages, &mut ImportMap::default())?;
+                pages::ensure_page_tree(&mut doc);
+                Ok(doc)
+            })
+            .collect()
+    }
+
+    /// Copy source pages, with their annotations and outline entries, to the end of the target
+    fn append_loaded_pages(
+        target_doc: &mut Document,
+        source_doc: &Document,
+        page_indices: &[usize],
+        import_map: &mut ImportMap,
+    ) -> Result<Vec<ObjectId>> {
+        let source_pages = source_doc.get_pages();
+        let selected = page_indices
+            .iter()
+            .map(|&index| {
+                source_pages
+                    .get(&(index as u32 + 1))
+                    .copied()
+                    .ok_or_else(|| Error::new(ErrorKind::NotFound, "Page not found in source PDF"))
+            })
+            .collect::<Result<Vec<ObjectId>>>()?;
+
+        // Never follow references into the page tree, catalog or outlines; pages that are
+        // not being copied turn into null so links to them can be recognised and dropped
+        let mut excluded: HashSet<ObjectId> = source_pages
+            .values()
+            .filter(|id| !selected.contains(id))
+            .copied()
+            .collect();
+        for (id, obj) in &source_doc.objects {
+            let structural = obj
+                .as_dict()
+                .and_then(|dict| dict.get(b"Type"))
+                .and_then(Object::as_name)
+                .map(|name| matches!(name, b"Pages" | b"Catalog" | b"Outlines"))
+                .unwrap_or(false);
+            if structural {
+                excluded.insert(*id);
+            }
+        }
+
+        // Reserve target pages up front so links between copied pages resolve to the copies.
+        // A page that is already in the target (or listed twice) becomes an extra copy
+        // sharing the same content, without annotations.
+        let mut reserved = HashSet::new();
+        let mut plan = Vec::new();
+        for &source_page_id in &selected {
+            let already_imported = import_map.get(target_doc, source_page_id).is_some();
+            let target_page_id = target_doc.new_object_id();
+            target_doc.set_object(target_page_id, Object::Null);
+
+            let primary = !already_imported && reserved.insert(source_page_id);
+            if primary {
+                import_map.reserve(source_page_id, target_page_id);
+            }
+            plan.push((source_page_id, target_page_id, primary));
+        }
+
+        let mut new_pages = Vec::new();
+        for &(source_page_id, target_page_id, primary) in &plan {
+            let mut page_dict = source_doc
+                .get_dictionary(source_page_id)
+                .map_err(|e| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Page object is not a dictionary: {}", e),
+                    )
+                })?
+                .clone();
+            pages::materialize_inherited_attributes(source_doc, &mut page_dict);
+            page_dict.remove(b"Parent");
+            if !primary {
+                page_dict.remove(b"Annots");
+                page_dict.remove(b"B");
+            }
+
+            let imported = ObjectImporter::new(source_doc, target_doc, import_map)
+                .excluding(excluded.clone())
+                .import(&Object::Dictionary(page_dict));
+            target_doc.set_object(target_page_id, imported);
+
+            if primary {
+                Self::resolve_page_links(target_doc, source_doc, target_page_id, import_map, &excluded);
+            }
+            new_pages.push(target_page_id);
+        }
+
+        Self::append_outlines(target_doc, source_doc, import_map, &excluded);
+
+        let mut all_pages = pages::page_ids(target_doc);
+        all_pages.extend(&new_pages);
+        pages::set_page_order(target_doc, &all_pages)?;
+
+        // Pages are only finished once linked into the page tree
+        for &(source_page_id, _, primary) in &plan {
+            if primary {
+                import_map.seal(target_doc, source_page_id);
+            }
+        }
+
+        Ok(new_pages)
+    }
+
+    /// Turn named destinations on a copied page's links into explicit ones and
+    /// drop links whose destination page was not copied
+    fn resolve_page_links(
+        target_doc: &mut Document,
+        source_doc: &Document,
+        page_id: ObjectId,
+        import_map: &mut ImportMap,
+        excluded: &HashSet<ObjectId>,
+    ) {
+        let annots: Vec<Object> = match target_doc
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"Annots"))
+            .and_then(|annots| target_doc.dereference(annots))
+            .and_then(|(_, annots)| annots.as_array())
+        {
+            Ok(annots) => annots.clone(),
+            Err(_) => return,
+        };
+
+        let mut kept = Vec::new();
+        for annot in annots {
+            let annot_id = match annot.as_reference() {
+                Ok(id) => id,
+                Err(_) => {
+                    kept.push(annot);
+                    continue;
+                }
+            };
+            let mut annot_dict = match target_doc.get_dictionary(annot_id) {
+                Ok(dict) => dict.clone(),
+                Err(_) => continue,
+            };
+            let is_link = annot_dict
+                .get(b"Subtype")
+                .and_then(Object::as_name)
+                .map(|name| name == b"Link")
+                .unwrap_or(false);
+            if !is_link {
+                kept.push(annot);
+                continue;
+            }
+
+            let mut importer =
+                ObjectImporter::new(source_doc, target_doc, import_map).excluding(excluded.clone());
+            let resolved = if let Ok(dest) = annot_dict.get(b"Dest") {
+                let dest = Self::resolve_imported_destination(&mut importer, &dest.clone());
+                if let Some(ref dest) = dest {
+                    annot_dict.set("Dest", dest.clone());
+                }
+                Some(dest)
+            } else if let Some(mut action) = annot_dict
+                .get(b"A")
+                .ok()
+                .and_then(|a| importer.target.dereference(a).ok())
+                .and_then(|(_, a)| a.as_dict().ok())
+                .filter(|a| a.get(b"S").and_then(Object::as_name).ok() == Some(b"GoTo".as_slice()))
+                .cloned()
+            {
+                let dest = match action.get(b"D") {
+                    Ok(dest) => Self::resolve_imported_destination(&mut importer, &dest.clone()),
+                    Err(_) => None,
+                };
+                if let Some(ref dest) = dest {
+                    action.set("D", dest.clone());
+                    annot_dict.set("A", action);
+                }
+                Some(dest)
+            } else {
+                // URI and other actions are kept as they are
+                None
+            };
+
+            let points_at_copied_page = match resolved {
+                None => true,
+                Some(dest) => dest.as_ref().and_then(Self::destination_page).is_some(),
+            };
+            if points_at_copied_page {
+                target_doc.set_object(annot_id, annot_dict);
+                kept.push(annot);
+            }
+        }
+
+        if let Ok(page) = target_doc.get_dictionary_mut(page_id) {
+            page.set("Annots", kept);
+        }
+    }
+
+    /// Resolve a destination that was already copied to the target. Only named
+    /// destinations still need looking up in the source and importing.
+    fn resolve_imported_destination(importer: &mut ObjectImporter, dest: &Object) -> Option<Object> {
+        let dest = importer.target.dereference(dest).ok()?.1.clone();
+        match dest {
+            Object::Name(_) | Object::String(..) => {
+                Self::resolve_destination(importer.source, importer.source, &dest)
+                    .map(|dest| importer.import(&dest))
+            }
+            _ => Self::resolve_destination(importer.source, importer.target, &dest),
+        }
+    }
+
+    /// Resolve a destination to its explicit array form, looking up named destinations
+    /// in the source document. Explicit destinations are returned as they are.
+    pub(crate) fn resolve_destination(source_doc: &Document, doc: &Document, dest: &Object) -> Option<Object> {
+        let (_, dest) = doc.dereference(dest).ok()?;
+        match dest {
+            Object::Array(_) => Some(dest.clone()),
+            Object::Dictionary(dict) => dict.get(b"D").ok().cloned(),
+            Object::Name(name) | Object::String(name, _) => {
+                let found = Self::lookup_named_destination(source_doc, name)?;
+                let (_, found) = source_doc.dereference(&found).ok()?;
+                match found {
+                    Object::Dictionary(dict) => dict.get(b"D").ok().cloned(),
+                    other => Some(other.clone()),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Find a named destination in the catalog's `/Dests` dictionary or `/Names` tree
+    fn lookup_named_destination(doc: &Document, name: &[u8]) -> Option<Object> {
+        let catalog = doc.catalog().ok()?;
+
+        if let Ok(dests) = catalog
+            .get(b"Dests")
+            .and_then(|d| doc.dereference(d))
+            .and_then(|(_, d)| d.as_dict())
+        {
+            if let Ok(dest) = dests.get(name) {
+                return Some(dest.clone());
+            }
+        }
+
+        let tree = catalog
+            .get(b"Names")
+            .and_then(|n| doc.dereference(n))
+            .and_then(|(_, n)| n.as_dict())
+            .and_then(|n| n.get(b"Dests"))
+            .and_then(|d| doc.dereference(d))
+            .and_then(|(_, d)| d.as_dict())
+            .ok()?;
+        Self::lookup_name_tree(doc, tree, name, 0)
+    }
+
+    /// Search a name tree node and its kids for a key
+    fn lookup_name_tree(doc: &Document, node: &Dictionary, name: &[u8], depth: usize) -> Option<Object> {
+        if depth > 32 {
+            return None;
+        }
+
+        if let Ok(names) = node.get(b"Names").and_then(Object::as_array) {
+            for pair in names.chunks(2) {
+                if let [key, value] = pair {
+                    if key.as_str().ok() == Some(name) {
+                        return Some(value.clone());
+                    }
+                }
+            }
+        }
+
+        let kids = node.get(b"Kids").and_then(Object::as_array).ok()?;
+        kids.iter()
+            .filter_map(|kid| doc.dereference(kid).ok())
+            .filter_map(|(_, kid)| kid.as_dict().ok())
+            .find_map(|kid| Self::lookup_name_tree(doc, kid, name, depth + 1))
+    }
+
+    /// The target page an explicit destination points at, if it survived the import
+    fn destination_page(dest: &Object) -> Option<ObjectId> {
+        dest.as_array()
+            .ok()
+            .and_then(|dest| dest.first())
+            .and_then(|page| page.as_reference().ok())
+    }
+
+    /// Copy the source outline entries that point at copied pages to the target's outline
+    fn append_outlines(
+        target_doc: &mut Document,
+        source_doc: &Document,
+        import_map: &mut ImportMap,
+        excluded: &HashSet<ObjectId>,
+    ) {
+        let first = source_doc
+            .catalog()
+            .and_then(|catalog| catalog.get(b"Outlines"))
+            .and_then(|outlines| source_doc.dereference(outlines))
+            .and_then(|(_, outlines)| outlines.as_dict())
+            .and_then(|outlines| outlines.get(b"First"))
+            .and_then(Object::as_reference);
+        let first = match first {
+            Ok(first) => first,
+            Err(_) => return,
+        };
+
+        let mut importer =
+            ObjectImporter::new(source_doc, target_doc, import_map).excluding(excluded.clone());
+        let nodes = Self::collect_outline_items(&mut importer, Some(first), &mut HashSet::new(), 0);
+        if nodes.is_empty() {
+            return;
+        }
+
+        // Find or create the target's outline root
+        let catalog_id = pages::ensure_page_tree(target_doc);
+        let catalog_id = match target_doc.trailer.get(b"Root").and_then(Object::as_reference) {
+            Ok(id) => id,
+            Err(_) => catalog_id,
+        };
+        let root_id = match target_doc
+            .get_dictionary(catalog_id)
+            .and_then(|catalog| catalog.get(b"Outlines"))
+            .and_then(Object::as_reference)
+        {
+            Ok(id) if target_doc.get_dictionary(id).is_ok() => id,
+            _ => {
+                let id = target_doc.add_object(dictionary! { "Type" => "Outlines", "Count" => 0 });
+                if let Ok(catalog) = target_doc.get_dictionary_mut(catalog_id) {
+                    catalog.set("Outlines", Object::Reference(id));
+                }
+                id
+            }
+        };
+
+        let (first_id, last_id) = Self::write_outline_level(target_doc, root_id, &nodes);
+
+        let previous_last = target_doc
+            .get_dictionary(root_id)
+            .and_then(|root| root.get(b"Last"))
+            .and_then(Object::as_reference)
+            .ok();
+        if let Some(previous_last) = previous_last {
+            if let Ok(item) = target_doc.get_dictionary_mut(previous_last) {
+                item.set("Next", Object::Reference(first_id));
+            }
+            if let Ok(item) = target_doc.get_dictionary_mut(first_id) {
+                item.set("Prev", Object::Reference(previous_last));
+            }
+        }
+
+        if let Ok(root) = target_doc.get_dictionary_mut(root_id) {
+            if previous_last.is_none() {
+                root.set("First", Object::Reference(first_id));
+            }
+            root.set("Last", Object::Reference(last_id));
+            let count = root.get(b"Count").and_then(Object::as_i64).unwrap_or(0).abs();
+            root.set("Count", count + nodes.len() as i64);
+        }
+    }
+
+    /// Walk a sibling chain of source outline items, keeping the ones whose destination
+    /// survived the import. Children of dropped items take their parent's place.
+    fn collect_outline_items(
+        importer: &mut ObjectImporter,
+        first: Option<ObjectId>,
+        visited: &mut HashSet<ObjectId>,
+        depth: usize,
+    ) -> Vec<OutlineNode> {
+        let mut nodes = Vec::new();
+        let mut current = first;
+        let source_doc = importer.source;
+
+        while let Some(item_id) = current {
+            if depth > 32 || !visited.insert(item_id) {
+                break;
+            }
+            let item = match source_doc.get_dictionary(item_id) {
+                Ok(item) => item,
+                Err(_) => break,
+            };
+            current = item.get(b"Next").and_then(Object::as_reference).ok();
+
+            let children = Self::collect_outline_items(
+                importer,
+                item.get(b"First").and_then(Object::as_reference).ok(),
+                visited,
+                depth + 1,
+            );
+
+            let dest = item
+                .get(b"Dest")
+                .ok()
+                .or_else(|| {
+                    item.get(b"A")
+                        .and_then(|a| source_doc.dereference(a))
+                        .and_then(|(_, a)| a.as_dict())
+                        .and_then(|a| a.get(b"D"))
+                        .ok()
+                })
+                .and_then(|dest| Self::resolve_destination(source_doc, source_doc, dest))
+                .map(|dest| importer.import(&dest))
+                .filter(|dest| Self::destination_page(dest).is_some());
+
+            match (dest, item.get(b"Title")) {
+                (Some(dest), Ok(title)) => nodes.push(OutlineNode {
+                    title: importer.import(title),
+                    dest,
+                    children,
+                }),
+                _ => nodes.extend(children),
+            }
+        }
+
+        nodes
+    }
+
+    /// Write one level of outline items under `parent_id`, returning the first and last IDs
+    fn write_outline_level(
+        target_doc: &mut Document,
+        parent_id: ObjectId,
+        nodes: &[OutlineNode],
+    ) -> (ObjectId, ObjectId) {
+        let ids: Vec<ObjectId> = nodes.iter().map(|_| target_doc.new_object_id()).collect();
+
+        for (index, node) in nodes.iter().enumerate() {
+            let mut item = dictionary! {
+                "Title" => node.title.clone(),
+                "Parent" => parent_id,
+                "Dest" => node.dest.clone(),
+            };
+            if index > 0 {
+                item.set("Prev", Object::Reference(ids[index - 1]));
+            }
+            if let Some(&next) = ids.get(index + 1) {
+                item.set("Next", Object::Reference(next));
+            }
+            if !node.children.is_empty() {
+                let (first, last) = Self::write_outline_level(target_doc, ids[index], &node.children);
+                item.set("First", Object::Reference(first));
+                item.set("Last", Object::Reference(last));
+                // Negative count: the entry starts closed
+                item.set("Count", -(node.children.len() as i64));
+            }
+            target_doc.set_object(ids[index], item);
+        }
+
+        (ids[0], ids[ids.len() - 1])
+    }
+
     /// Import a page from source document as a Form XObject
     fn import_page_as_xobject(
         &mut self,
         target_doc: &mut Document,
+        source_identifier: &str,
         source_doc: &Document,
         page_index: usize,
+        page_box: PageBox,
     ) -> Result<Object> {
+        // Reuse the XObject if this page was already imported into the target
+        let page_key = (source_identifier.to_string(), page_index, page_box);
+        if let Some(&(xobject_id, written)) = self.imported_pages.get(&page_key) {
+            if holds_written_object(target_doc, xobject_id, written) {
+                return Ok(Object::Reference(xobject_id));
+            }
+        }
+
         // Get the page from source document
         let pages = source_doc.get_pages();
         let page_id = pages
@@ -343,15 +1548,22 @@ impl PdfEmbedder {
             )
         })?;
 
-        // Get page dimensions
-        let media_box = self.get_media_box(page_dict, source_doc)?;
+        // Get the visible boundary and rotation
+        let geometry = self.get_page_geometry(page_dict, source_doc, page_box);
+        let (x0, y0, x1, y1) = geometry.rect;
 
         // Get page content
         let content_stream = self.get_page_content_stream(source_doc, page_dict)?;
 
         // Get page resources
-        let resources = if let Ok(res_obj) = page_dict.get(b"Resources") {
-            self.copy_object_to_target(source_doc, target_doc, res_obj)?
+        let resources = if let Some(res_obj) =
+            self.get_inherited_attribute(page_dict, source_doc, b"Resources")
+        {
+            let import_map = self
+                .import_maps
+                .entry(source_identifier.to_string())
+                .or_default();
+            ObjectImporter::new(source_doc, target_doc, import_map).import(res_obj)
         } else {
             Object::Dictionary(Dictionary::new())
         };
@@ -360,14 +1572,16 @@ impl PdfEmbedder {
         let xobject_dict = dictionary! {
             "Type" => "XObject",
             "Subtype" => "Form",
-            "BBox" => media_box,
+            "BBox" => vec![x0.into(), y0.into(), x1.into(), y1.into()],
             "Resources" => resources,
-            "Matrix" => vec![1.into(), 0.into(), 0.into(), 1.into(), 0.into(), 0.into()],
+            "Matrix" => geometry.matrix().iter().map(|&v| v.into()).collect::<Vec<Object>>(),
         };
 
         // Create the Form XObject stream
         let xobject_stream = Stream::new(xobject_dict, content_stream);
         let xobject_id = target_doc.add_object(xobject_stream);
+        let written = target_doc.get_object(xobject_id).map(stamp).unwrap_or_default();
+        self.imported_pages.insert(page_key, (xobject_id, written));
 
         Ok(Object::Reference(xobject_id))
     }
@@ -414,97 +1628,93 @@ impl PdfEmbedder {
         }
     }
 
-    /// Copy an object from source to target document
-    fn copy_object_to_target(
+    /// Look up a page attribute, walking up the Pages tree for inheritable keys
+    fn get_inherited_attribute<'a>(
+        &self,
+        page_dict: &'a Dictionary,
+        source_doc: &'a Document,
+        key: &[u8],
+    ) -> Option<&'a Object> {
+        pages::get_inherited_attribute(source_doc, page_dict, key)
+    }
+
+    /// Read a rectangle attribute of a page, normalised to (llx, lly, urx, ury)
+    fn get_page_rect(
         &self,
+        page_dict: &Dictionary,
         source_doc: &Document,
-        target_doc: &mut Document,
-        obj: &Object,
-    ) -> Result<Object> {
-        match obj {
-            Object::Reference(ref_id) => {
-                // Dereference and copy the actual object
-                if let Ok(actual_obj) = source_doc.get_object(*ref_id) {
-                    self.copy_object_to_target(source_doc, target_doc, actual_obj)
-                } else {
-                    Ok(Object::Null)
-                }
-            }
-            Object::Dictionary(dict) => {
-                let mut new_dict = Dictionary::new();
-                for (key, value) in dict.iter() {
-                    let new_value = self.copy_object_to_target(source_doc, target_doc, value)?;
-                    new_dict.set(key.clone(), new_value);
-                }
-                Ok(Object::Dictionary(new_dict))
-            }
-            Object::Array(array) => {
-                let mut new_array = Vec::new();
-                for item in array {
-                    let new_item = self.copy_object_to_target(source_doc, target_doc, item)?;
-                    new_array.push(new_item);
-                }
-                Ok(Object::Array(new_array))
-            }
-            Object::Stream(stream) => {
-                let new_dict = if let Object::Dictionary(dict) = self.copy_object_to_target(
-                    source_doc,
-                    target_doc,
-                    &Object::Dictionary(stream.dict.clone()),
-                )? {
-                    dict
-                } else {
-                    Dictionary::new()
-                };
-                let new_stream = Stream::new(new_dict, stream.content.clone());
-                let stream_id = target_doc.add_object(new_stream);
-                Ok(Object::Reference(stream_id))
-            }
-            // For simple types, just clone
-            _ => Ok(obj.clone()),
-        }
-    }
-
-    /// Get MediaBox from page dictionary
-    fn get_media_box(&self, page_dict: &Dictionary, source_doc: &Document) -> Result<Object> {
-        if let Ok(media_box_obj) = page_dict.get(b"MediaBox") {
-            match media_box_obj {
-                Object::Reference(ref_id) => {
-                    if let Ok(actual_obj) = source_doc.get_object(*ref_id) {
-                        Ok(actual_obj.clone())
-                    } else {
-                        // Default A4 size
-                        Ok(Object::Array(vec![
-                            0.into(),
-                            0.into(),
-                            595.into(),
-                            842.into(),
-                        ]))
-                    }
-                }
-                _ => Ok(media_box_obj.clone()),
-            }
-        } else {
-            // Default A4 size
-            Ok(Object::Array(vec![
-                0.into(),
-                0.into(),
-                595.into(),
-                842.into(),
-            ]))
+        key: &[u8],
+    ) -> Option<(f32, f32, f32, f32)> {
+        let obj = self.get_inherited_attribute(page_dict, source_doc, key)?;
+        let (_, obj) = source_doc.dereference(obj).ok()?;
+        let coords = obj.as_array().ok()?;
+        if coords.len() < 4 {
+            return None;
+        }
+
+        let mut values = [0.0f32; 4];
+        for (value, coord) in values.iter_mut().zip(coords) {
+            let (_, coord) = source_doc.dereference(coord).ok()?;
+            *value = coord.as_float().ok()?;
         }
+        let [ax, ay, bx, by] = values;
+        Some((ax.min(bx), ay.min(by), ax.max(bx), ay.max(by)))
+    }
+
+    /// Resolve the requested boundary and `/Rotate` of a page, applying the spec defaults
+    fn get_page_geometry(
+        &self,
+        page_dict: &Dictionary,
+        source_doc: &Document,
+        page_box: PageBox,
+    ) -> PageGeometry {
+        // Default A4 size
+        let media_box = self
+            .get_page_rect(page_dict, source_doc, b"MediaBox")
+            .unwrap_or((0.0, 0.0, 595.0, 842.0));
+        let crop_box = self
+            .get_page_rect(page_dict, source_doc, b"CropBox")
+            .unwrap_or(media_box);
+
+        let rect = match page_box {
+            PageBox::MediaBox => media_box,
+            PageBox::CropBox => crop_box,
+            other => self
+                .get_page_rect(page_dict, source_doc, other.as_key())
+                .unwrap_or(crop_box),
+        };
+
+        // Boundaries are clipped to the MediaBox; fall back to it if nothing is left
+        let clipped = (
+            rect.0.max(media_box.0),
+            rect.1.max(media_box.1),
+            rect.2.min(media_box.2),
+            rect.3.min(media_box.3),
+        );
+        let rect = if clipped.0 < clipped.2 && clipped.1 < clipped.3 {
+            clipped
+        } else {
+            media_box
+        };
+
+        let rotate = self
+            .get_inherited_attribute(page_dict, source_doc, b"Rotate")
+            .and_then(|obj| source_doc.dereference(obj).ok())
+            .and_then(|(_, obj)| obj.as_i64().ok())
+            .map(|degrees| (degrees / 90 * 90).rem_euclid(360))
+            .unwrap_or(0);
+
+        PageGeometry { rect, rotate }
     }
 
     /// Generate operations to place an XObject
     fn place_xobject(
         &self,
         xobject_name: &str,
-        x: f32,
-        y: f32,
-        scale_x: f32,
-        scale_y: f32,
+        (x, y): (f32, f32),
+        (scale_x, scale_y): (f32, f32),
         rotation: f32,
-        opacity: f32,
+        ext_gstate: Option<&str>,
     ) -> Vec<Operation> {
         let mut operations = Vec::new();
 
@@ -529,10 +1739,12 @@ impl PdfEmbedder {
             ],
         ));
 
-        // Apply opacity if needed (simplified - in production you'd need to properly handle ExtGState)
-        if opacity < 1.0 {
-            // This is simplified - proper implementation would require adding to Resources/ExtGState
-            // For now, we'll skip opacity handling in the actual rendering
+        // Apply opacity, blend mode and soft mask
+        if let Some(gs_name) = ext_gstate {
+            operations.push(Operation::new(
+                "gs",
+                vec![Object::Name(gs_name.as_bytes().to_vec())],
+            ));
         }
 
         // Draw the XObject
@@ -556,7 +1768,9 @@ impl PdfEmbedder {
         for (_page_num, page_id) in pages.iter() {
             if let Ok(page_obj) = doc.get_object(*page_id) {
                 if let Ok(page_dict) = page_obj.as_dict() {
-                    let dimensions = self.get_page_dimensions(page_dict, doc);
+                    let dimensions = self
+                        .get_page_geometry(page_dict, doc, PageBox::CropBox)
+                        .dimensions();
                     page_dimensions.push(dimensions);
                 }
             }
@@ -564,18 +1778,16 @@ impl PdfEmbedder {
 
         // Extract metadata
         let mut metadata = HashMap::new();
-        if let Ok(info_obj) = doc.trailer.get(b"Info") {
-            if let Object::Reference(info_ref) = info_obj {
-                if let Ok(info_obj) = doc.get_object(*info_ref) {
-                    if let Ok(info_dict) = info_obj.as_dict() {
-                        // Extract common metadata fields
-                        for (key, value) in info_dict.iter() {
-                            if let Ok(string_val) = value.as_str() {
-                                metadata.insert(
-                                    String::from_utf8_lossy(key).to_string(),
-                                    String::from_utf8_lossy(string_val).to_string(),
-                                );
-                            }
+        if let Ok(Object::Reference(info_ref)) = doc.trailer.get(b"Info") {
+            if let Ok(info_obj) = doc.get_object(*info_ref) {
+                if let Ok(info_dict) = info_obj.as_dict() {
+                    // Extract common metadata fields
+                    for (key, value) in info_dict.iter() {
+                        if let Ok(string_val) = value.as_str() {
+                            metadata.insert(
+                                String::from_utf8_lossy(key).to_string(),
+                                String::from_utf8_lossy(string_val).to_string(),
+                            );
                         }
                     }
                 }
@@ -589,72 +1801,19 @@ impl PdfEmbedder {
         })
     }
 
-    /// Get dimensions of a page from its dictionary
-    fn get_page_dimensions(&self, page_dict: &Dictionary, source_doc: &Document) -> (f32, f32) {
-        if let Ok(media_box_obj) = page_dict.get(b"MediaBox") {
-            let media_box = match media_box_obj {
-                Object::Reference(ref_id) => {
-                    if let Ok(actual_obj) = source_doc.get_object(*ref_id) {
-                        actual_obj
-                    } else {
-                        media_box_obj
-                    }
-                }
-                _ => media_box_obj,
-            };
-
-            if let Object::Array(coords) = media_box {
-                if coords.len() >= 4 {
-                    // Extract coordinates, handling different number types
-                    let x1 = match &coords[0] {
-                        Object::Real(val) => *val,
-                        Object::Integer(val) => *val as f32,
-                        _ => 0.0,
-                    };
-                    let y1 = match &coords[1] {
-                        Object::Real(val) => *val,
-                        Object::Integer(val) => *val as f32,
-                        _ => 0.0,
-                    };
-                    let x2 = match &coords[2] {
-                        Object::Real(val) => *val,
-                        Object::Integer(val) => *val as f32,
-                        _ => 595.0,
-                    };
-                    let y2 = match &coords[3] {
-                        Object::Real(val) => *val,
-                        Object::Integer(val) => *val as f32,
-                        _ => 842.0,
-                    };
-                    return ((x2 - x1).abs(), (y2 - y1).abs());
-                }
-            }
-        }
-        (595.0, 842.0) // Default A4 size
-    }
-
     /// Determine which pages to include based on options
     fn determine_pages(&self, options: &EmbedOptions, total_pages: usize) -> Vec<usize> {
         let range = options.page_range.as_ref().unwrap_or(&PageRange::All);
 
-        let mut pages = match range {
-            PageRange::Single(page) => vec![*page],
-            PageRange::Range(start, end) => (*start..=*end.min(&(total_pages - 1))).collect(),
-            PageRange::Pages(specific) => specific.clone(),
-            PageRange::All => (0..total_pages).collect(),
-        };
+        let mut pages = range.clamped_indices(total_pages);
 
         // Apply layout-specific filtering
         match options.layout {
-            MultiPageLayout::FirstPageOnly => {
-                if !pages.is_empty() {
-                    pages = vec![pages[0]];
-                }
+            MultiPageLayout::FirstPageOnly if !pages.is_empty() => {
+                pages = vec![pages[0]];
             }
-            MultiPageLayout::SpecificPage(page) => {
-                if page < total_pages {
-                    pages = vec![page];
-                }
+            MultiPageLayout::SpecificPage(page) if page < total_pages => {
+                pages = vec![page];
             }
             _ => {} // Other layouts use all specified pages
         }
@@ -663,7 +1822,7 @@ impl PdfEmbedder {
     }
 
     /// Calculate positions for each page based on layout strategy
-    fn calculate_page_positions(
+    pub(crate) fn calculate_page_positions(
         &self,
         pages: &[usize],
         info: &EmbeddedPdfInfo,
@@ -759,6 +1918,7 @@ pub struct EmbedLayoutBuilder {
     embedder: PdfEmbedder,
     operations: Vec<Operation>,
     xobject_resources: HashMap<String, Object>,
+    extgstate_resources: HashMap<String, Object>,
 }
 
 impl Default for EmbedLayoutBuilder {
@@ -773,6 +1933,7 @@ impl EmbedLayoutBuilder {
             embedder: PdfEmbedder::new(),
             operations: Vec::new(),
             xobject_resources: HashMap::new(),
+            extgstate_resources: HashMap::new(),
         }
     }
 
@@ -791,10 +1952,13 @@ impl EmbedLayoutBuilder {
         let result = self.embedder.embed_pdf(target_doc, source_id, &options)?;
         self.operations.extend(result.operations);
         self.xobject_resources.extend(result.xobject_resources);
+        self.extgstate_resources
+            .extend(result.extgstate_resources);
         Ok(self)
     }
 
     /// Create a thumbnail gallery of PDF pages
+    #[allow(clippy::too_many_arguments)]
     pub fn create_thumbnail_gallery(
         &mut self,
         target_doc: &mut Document,
@@ -819,6 +1983,7 @@ impl EmbedLayoutBuilder {
     }
 
     /// Create a side-by-side comparison of two PDFs
+    #[allow(clippy::too_many_arguments)]
     pub fn create_comparison(
         &mut self,
         target_doc: &mut Document,
@@ -856,6 +2021,7 @@ impl EmbedLayoutBuilder {
         EmbedResult {
             operations: self.operations,
             xobject_resources: self.xobject_resources,
+            extgstate_resources: self.extgstate_resources,
         }
     }
 
@@ -7,12 +7,19 @@ use lopdf::{
     content::{Content, Operation},
     dictionary, Dictionary, Document, Object, ObjectId, Stream,
 };
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::f32::consts::PI;
 use std::sync::Arc;
 
+/// Resource name of the `[/Pattern /DeviceRGB]` color space used by uncolored patterns
+pub const UNCOLORED_PATTERN_COLORSPACE: &str = "PatternRGB";
+
+/// Operators that set colors, stripped from uncolored pattern content
+const COLOR_OPERATORS: [&str; 12] = ["CS", "cs", "SC", "SCN", "sc", "scn", "G", "g", "RG", "rg", "K", "k"];
+
 /// Represents a hatching pattern style
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HatchStyle {
     /// Single diagonal lines (/)
     DiagonalRight,
@@ -54,20 +61,71 @@ pub enum HatchStyle {
     ConcentricCircles,
     /// Wood grain pattern
     WoodGrain,
-    /// Custom user-defined pattern
+    /// Custom user-defined pattern; holds functions, so it cannot be serialized
+    #[cfg_attr(feature = "serde", serde(skip))]
     Custom(CustomPattern),
+    /// Parallel line families, such as a pattern loaded from a `.pat` file
+    LineFamilies(Vec<HatchLineFamily>),
+    /// A pattern drawn from a data-only description, which can be saved and loaded
+    Described(PatternDescription),
+}
+
+impl HatchStyle {
+    /// The line families a line-based style is made of at the given spacing, None for
+    /// other styles.
+    ///
+    /// Brick, Diamond and Triangles are straight-line tiles and are modelled as families
+    /// too, Brick as in AutoCAD's `BRICK` pattern. Dots, Checkerboard and the curved
+    /// styles have no line families.
+    ///
+    /// Start from these to restyle a preset, e.g. to give a cross hatch a heavier
+    /// horizontal family, and pass the result to [`HatchConfig::with_line_families`].
+    pub fn line_families(&self, spacing: f32) -> Option<Vec<HatchLineFamily>> {
+        let diagonal = spacing / 2f32.sqrt();
+        let horizontal = HatchLineFamily::new(0.0, (0.0, spacing / 2.0), (0.0, spacing));
+        let vertical = HatchLineFamily::new(90.0, (spacing / 2.0, 0.0), (0.0, spacing));
+        let right = HatchLineFamily::new(45.0, (0.0, 0.0), (0.0, diagonal));
+        let left = HatchLineFamily::new(135.0, (0.0, spacing), (0.0, diagonal));
+        let row = 3f32.sqrt() * spacing;
+
+        Some(match self {
+            HatchStyle::Horizontal => vec![horizontal],
+            HatchStyle::Vertical => vec![vertical],
+            HatchStyle::DiagonalRight => vec![right],
+            HatchStyle::DiagonalLeft => vec![left],
+            HatchStyle::Cross => vec![horizontal, vertical],
+            HatchStyle::DiagonalCross => vec![right, left],
+            // Courses of bricks twice as long as they are high, joints offset by half a brick
+            HatchStyle::Brick => vec![
+                HatchLineFamily::new(0.0, (0.0, 0.0), (0.0, spacing)),
+                HatchLineFamily::new(90.0, (spacing, 0.0), (spacing, 2.0 * spacing))
+                    .with_dashes(vec![spacing, -spacing]),
+            ],
+            HatchStyle::Diamond => vec![
+                HatchLineFamily::new(45.0, (spacing, 0.0), (0.0, 2.0 * diagonal)),
+                HatchLineFamily::new(135.0, (0.0, spacing), (0.0, 2.0 * diagonal)),
+            ],
+            HatchStyle::Triangles => vec![
+                HatchLineFamily::new(0.0, (0.0, 0.0), (0.0, row)),
+                HatchLineFamily::new(60.0, (spacing, 0.0), (0.0, row)),
+                HatchLineFamily::new(120.0, (spacing, 0.0), (0.0, row)),
+            ],
+            HatchStyle::LineFamilies(families) => families.clone(),
+            _ => return None,
+        })
+    }
 }
 
+/// Drawing function used by parametric custom patterns
+pub type ParametricPatternFn = Arc<dyn Fn(f32, f32, &PatternParams) -> Vec<Operation> + Send + Sync>;
+
 /// Represents a custom pattern defined by user-provided drawing commands
 #[derive(Clone)]
 pub enum CustomPattern {
     /// A simple function that generates operations
     Simple(Arc<dyn Fn(f32, f32) -> Vec<Operation> + Send + Sync>),
     /// A parameterized pattern with custom data
-    Parametric(
-        Arc<dyn Fn(f32, f32, &PatternParams) -> Vec<Operation> + Send + Sync>,
-        PatternParams,
-    ),
+    Parametric(ParametricPatternFn, PatternParams),
     /// A procedural pattern based on mathematical functions
     Procedural(ProceduralPattern),
     /// A composite pattern that combines multiple patterns
@@ -93,6 +151,8 @@ impl std::fmt::Debug for CustomPattern {
 
 /// Parameters for custom patterns
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct PatternParams {
     pub data: HashMap<String, f32>,
     pub colors: Vec<(f32, f32, f32)>,
@@ -132,9 +192,38 @@ impl PatternParams {
 /// Procedural pattern generator using mathematical functions
 #[derive(Clone)]
 pub struct ProceduralPattern {
+    /// Whether the sample at (x, y) is filled; the third argument runs from 0 to 1 across the tile
     pub sampler: Arc<dyn Fn(f32, f32, f32) -> bool + Send + Sync>,
+    /// Number of samples along each side of the tile
     pub resolution: usize,
+    /// Fill the traced regions of filled samples, or draw a dot per filled sample
     pub fill: bool,
+    /// Round the traced region outlines into curves
+    pub smooth: bool,
+}
+
+impl ProceduralPattern {
+    /// Creates a pattern filling the regions where the sampler is true
+    pub fn new(sampler: impl Fn(f32, f32, f32) -> bool + Send + Sync + 'static, resolution: usize) -> Self {
+        ProceduralPattern {
+            sampler: Arc::new(sampler),
+            resolution,
+            fill: true,
+            smooth: false,
+        }
+    }
+
+    /// Builder method to fill regions (true) or draw dots (false)
+    pub fn with_fill(mut self, fill: bool) -> Self {
+        self.fill = fill;
+        self
+    }
+
+    /// Builder method to round region outlines into curves
+    pub fn with_smoothing(mut self, smooth: bool) -> Self {
+        self.smooth = smooth;
+        self
+    }
 }
 
 impl std::fmt::Debug for ProceduralPattern {
@@ -142,6 +231,7 @@ impl std::fmt::Debug for ProceduralPattern {
         f.debug_struct("ProceduralPattern")
             .field("resolution", &self.resolution)
             .field("fill", &self.fill)
+            .field("smooth", &self.smooth)
             .field("sampler", &"<function>")
             .finish()
     }
@@ -157,6 +247,8 @@ pub struct PatternElement {
 
 /// Transform for pattern elements
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Transform {
     pub translate: (f32, f32),
     pub rotate: f32,
@@ -383,8 +475,220 @@ impl CustomPatternBuilder {
     }
 }
 
-/// Configuration for a hatching pattern
+/// A number in a [`PatternDescription`]: a literal, or the name of a parameter.
+///
+/// The names `width` and `height` refer to the size of the pattern tile.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum PatternValue {
+    Number(f32),
+    Param(String),
+}
+
+impl PatternValue {
+    fn resolve(&self, params: &PatternParams, width: f32, height: f32) -> crate::Result<f32> {
+        match self {
+            PatternValue::Number(value) => Ok(*value),
+            PatternValue::Param(name) => match name.as_str() {
+                "width" => Ok(width),
+                "height" => Ok(height),
+                _ => params
+                    .data
+                    .get(name)
+                    .copied()
+                    .ok_or_else(|| format!("Unknown pattern parameter '{}'", name).into()),
+            },
+        }
+    }
+}
+
+impl From<f32> for PatternValue {
+    fn from(value: f32) -> Self {
+        PatternValue::Number(value)
+    }
+}
+
+impl From<&str> for PatternValue {
+    fn from(name: &str) -> Self {
+        PatternValue::Param(name.to_string())
+    }
+}
+
+/// A drawing step of a [`PatternDescription`], one per [`CustomPatternBuilder`] method
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "op", rename_all = "snake_case"))]
+pub enum PathElement {
+    MoveTo { x: PatternValue, y: PatternValue },
+    LineTo { x: PatternValue, y: PatternValue },
+    CurveTo {
+        x1: PatternValue,
+        y1: PatternValue,
+        x2: PatternValue,
+        y2: PatternValue,
+        x: PatternValue,
+        y: PatternValue,
+    },
+    ClosePath,
+    Rectangle {
+        x: PatternValue,
+        y: PatternValue,
+        width: PatternValue,
+        height: PatternValue,
+    },
+    Circle { cx: PatternValue, cy: PatternValue, r: PatternValue },
+    Polygon { points: Vec<(PatternValue, PatternValue)> },
+    Stroke,
+    Fill,
+    FillStroke,
+    LineWidth { width: PatternValue },
+    StrokeColor { color: (f32, f32, f32) },
+    FillColor { color: (f32, f32, f32) },
+    Dash { pattern: Vec<PatternValue>, phase: PatternValue },
+}
+
+/// A piece of a [`PatternDescription`]: path elements drawn with an optional transform,
+/// like a [`PatternElement`] of a composite pattern
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DescribedElement {
+    pub path: Vec<PathElement>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub transform: Option<Transform>,
+}
+
+/// A custom pattern described by data alone, so it can be stored as JSON or TOML with
+/// the `serde` feature and drawn with [`HatchStyle::Described`].
+///
+/// Numbers may name a parameter in `params` instead of being written out, which keeps
+/// variants of one pattern in a single description. Deserializing fails for a description
+/// referring to an undefined parameter.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "StoredDescription"))]
+pub struct PatternDescription {
+    /// Tile size, None for the config's spacing times its scale
+    pub size: Option<(f32, f32)>,
+    /// Values elements refer to by name
+    pub params: PatternParams,
+    /// Pieces of the pattern, drawn in order
+    pub elements: Vec<DescribedElement>,
+}
+
+/// A [`PatternDescription`] as stored, validated before it is used
+#[cfg(feature = "serde")]
+#[derive(Default, serde::Deserialize)]
+#[serde(default)]
+struct StoredDescription {
+    size: Option<(f32, f32)>,
+    params: PatternParams,
+    elements: Vec<DescribedElement>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<StoredDescription> for PatternDescription {
+    type Error = String;
+
+    fn try_from(stored: StoredDescription) -> std::result::Result<Self, String> {
+        let description = PatternDescription {
+            size: stored.size,
+            params: stored.params,
+            elements: stored.elements,
+        };
+        description.validate().map_err(|err| err.to_string())?;
+        Ok(description)
+    }
+}
+
+impl PatternDescription {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builder method to set the tile size
+    pub fn with_size(mut self, width: f32, height: f32) -> Self {
+        self.size = Some((width, height));
+        self
+    }
+
+    /// Builder method to set a parameter elements can refer to
+    pub fn with_param(mut self, key: &str, value: f32) -> Self {
+        self.params.data.insert(key.to_string(), value);
+        self
+    }
+
+    /// Builder method to add a piece of the pattern
+    pub fn with_element(mut self, path: Vec<PathElement>, transform: Option<Transform>) -> Self {
+        self.elements.push(DescribedElement { path, transform });
+        self
+    }
+
+    /// Checks that every parameter the elements refer to is defined
+    pub fn validate(&self) -> crate::Result<()> {
+        self.operations(1.0, 1.0).map(|_| ())
+    }
+
+    /// The content drawing one tile of the given size
+    pub fn operations(&self, width: f32, height: f32) -> crate::Result<Vec<Operation>> {
+        let value = |value: &PatternValue| value.resolve(&self.params, width, height);
+        let mut builder = CustomPatternBuilder::new();
+
+        for element in &self.elements {
+            if let Some(ref transform) = element.transform {
+                builder.push_transform(transform.clone());
+            }
+            for step in &element.path {
+                match step {
+                    PathElement::MoveTo { x, y } => builder.move_to(value(x)?, value(y)?),
+                    PathElement::LineTo { x, y } => builder.line_to(value(x)?, value(y)?),
+                    PathElement::CurveTo { x1, y1, x2, y2, x, y } => builder.curve_to(
+                        value(x1)?,
+                        value(y1)?,
+                        value(x2)?,
+                        value(y2)?,
+                        value(x)?,
+                        value(y)?,
+                    ),
+                    PathElement::ClosePath => builder.close_path(),
+                    PathElement::Rectangle { x, y, width, height } => {
+                        builder.rectangle(value(x)?, value(y)?, value(width)?, value(height)?)
+                    }
+                    PathElement::Circle { cx, cy, r } => builder.circle(value(cx)?, value(cy)?, value(r)?),
+                    PathElement::Polygon { points } => {
+                        let points = points
+                            .iter()
+                            .map(|(x, y)| Ok((value(x)?, value(y)?)))
+                            .collect::<crate::Result<Vec<(f32, f32)>>>()?;
+                        builder.polygon(&points)
+                    }
+                    PathElement::Stroke => builder.stroke(),
+                    PathElement::Fill => builder.fill(),
+                    PathElement::FillStroke => builder.fill_stroke(),
+                    PathElement::LineWidth { width } => builder.set_line_width(value(width)?),
+                    PathElement::StrokeColor { color: (r, g, b) } => builder.set_stroke_color(*r, *g, *b),
+                    PathElement::FillColor { color: (r, g, b) } => builder.set_fill_color(*r, *g, *b),
+                    PathElement::Dash { pattern, phase } => {
+                        let pattern = pattern.iter().map(value).collect::<crate::Result<Vec<f32>>>()?;
+                        builder.set_dash_pattern(pattern, value(phase)?)
+                    }
+                };
+            }
+            if element.transform.is_some() {
+                builder.pop_transform();
+            }
+        }
+        Ok(builder.build())
+    }
+}
+
+/// Configuration for a hatching pattern.
+///
+/// With the `serde` feature, configs can be saved and loaded unless their style is
+/// [`HatchStyle::Custom`]; missing fields take their default values.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct HatchConfig {
     /// The style of hatching
     pub style: HatchStyle,
@@ -400,6 +704,13 @@ pub struct HatchConfig {
     pub angle: f32,
     /// Scale factor for the pattern
     pub scale: f32,
+    /// Dash lengths and phase for hatch lines, None for solid lines
+    pub dash: Option<(Vec<f32>, f32)>,
+    /// Leave color out of the pattern so it is chosen where the pattern is used
+    pub uncolored: bool,
+    /// Pattern matrix placing the pattern in the space of the content using it, None for
+    /// the default anchoring at that space's origin
+    pub matrix: Option<[f32; 6]>,
 }
 
 impl Default for HatchConfig {
@@ -412,6 +723,9 @@ impl Default for HatchConfig {
             background: None,
             angle: 0.0,
             scale: 1.0,
+            dash: None,
+            uncolored: false,
+            matrix: None,
         }
     }
 }
@@ -460,12 +774,555 @@ impl HatchConfig {
         self.scale = scale;
         self
     }
+
+    /// Builder method to dash the hatch lines
+    pub fn with_dash(mut self, pattern: Vec<f32>, phase: f32) -> Self {
+        self.dash = Some((pattern, phase));
+        self
+    }
+
+    /// Builder method to hatch with the given line families.
+    ///
+    /// Each family may set its own width, color, cap and join; unset ones use this
+    /// config's line width and color. Families are scaled and rotated with the config.
+    pub fn with_line_families(mut self, families: Vec<HatchLineFamily>) -> Self {
+        self.style = HatchStyle::LineFamilies(families);
+        self
+    }
+
+    /// Builder method to make an uncolored (PaintType 2) pattern.
+    ///
+    /// The color and background are ignored; the color is given with
+    /// [`PatternOperations::set_fill_pattern_with_color`] each time the pattern is used.
+    pub fn with_uncolored(mut self, uncolored: bool) -> Self {
+        self.uncolored = uncolored;
+        self
+    }
+
+    /// Builder method to set the pattern matrix
+    pub fn with_matrix(mut self, matrix: [f32; 6]) -> Self {
+        self.matrix = Some(matrix);
+        self
+    }
+
+    /// Builder method to anchor the hatch at a base point, like a CAD hatch origin.
+    ///
+    /// Shapes hatched with the same origin line up with each other. After
+    /// [`with_block_transform`](Self::with_block_transform) the point is in block coordinates.
+    pub fn with_origin(mut self, x: f32, y: f32) -> Self {
+        let current = self.matrix.unwrap_or(IDENTITY_MATRIX);
+        self.matrix = Some(multiply_matrices([1.0, 0.0, 0.0, 1.0, x, y], current));
+        self
+    }
+
+    /// Builder method to make the hatch move, rotate and scale with a block instance drawn
+    /// inline with [`BlockManager::render_instance`](crate::blocks::BlockManager::render_instance).
+    ///
+    /// Patterns used inside block XObjects already follow the block and do not need this.
+    pub fn with_block_transform(mut self, transform: &crate::blocks::Transform) -> Self {
+        let current = self.matrix.unwrap_or(IDENTITY_MATRIX);
+        self.matrix = Some(multiply_matrices(current, transform.to_matrix()));
+        self
+    }
+
+    /// Line families as drawn in the content's space, with the pattern matrix applied
+    fn placed_line_families(&self) -> crate::Result<Vec<HatchLineFamily>> {
+        let families = self
+            .line_families()
+            .ok_or_else(|| format!("{:?} hatches cannot be drawn as vector lines", self.style))?;
+        Ok(match self.matrix {
+            Some(matrix) => families.iter().map(|family| family.transformed(matrix)).collect(),
+            None => families,
+        })
+    }
+
+    /// The parallel line families making up a line-based style, None for other styles.
+    ///
+    /// Families match the lines a tiling pattern of this config draws, so both modes
+    /// produce the same hatch. Scale and angle are already applied.
+    fn line_families(&self) -> Option<Vec<HatchLineFamily>> {
+        let families = self.style.line_families(self.spacing)?;
+
+        // The tiling pattern rotates about its origin
+        Some(
+            families
+                .into_iter()
+                .map(|family| {
+                    let origin = (family.origin.0 * self.scale, family.origin.1 * self.scale);
+                    HatchLineFamily {
+                        angle: family.angle + self.angle,
+                        origin: rotate_point(origin, self.angle),
+                        offset: (family.offset.0 * self.scale, family.offset.1 * self.scale),
+                        dashes: family.dashes.iter().map(|length| length * self.scale).collect(),
+                        ..family
+                    }
+                })
+                .collect(),
+        )
+    }
 }
 
-/// Manager for creating and managing hatching patterns in a PDF
-pub struct HatchingManager {
+/// A straight piece of a hatch line, from start to end point
+pub type HatchSegment = ((f32, f32), (f32, f32));
+
+/// How the ends of stroked lines are drawn (the `J` operator)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineCap {
+    /// Squared off at the end point
+    Butt,
+    /// A half circle around the end point
+    Round,
+    /// Squared off half a line width past the end point
+    Square,
+}
+
+impl LineCap {
+    fn operand(self) -> i32 {
+        match self {
+            LineCap::Butt => 0,
+            LineCap::Round => 1,
+            LineCap::Square => 2,
+        }
+    }
+}
+
+/// How corners of stroked paths are drawn (the `j` operator)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+impl LineJoin {
+    fn operand(self) -> i32 {
+        match self {
+            LineJoin::Miter => 0,
+            LineJoin::Round => 1,
+            LineJoin::Bevel => 2,
+        }
+    }
+}
+
+/// A family of parallel, evenly spaced hatch lines, as in an AutoCAD `.pat` line.
+///
+/// Lines run at `angle` through `origin`; each next line is shifted by `offset`, measured
+/// along and perpendicular to the line direction. Dash lengths follow the `.pat`
+/// convention: positive draws, negative skips and zero draws a dot.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HatchLineFamily {
+    /// Direction of the lines in degrees
+    pub angle: f32,
+    /// A point where one line's dash sequence starts
+    pub origin: (f32, f32),
+    /// Shift from one line to the next (along the line, perpendicular to it)
+    pub offset: (f32, f32),
+    /// Dash lengths, empty for solid lines
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub dashes: Vec<f32>,
+    /// Line width, None for the config's line width
+    pub width: Option<f32>,
+    /// Line color, None for the config's color
+    pub color: Option<(f32, f32, f32)>,
+    /// Line cap, None for butt caps (round when the family has dots)
+    pub cap: Option<LineCap>,
+    /// Line join, None to keep the current join
+    pub join: Option<LineJoin>,
+}
+
+impl HatchLineFamily {
+    /// Creates a family of solid lines
+    pub fn new(angle: f32, origin: (f32, f32), offset: (f32, f32)) -> Self {
+        HatchLineFamily {
+            angle,
+            origin,
+            offset,
+            dashes: Vec::new(),
+            width: None,
+            color: None,
+            cap: None,
+            join: None,
+        }
+    }
+
+    /// Builder method to dash the lines
+    pub fn with_dashes(mut self, dashes: Vec<f32>) -> Self {
+        self.dashes = dashes;
+        self
+    }
+
+    /// Builder method to set the line width
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Builder method to set the line color
+    pub fn with_color(mut self, r: f32, g: f32, b: f32) -> Self {
+        self.color = Some((r, g, b));
+        self
+    }
+
+    /// Builder method to set the line cap
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = Some(cap);
+        self
+    }
+
+    /// Builder method to set the line join
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = Some(join);
+        self
+    }
+
+    /// Unit vectors along and perpendicular to the lines
+    fn axes(&self) -> ((f32, f32), (f32, f32)) {
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        ((cos, sin), (-sin, cos))
+    }
+
+    /// Pieces of the family's lines inside a boundary with the index of their line,
+    /// each running in the line direction
+    fn clip(&self, polygons: &[Vec<(f32, f32)>], fill_rule: FillRule) -> Vec<(i64, HatchSegment)> {
+        let (direction, normal) = self.axes();
+        let dot = |a: (f32, f32), b: (f32, f32)| a.0 * b.0 + a.1 * b.1;
+        let spacing = self.offset.1;
+
+        let offsets = polygons.iter().flatten().map(|&point| dot(point, normal));
+        let (min, max) = offsets.fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+        if spacing.abs() <= f32::EPSILON || !min.is_finite() {
+            return Vec::new();
+        }
+
+        let base = dot(self.origin, normal);
+        let (low, high) = ((min - base) / spacing, (max - base) / spacing);
+        let first = low.min(high).ceil() as i64;
+        let last = low.max(high).floor() as i64;
+        let mut segments = Vec::new();
+
+        for index in first..=last {
+            let offset = base + index as f32 * spacing;
+
+            // Where the line crosses each edge, and which way the edge winds
+            let mut crossings: Vec<(f32, i32)> = Vec::new();
+            for polygon in polygons {
+                for (i, &p) in polygon.iter().enumerate() {
+                    let q = polygon[(i + 1) % polygon.len()];
+                    let (sp, sq) = (dot(p, normal) - offset, dot(q, normal) - offset);
+                    // Half-open test so a line through a vertex counts it once
+                    let winding = match (sp <= 0.0, sq <= 0.0) {
+                        (true, false) => 1,
+                        (false, true) => -1,
+                        _ => continue,
+                    };
+                    let t = sp / (sp - sq);
+                    let point = (p.0 + (q.0 - p.0) * t, p.1 + (q.1 - p.1) * t);
+                    crossings.push((dot(point, direction), winding));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                let inside = match fill_rule {
+                    FillRule::NonZero => winding != 0,
+                    FillRule::EvenOdd => winding % 2 != 0,
+                };
+                if inside && pair[1].0 - pair[0].0 > 1e-4 {
+                    let at = |along: f32| {
+                        (direction.0 * along + normal.0 * offset, direction.1 * along + normal.1 * offset)
+                    };
+                    segments.push((index, (at(pair[0].0), at(pair[1].0))));
+                }
+            }
+        }
+        segments
+    }
+
+    /// The dashes as a PDF dash array and the distance into it where each line's
+    /// sequence starts, None for solid lines
+    fn pdf_dash(&self) -> Option<(Vec<f32>, f32)> {
+        // Merge runs that draw or skip alike; dots count as drawing
+        let mut runs: Vec<(bool, f32)> = Vec::new();
+        for &length in &self.dashes {
+            let on = length >= 0.0;
+            match runs.last_mut() {
+                Some(run) if run.0 == on => run.1 += length.abs(),
+                _ => runs.push((on, length.abs())),
+            }
+        }
+        if runs.iter().all(|run| run.0) {
+            return None;
+        }
+
+        // PDF dash arrays start drawing and alternate in pairs
+        let mut shift = 0.0;
+        if !runs[0].0 {
+            let skipped = runs.remove(0);
+            shift -= skipped.1;
+            match runs.last_mut() {
+                Some(last) if !last.0 => last.1 += skipped.1,
+                _ => runs.push(skipped),
+            }
+        }
+        // Starting and ending with a dash: the last one continues into the first
+        if runs.len() % 2 == 1 {
+            let last = runs.pop().unwrap_or_default();
+            runs[0].1 += last.1;
+            shift += last.1;
+        }
+        Some((runs.into_iter().map(|run| run.1).collect(), shift))
+    }
+
+    /// The family mapped through a transformation matrix
+    fn transformed(&self, matrix: [f32; 6]) -> Self {
+        let [a, b, c, d, e, f] = matrix;
+        let linear = |(x, y): (f32, f32)| (a * x + c * y, b * x + d * y);
+        let (direction, normal) = self.axes();
+
+        // Lengths along the lines scale evenly; the step between lines is re-measured
+        // against the new line direction
+        let along = linear(direction);
+        let stretch = along.0.hypot(along.1);
+        let (ux, uy) = (along.0 / stretch, along.1 / stretch);
+        let step = linear((
+            self.offset.0 * direction.0 + self.offset.1 * normal.0,
+            self.offset.0 * direction.1 + self.offset.1 * normal.1,
+        ));
+        let origin = linear(self.origin);
+
+        HatchLineFamily {
+            angle: uy.atan2(ux).to_degrees(),
+            origin: (origin.0 + e, origin.1 + f),
+            offset: (step.0 * ux + step.1 * uy, step.1 * ux - step.0 * uy),
+            dashes: self.dashes.iter().map(|length| length * stretch).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Whether any dash is a dot, which needs round caps to show
+    fn has_dots(&self) -> bool {
+        self.dashes.contains(&0.0)
+    }
+
+    /// Strokes clipped pieces of this family's lines, phasing the dashes from the line
+    /// origins so they stay aligned across pieces and tiles
+    fn stroke_segments(&self, segments: &[(i64, HatchSegment)], dash: Option<&(Vec<f32>, f32)>, ops: &mut Vec<Operation>) {
+        let (direction, _) = self.axes();
+        let own_dash = self.pdf_dash();
+        let dash = own_dash
+            .as_ref()
+            .or(dash)
+            .filter(|(pattern, _)| pattern.iter().sum::<f32>() > 0.0);
+        let start = self.origin.0 * direction.0 + self.origin.1 * direction.1;
+
+        for &(index, (from, to)) in segments {
+            if let Some((pattern, shift)) = dash {
+                let length: f32 = pattern.iter().sum();
+                let along = from.0 * direction.0 + from.1 * direction.1;
+                let line_start = start + index as f32 * self.offset.0;
+                let array: Vec<Object> = pattern.iter().map(|&v| v.into()).collect();
+                ops.push(Operation::new(
+                    "d",
+                    vec![Object::Array(array), (along - line_start + shift).rem_euclid(length).into()],
+                ));
+            }
+            ops.push(Operation::new("m", vec![from.0.into(), from.1.into()]));
+            ops.push(Operation::new("l", vec![to.0.into(), to.1.into()]));
+            ops.push(Operation::new("S", vec![]));
+        }
+    }
+
+    /// Distances along an axis after which the family may repeat: the first `count`
+    /// multiples of its line spacing and of its dash length, measured along the axis
+    fn period_candidates(&self, axis: (f32, f32), count: usize) -> Vec<f32> {
+        let (direction, normal) = self.axes();
+        let across = axis.0 * normal.0 + axis.1 * normal.1;
+        let along = axis.0 * direction.0 + axis.1 * direction.1;
+        let dash_length = self.pdf_dash().map(|(pattern, _)| pattern.iter().sum::<f32>()).unwrap_or(0.0);
+        let mut candidates = Vec::new();
+
+        for k in 1..=count {
+            if across.abs() > 1e-4 {
+                candidates.push(k as f32 * (self.offset.1 / across).abs());
+            }
+            if dash_length > 0.0 && along.abs() > 1e-4 {
+                candidates.push(k as f32 * dash_length / along.abs());
+            }
+        }
+        candidates
+    }
+
+    /// Whether shifting by `distance` along an axis maps the family onto itself
+    fn repeats_after(&self, axis: (f32, f32), distance: f32) -> bool {
+        let (direction, normal) = self.axes();
+        let near_integer = |value: f32| (value - value.round()).abs() < 1e-3;
+        let across = distance * (axis.0 * normal.0 + axis.1 * normal.1);
+        let along = distance * (axis.0 * direction.0 + axis.1 * direction.1);
+
+        if self.offset.1.abs() <= f32::EPSILON {
+            return true;
+        }
+        let lines = across / self.offset.1;
+        if !near_integer(lines) {
+            return false;
+        }
+        match self.pdf_dash() {
+            Some((pattern, _)) => {
+                let length: f32 = pattern.iter().sum();
+                near_integer((along - lines.round() * self.offset.0) / length)
+            }
+            None => true,
+        }
+    }
+}
+
+/// Size of a tile that repeats every family seamlessly along both axes.
+///
+/// Falls back to the largest candidate tried when the families share no small period.
+/// An axis every family repeats along freely takes the other axis' size.
+fn line_family_tile(families: &[HatchLineFamily]) -> (f32, f32) {
+    let period = |axis: (f32, f32)| {
+        let mut candidates: Vec<f32> = families
+            .iter()
+            .flat_map(|family| family.period_candidates(axis, 24))
+            .filter(|&candidate| candidate > 1e-3)
+            .collect();
+        candidates.sort_by(f32::total_cmp);
+        candidates
+            .iter()
+            .copied()
+            .find(|&candidate| families.iter().all(|family| family.repeats_after(axis, candidate)))
+            .or_else(|| candidates.last().copied())
+    };
+    match (period((1.0, 0.0)), period((0.0, 1.0))) {
+        (Some(width), Some(height)) => (width, height),
+        (Some(size), None) | (None, Some(size)) => (size, size),
+        (None, None) => (1.0, 1.0),
+    }
+}
+
+/// Strokes every family's lines inside the given polygons, setting each family's width,
+/// color, cap and join only where they differ from the previous family's
+fn line_family_operations(
+    families: &[HatchLineFamily],
+    polygons: &[Vec<(f32, f32)>],
+    fill_rule: FillRule,
+    config: &HatchConfig,
+) -> Vec<Operation> {
+    let mut ops = Vec::new();
+    let (mut width, mut color, mut cap, mut join) = (None, None, None, None);
+
+    for family in families {
+        let segments = family.clip(polygons, fill_rule);
+        if segments.is_empty() {
+            continue;
+        }
+
+        let family_width = family.width.unwrap_or(config.line_width);
+        if width != Some(family_width) {
+            ops.push(Operation::new("w", vec![family_width.into()]));
+            width = Some(family_width);
+        }
+        let (r, g, b) = family.color.unwrap_or(config.color);
+        if !config.uncolored && color != Some((r, g, b)) {
+            ops.push(Operation::new("RG", vec![r.into(), g.into(), b.into()]));
+            color = Some((r, g, b));
+        }
+
+        // Butt caps are the default, so they are only set to undo an earlier family's cap
+        let family_cap = family
+            .cap
+            .or_else(|| family.has_dots().then_some(LineCap::Round))
+            .map(LineCap::operand)
+            .or(cap.map(|_| LineCap::Butt.operand()));
+        if family_cap != cap {
+            ops.push(Operation::new("J", vec![family_cap.unwrap_or(0).into()]));
+            cap = family_cap;
+        }
+        if let Some(family_join) = family.join.map(LineJoin::operand) {
+            if join != Some(family_join) {
+                ops.push(Operation::new("j", vec![family_join.into()]));
+                join = Some(family_join);
+            }
+        }
+
+        family.stroke_segments(&segments, config.dash.as_ref(), &mut ops);
+    }
+    ops
+}
+
+/// The matrix that changes nothing
+const IDENTITY_MATRIX: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// The matrix applying `first` and then `then`
+fn multiply_matrices(first: [f32; 6], then: [f32; 6]) -> [f32; 6] {
+    let [a, b, c, d, e, f] = first;
+    let [a2, b2, c2, d2, e2, f2] = then;
+    [
+        a * a2 + b * c2,
+        a * b2 + b * d2,
+        c * a2 + d * c2,
+        c * b2 + d * d2,
+        e * a2 + f * c2 + e2,
+        e * b2 + f * d2 + f2,
+    ]
+}
+
+fn rotate_point((x, y): (f32, f32), degrees: f32) -> (f32, f32) {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    (x * cos - y * sin, x * sin + y * cos)
+}
+
+/// Identifies an object by its resource category, dictionary and stream content
+type PatternKey = (&'static str, String, Vec<u8>);
+
+/// Patterns and shadings already written to a document, keyed by their content.
+///
+/// A cache belongs to one document. Share it between the pages of that document, for
+/// example by handing it from one manager to the next with [`HatchingManager::with_cache`].
+#[derive(Debug, Clone, Default)]
+pub struct PatternCache {
     /// Counter for generating unique pattern names
     pattern_counter: usize,
+    entries: HashMap<PatternKey, (ObjectId, String)>,
+}
+
+impl PatternCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of distinct patterns and shadings written
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forgets all written objects; names stay unique
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Written objects with their resource category ("Pattern" or "Shading") and name
+    pub fn entries(&self) -> impl Iterator<Item = (&'static str, ObjectId, &str)> {
+        self.entries
+            .iter()
+            .map(|((category, _, _), (id, name))| (*category, *id, name.as_str()))
+    }
+}
+
+/// Manager for creating and managing hatching patterns in a PDF
+pub struct HatchingManager {
+    /// Objects written so far, reused for identical patterns
+    cache: PatternCache,
 }
 
 impl Default for HatchingManager {
@@ -477,31 +1334,104 @@ impl Default for HatchingManager {
 impl HatchingManager {
     /// Creates a new HatchingManager
     pub fn new() -> Self {
-        HatchingManager { pattern_counter: 0 }
+        HatchingManager {
+            cache: PatternCache::new(),
+        }
+    }
+
+    /// Creates a HatchingManager that reuses the patterns in a cache from the same document
+    pub fn with_cache(cache: PatternCache) -> Self {
+        HatchingManager { cache }
+    }
+
+    /// Patterns and shadings written by this manager
+    pub fn cache(&self) -> &PatternCache {
+        &self.cache
+    }
+
+    /// Takes the cache out of the manager to share it with another
+    pub fn into_cache(self) -> PatternCache {
+        self.cache
+    }
+
+    /// Adds every cached pattern and shading to a page's resources
+    pub fn add_cached_to_resources(&self, resources: &mut Dictionary) {
+        for (category, id, name) in self.cache.entries() {
+            match category {
+                "Shading" => self.add_shading_to_resources(resources, name, id),
+                _ => self.add_pattern_to_resources(resources, name, id),
+            }
+        }
+    }
+
+    /// Writes an object unless an identical one is cached; returns its ID and name
+    fn add_cached(
+        &mut self,
+        doc: &mut Document,
+        category: &'static str,
+        prefix: &str,
+        object: Object,
+    ) -> (ObjectId, String) {
+        let key = match object {
+            Object::Stream(ref stream) => (category, format!("{:?}", stream.dict), stream.content.clone()),
+            ref other => (category, format!("{:?}", other), Vec::new()),
+        };
+        if let Some(existing) = self.cache.entries.get(&key) {
+            return existing.clone();
+        }
+
+        self.cache.pattern_counter += 1;
+        let name = format!("{}{}", prefix, self.cache.pattern_counter);
+        let id = doc.add_object(object);
+        self.cache.entries.insert(key, (id, name.clone()));
+        (id, name)
     }
 
     /// Creates a pattern object in the PDF document
-    /// Returns the pattern ID and name to use in content streams
+    /// Returns the pattern ID and name to use in content streams; an identical pattern
+    /// created before is returned instead of writing a new one
+    ///
+    /// Loaded descriptions are checked when deserialized. A [`HatchStyle::Described`]
+    /// pattern built in code that refers to an undefined parameter draws an empty tile;
+    /// [`HatchingManager::try_create_pattern`] reports it instead.
     pub fn create_pattern(
         &mut self,
         doc: &mut Document,
         config: &HatchConfig,
     ) -> (ObjectId, String) {
-        self.pattern_counter += 1;
-        let pattern_name = format!("P{}", self.pattern_counter);
+        let (width, height) = self.calculate_pattern_bounds(config);
+        let operations = self.generate_pattern_operations(config, width, height).unwrap_or_default();
+        self.write_pattern(doc, config, width, height, operations)
+    }
 
-        // Calculate pattern bounds based on style and config
+    /// Creates a pattern object like [`HatchingManager::create_pattern`], returning an error
+    /// instead of writing anything if a described pattern refers to an undefined parameter
+    pub fn try_create_pattern(
+        &mut self,
+        doc: &mut Document,
+        config: &HatchConfig,
+    ) -> crate::Result<(ObjectId, String)> {
         let (width, height) = self.calculate_pattern_bounds(config);
+        let operations = self.generate_pattern_operations(config, width, height)?;
+        Ok(self.write_pattern(doc, config, width, height, operations))
+    }
 
-        // Generate pattern content
-        let operations = self.generate_pattern_operations(config, width, height);
+    /// Writes a tiling pattern drawing the given content, unless an identical one is cached
+    fn write_pattern(
+        &mut self,
+        doc: &mut Document,
+        config: &HatchConfig,
+        width: f32,
+        height: f32,
+        operations: Vec<Operation>,
+    ) -> (ObjectId, String) {
         let content = Content { operations };
 
         // Create pattern stream
-        let pattern_dict = dictionary! {
+        let mut pattern_dict = dictionary! {
             "Type" => "Pattern",
             "PatternType" => 1i32,  // Tiling pattern
-            "PaintType" => 1i32,    // Colored pattern
+            "PaintType" => if config.uncolored { 2i32 } else { 1i32 },  // Uncolored or colored pattern
             "TilingType" => 1i32,    // Constant spacing
             "BBox" => vec![0.into(), 0.into(), width.into(), height.into()],
             "XStep" => Object::Real(width),
@@ -509,11 +1439,12 @@ impl HatchingManager {
             "Resources" => dictionary!{},
         };
 
-        let pattern_stream = Stream::new(pattern_dict, content.encode().unwrap());
-        let pattern_id = doc.add_object(pattern_stream);
+        if let Some(matrix) = config.matrix {
+            pattern_dict.set("Matrix", matrix.iter().map(|&v| Object::Real(v)).collect::<Vec<Object>>());
+        }
 
-        // Return the pattern ID and name
-        (pattern_id, pattern_name)
+        let pattern_stream = Stream::new(pattern_dict, content.encode().unwrap());
+        self.add_cached(doc, "Pattern", "P", Object::Stream(pattern_stream))
     }
 
     /// Adds a pattern to a page's resources
@@ -533,25 +1464,127 @@ impl HatchingManager {
         }
     }
 
-    /// Calculate pattern bounds based on style
-    fn calculate_pattern_bounds(&self, config: &HatchConfig) -> (f32, f32) {
-        let base_size = config.spacing * config.scale;
+    /// Writes a shading to the document for painting with the `sh` operator.
+    /// Returns the shading ID and name to use in content streams
+    pub fn create_shading(&mut self, doc: &mut Document, shading: &Shading) -> crate::Result<(ObjectId, String)> {
+        let shading_object = shading.to_object()?;
+        Ok(self.add_cached(doc, "Shading", "Sh", shading_object))
+    }
 
-        match config.style {
-            HatchStyle::Checkerboard => (base_size * 2.0, base_size * 2.0),
-            HatchStyle::Brick => (base_size * 4.0, base_size * 2.0),
-            HatchStyle::Hexagonal => (base_size * 3.0, base_size * 2.6),
-            HatchStyle::Circles | HatchStyle::ConcentricCircles => {
-                (base_size * 2.0, base_size * 2.0)
-            }
-            HatchStyle::Diamond => (base_size * 2.0, base_size * 2.0),
-            HatchStyle::Scales => (base_size * 2.0, base_size * 2.0),
-            HatchStyle::Triangles => (base_size * 2.0, base_size * 1.73),
+    /// Creates a shading (type 2) pattern, used like a tiling pattern to fill shapes.
+    ///
+    /// Shading patterns are positioned in the page's default coordinate space, not the
+    /// space current where they are used. Returns the pattern ID and name.
+    pub fn create_shading_pattern(
+        &mut self,
+        doc: &mut Document,
+        shading: &Shading,
+    ) -> crate::Result<(ObjectId, String)> {
+        let (shading_id, _) = self.create_shading(doc, shading)?;
+        let pattern = dictionary! {
+            "Type" => "Pattern",
+            "PatternType" => 2i32,  // Shading pattern
+            "Shading" => Object::Reference(shading_id),
+        };
+        Ok(self.add_cached(doc, "Pattern", "P", Object::Dictionary(pattern)))
+    }
+
+    /// Adds a shading to a page's resources
+    pub fn add_shading_to_resources(&self, resources: &mut Dictionary, shading_name: &str, shading_id: ObjectId) {
+        if !resources.has(b"Shading") {
+            resources.set("Shading", Dictionary::new());
+        }
+
+        if let Ok(Object::Dictionary(ref mut shadings)) = resources.get_mut(b"Shading") {
+            shadings.set(shading_name, Object::Reference(shading_id));
+        }
+    }
+
+    /// Adds the `[/Pattern /DeviceRGB]` color space used by uncolored patterns to a
+    /// page's resources
+    pub fn add_uncolored_colorspace_to_resources(&self, resources: &mut Dictionary) {
+        if !resources.has(b"ColorSpace") {
+            resources.set("ColorSpace", Dictionary::new());
+        }
+
+        if let Ok(Object::Dictionary(ref mut color_spaces)) = resources.get_mut(b"ColorSpace") {
+            color_spaces.set(
+                UNCOLORED_PATTERN_COLORSPACE,
+                vec![Object::Name(b"Pattern".to_vec()), Object::Name(b"DeviceRGB".to_vec())],
+            );
+        }
+    }
+
+    /// Computes the hatch lines of a line-based style inside a boundary.
+    ///
+    /// Lines are clipped exactly against the boundary's loops (curves are flattened to
+    /// within 0.05pt) using its fill rule. Each segment runs in its line family's direction.
+    pub fn hatch_line_segments(
+        &self,
+        boundary: &HatchBoundary,
+        config: &HatchConfig,
+    ) -> crate::Result<Vec<HatchSegment>> {
+        let families = config.placed_line_families()?;
+        let polygons: Vec<Vec<(f32, f32)>> = boundary
+            .loops
+            .iter()
+            .map(|boundary_loop| boundary_loop.flatten(0.05))
+            .filter(|polygon| polygon.len() > 2)
+            .collect();
+
+        Ok(families
+            .iter()
+            .flat_map(|family| family.clip(&polygons, boundary.fill_rule))
+            .map(|(_, segment)| segment)
+            .collect())
+    }
+
+    /// Draws a hatch as plain stroked lines clipped to a boundary instead of a tiling pattern.
+    ///
+    /// The result is exact and seam-free in every viewer and stays selectable in CAD
+    /// tools. Dashes are phased along each line so they align across islands.
+    pub fn create_vector_hatch(&self, boundary: &HatchBoundary, config: &HatchConfig) -> crate::Result<Vec<Operation>> {
+        let families = config.placed_line_families()?;
+        let polygons: Vec<Vec<(f32, f32)>> = boundary
+            .loops
+            .iter()
+            .map(|boundary_loop| boundary_loop.flatten(0.05))
+            .filter(|polygon| polygon.len() > 2)
+            .collect();
+        let mut ops = vec![Operation::new("q", vec![])];
+
+        if let Some((r, g, b)) = config.background {
+            ops.push(Operation::new("rg", vec![r.into(), g.into(), b.into()]));
+            ops.extend(boundary.to_operations());
+            ops.push(Operation::new(boundary.fill_rule.fill_operator(), vec![]));
+        }
+
+        ops.extend(line_family_operations(&families, &polygons, boundary.fill_rule, config));
+
+        ops.push(Operation::new("Q", vec![]));
+        Ok(ops)
+    }
+
+    /// Calculate pattern bounds based on style
+    fn calculate_pattern_bounds(&self, config: &HatchConfig) -> (f32, f32) {
+        let base_size = config.spacing * config.scale;
+        if let Some(families) = config.line_families() {
+            return line_family_tile(&families);
+        }
+
+        match config.style {
+            HatchStyle::Checkerboard => (base_size * 2.0, base_size * 2.0),
+            HatchStyle::Hexagonal => (base_size * 3.0, base_size * 2.6),
+            HatchStyle::Circles | HatchStyle::ConcentricCircles => {
+                (base_size * 2.0, base_size * 2.0)
+            }
+            HatchStyle::Scales => (base_size * 2.0, base_size * 2.0),
             HatchStyle::Wave => (base_size * 4.0, base_size * 2.0),
             HatchStyle::Zigzag => (base_size * 4.0, base_size),
             HatchStyle::Spiral => (base_size * 4.0, base_size * 4.0),
             HatchStyle::WoodGrain => (base_size * 8.0, base_size * 2.0),
             HatchStyle::Custom(_) => (base_size, base_size), // Default size for custom patterns
+            HatchStyle::Described(ref description) => description.size.unwrap_or((base_size, base_size)),
             _ => (base_size, base_size),
         }
     }
@@ -562,11 +1595,11 @@ impl HatchingManager {
         config: &HatchConfig,
         width: f32,
         height: f32,
-    ) -> Vec<Operation> {
+    ) -> crate::Result<Vec<Operation>> {
         let mut ops = Vec::new();
 
-        // Add background if specified
-        if let Some((r, g, b)) = config.background {
+        // Add background if specified; uncolored patterns only paint in the color they are used with
+        if let Some((r, g, b)) = config.background.filter(|_| !config.uncolored) {
             ops.push(Operation::new("rg", vec![r.into(), g.into(), b.into()]));
             ops.push(Operation::new(
                 "re",
@@ -600,14 +1633,50 @@ impl HatchingManager {
                     }
                 }
             }
-            return ops;
+            // Color operators are not allowed in uncolored pattern content
+            if config.uncolored {
+                ops.retain(|op| !COLOR_OPERATORS.contains(&op.operator.as_str()));
+            }
+            return Ok(ops);
         }
 
-        // Set line width and color
+        if let HatchStyle::Described(ref description) = config.style {
+            ops.extend(description.operations(width, height)?);
+            if config.uncolored {
+                ops.retain(|op| !COLOR_OPERATORS.contains(&op.operator.as_str()));
+            }
+            return Ok(ops);
+        }
+
+        // Line families are already rotated; lines crossing into the tile from its
+        // neighbours are included so strokes meet at the tile edges
+        if let Some(families) = config.line_families() {
+            let widest = families
+                .iter()
+                .map(|family| family.width.unwrap_or(config.line_width))
+                .fold(config.line_width, f32::max);
+            let margin = widest + 1.0;
+            let tile = vec![
+                (-margin, -margin),
+                (width + margin, -margin),
+                (width + margin, height + margin),
+                (-margin, height + margin),
+            ];
+            ops.extend(line_family_operations(&families, &[tile], FillRule::NonZero, config));
+            return Ok(ops);
+        }
+
+        // Set line width, dash and color
         ops.push(Operation::new("w", vec![config.line_width.into()]));
-        let (r, g, b) = config.color;
-        ops.push(Operation::new("RG", vec![r.into(), g.into(), b.into()]));
-        ops.push(Operation::new("rg", vec![r.into(), g.into(), b.into()]));
+        if let Some((ref pattern, phase)) = config.dash {
+            let array: Vec<Object> = pattern.iter().map(|&v| v.into()).collect();
+            ops.push(Operation::new("d", vec![Object::Array(array), phase.into()]));
+        }
+        if !config.uncolored {
+            let (r, g, b) = config.color;
+            ops.push(Operation::new("RG", vec![r.into(), g.into(), b.into()]));
+            ops.push(Operation::new("rg", vec![r.into(), g.into(), b.into()]));
+        }
 
         // Apply rotation if specified
         if config.angle != 0.0 {
@@ -629,42 +1698,32 @@ impl HatchingManager {
 
         // Generate pattern-specific operations
         match config.style {
-            HatchStyle::DiagonalRight => self.diagonal_right_ops(&mut ops, width, height),
-            HatchStyle::DiagonalLeft => self.diagonal_left_ops(&mut ops, width, height),
-            HatchStyle::Horizontal => self.horizontal_ops(&mut ops, width, height),
-            HatchStyle::Vertical => self.vertical_ops(&mut ops, width, height),
-            HatchStyle::Cross => self.cross_ops(&mut ops, width, height),
-            HatchStyle::DiagonalCross => self.diagonal_cross_ops(&mut ops, width, height),
             HatchStyle::Dots => self.dots_ops(&mut ops, width, height, config.spacing),
             HatchStyle::Checkerboard => self.checkerboard_ops(&mut ops, width, height),
-            HatchStyle::Brick => self.brick_ops(&mut ops, width, height),
             HatchStyle::Hexagonal => self.hexagonal_ops(&mut ops, width, height),
             HatchStyle::Wave => self.wave_ops(&mut ops, width, height),
             HatchStyle::Zigzag => self.zigzag_ops(&mut ops, width, height),
             HatchStyle::Circles => self.circles_ops(&mut ops, width, height),
-            HatchStyle::Triangles => self.triangles_ops(&mut ops, width, height),
-            HatchStyle::Diamond => self.diamond_ops(&mut ops, width, height),
             HatchStyle::Scales => self.scales_ops(&mut ops, width, height),
             HatchStyle::Spiral => self.spiral_ops(&mut ops, width, height),
             HatchStyle::DottedGrid => self.dotted_grid_ops(&mut ops, width, height),
             HatchStyle::ConcentricCircles => self.concentric_circles_ops(&mut ops, width, height),
             HatchStyle::WoodGrain => self.wood_grain_ops(&mut ops, width, height),
-            HatchStyle::Custom(_) => {} // Custom patterns are handled above
+            HatchStyle::Custom(_)
+            | HatchStyle::DiagonalRight
+            | HatchStyle::DiagonalLeft
+            | HatchStyle::Horizontal
+            | HatchStyle::Vertical
+            | HatchStyle::Cross
+            | HatchStyle::DiagonalCross
+            | HatchStyle::Brick
+            | HatchStyle::Triangles
+            | HatchStyle::Diamond
+            | HatchStyle::LineFamilies(_)
+            | HatchStyle::Described(_) => {} // Handled above
         }
 
-        ops
-    }
-
-    fn diagonal_right_ops(&self, ops: &mut Vec<Operation>, width: f32, height: f32) {
-        ops.push(Operation::new("m", vec![0.into(), 0.into()]));
-        ops.push(Operation::new("l", vec![width.into(), height.into()]));
-        ops.push(Operation::new("S", vec![]));
-    }
-
-    fn diagonal_left_ops(&self, ops: &mut Vec<Operation>, width: f32, height: f32) {
-        ops.push(Operation::new("m", vec![0.into(), height.into()]));
-        ops.push(Operation::new("l", vec![width.into(), 0.into()]));
-        ops.push(Operation::new("S", vec![]));
+        Ok(ops)
     }
 
     fn horizontal_ops(&self, ops: &mut Vec<Operation>, width: f32, height: f32) {
@@ -690,11 +1749,6 @@ impl HatchingManager {
         self.vertical_ops(ops, width, height);
     }
 
-    fn diagonal_cross_ops(&self, ops: &mut Vec<Operation>, width: f32, height: f32) {
-        self.diagonal_right_ops(ops, width, height);
-        self.diagonal_left_ops(ops, width, height);
-    }
-
     fn dots_ops(&self, ops: &mut Vec<Operation>, width: f32, height: f32, spacing: f32) {
         let radius = spacing * 0.2;
         self.circle_at(ops, width / 2.0, height / 2.0, radius);
@@ -723,34 +1777,6 @@ impl HatchingManager {
         ops.push(Operation::new("f", vec![]));
     }
 
-    fn brick_ops(&self, ops: &mut Vec<Operation>, width: f32, height: f32) {
-        // Horizontal lines
-        ops.push(Operation::new("m", vec![0.into(), (height / 2.0).into()]));
-        ops.push(Operation::new(
-            "l",
-            vec![width.into(), (height / 2.0).into()],
-        ));
-        ops.push(Operation::new("S", vec![]));
-
-        // Vertical lines (staggered)
-        ops.push(Operation::new("m", vec![(width / 4.0).into(), 0.into()]));
-        ops.push(Operation::new(
-            "l",
-            vec![(width / 4.0).into(), (height / 2.0).into()],
-        ));
-        ops.push(Operation::new("S", vec![]));
-
-        ops.push(Operation::new(
-            "m",
-            vec![(width * 3.0 / 4.0).into(), (height / 2.0).into()],
-        ));
-        ops.push(Operation::new(
-            "l",
-            vec![(width * 3.0 / 4.0).into(), height.into()],
-        ));
-        ops.push(Operation::new("S", vec![]));
-    }
-
     fn hexagonal_ops(&self, ops: &mut Vec<Operation>, width: f32, height: f32) {
         let cx = width / 2.0;
         let cy = height / 2.0;
@@ -806,28 +1832,6 @@ impl HatchingManager {
         ops.push(Operation::new("S", vec![]));
     }
 
-    fn triangles_ops(&self, ops: &mut Vec<Operation>, width: f32, height: f32) {
-        ops.push(Operation::new("m", vec![(width / 2.0).into(), 0.into()]));
-        ops.push(Operation::new("l", vec![0.into(), height.into()]));
-        ops.push(Operation::new("l", vec![width.into(), height.into()]));
-        ops.push(Operation::new("h", vec![]));
-        ops.push(Operation::new("S", vec![]));
-    }
-
-    fn diamond_ops(&self, ops: &mut Vec<Operation>, width: f32, height: f32) {
-        ops.push(Operation::new("m", vec![(width / 2.0).into(), 0.into()]));
-        ops.push(Operation::new(
-            "l",
-            vec![width.into(), (height / 2.0).into()],
-        ));
-        ops.push(Operation::new(
-            "l",
-            vec![(width / 2.0).into(), height.into()],
-        ));
-        ops.push(Operation::new("l", vec![0.into(), (height / 2.0).into()]));
-        ops.push(Operation::new("h", vec![]));
-        ops.push(Operation::new("S", vec![]));
-    }
 
     fn scales_ops(&self, ops: &mut Vec<Operation>, width: f32, height: f32) {
         let r = width / 2.0;
@@ -987,9 +1991,6 @@ impl HatchingManager {
         height: f32,
         builder_fn: impl FnOnce(&mut CustomPatternBuilder) -> &mut CustomPatternBuilder,
     ) -> (ObjectId, String) {
-        self.pattern_counter += 1;
-        let pattern_name = format!("P{}", self.pattern_counter);
-
         let mut builder = CustomPatternBuilder::new();
         builder_fn(&mut builder);
         let operations = builder.build();
@@ -1007,12 +2008,13 @@ impl HatchingManager {
         };
 
         let pattern_stream = Stream::new(pattern_dict, content.encode().unwrap());
-        let pattern_id = doc.add_object(pattern_stream);
-
-        (pattern_id, pattern_name)
+        self.add_cached(doc, "Pattern", "P", Object::Stream(pattern_stream))
     }
 
-    /// Generate procedural pattern operations
+    /// Generate procedural pattern operations.
+    ///
+    /// Filled samples are traced into merged regions and painted with a single fill.
+    /// The sample grid wraps around, so regions continue seamlessly into the next tile.
     fn generate_procedural_pattern(
         &self,
         proc: &ProceduralPattern,
@@ -1020,37 +2022,140 @@ impl HatchingManager {
         height: f32,
     ) -> Vec<Operation> {
         let mut ops = Vec::new();
+        if proc.resolution == 0 {
+            return ops;
+        }
+        let resolution = proc.resolution as i64;
         let step = width.min(height) / proc.resolution as f32;
 
-        for i in 0..proc.resolution {
-            for j in 0..proc.resolution {
-                let x = i as f32 * step;
-                let y = j as f32 * step;
-                let t =
-                    (i as f32 / proc.resolution as f32 + j as f32 / proc.resolution as f32) / 2.0;
-
-                if (proc.sampler)(x, y, t) {
-                    if proc.fill {
-                        ops.push(Operation::new(
-                            "re",
-                            vec![x.into(), y.into(), step.into(), step.into()],
-                        ));
-                        ops.push(Operation::new("f", vec![]));
-                    } else {
-                        let cx = x + step / 2.0;
-                        let cy = y + step / 2.0;
+        let samples: Vec<bool> = (0..resolution * resolution)
+            .map(|index| {
+                let (i, j) = (index / resolution, index % resolution);
+                let t = (i as f32 / proc.resolution as f32 + j as f32 / proc.resolution as f32) / 2.0;
+                (proc.sampler)(i as f32 * step, j as f32 * step, t)
+            })
+            .collect();
+        let filled = |i: i64, j: i64| samples[(i.rem_euclid(resolution) * resolution + j.rem_euclid(resolution)) as usize];
+
+        if !proc.fill {
+            for i in 0..resolution {
+                for j in 0..resolution {
+                    if filled(i, j) {
+                        let cx = i as f32 * step + step / 2.0;
+                        let cy = j as f32 * step + step / 2.0;
                         let r = step * 0.3;
                         self.circle_at(&mut ops, cx, cy, r);
                         ops.push(Operation::new("f", vec![]));
                     }
                 }
             }
+            return ops;
+        }
+
+        // Samples sit at cell centers; one wrapped ring around the tile carries regions over its edges
+        let position = |(a, b): (i64, i64)| ((a as f32 + 1.0) * step / 2.0, (b as f32 + 1.0) * step / 2.0);
+        for outline in trace_regions(-1..resolution + 1, filled) {
+            let outline = if proc.smooth { outline } else { merge_straight_runs(&outline) };
+            if outline.len() < 3 {
+                continue;
+            }
+            let points: Vec<(f32, f32)> = outline.into_iter().map(position).collect();
+            if proc.smooth {
+                // Quadratic B-spline through the edge midpoints, as cubic curves
+                let mid = |p: (f32, f32), q: (f32, f32)| ((p.0 + q.0) / 2.0, (p.1 + q.1) / 2.0);
+                let toward = |from: (f32, f32), to: (f32, f32)| {
+                    (from.0 + (to.0 - from.0) * 2.0 / 3.0, from.1 + (to.1 - from.1) * 2.0 / 3.0)
+                };
+                let count = points.len();
+                let start = mid(points[count - 1], points[0]);
+                ops.push(Operation::new("m", vec![start.0.into(), start.1.into()]));
+                for (index, &corner) in points.iter().enumerate() {
+                    let from = mid(points[(index + count - 1) % count], corner);
+                    let to = mid(corner, points[(index + 1) % count]);
+                    let (c1, c2) = (toward(from, corner), toward(to, corner));
+                    ops.push(Operation::new(
+                        "c",
+                        vec![c1.0.into(), c1.1.into(), c2.0.into(), c2.1.into(), to.0.into(), to.1.into()],
+                    ));
+                }
+            } else {
+                ops.push(Operation::new("m", vec![points[0].0.into(), points[0].1.into()]));
+                for point in &points[1..] {
+                    ops.push(Operation::new("l", vec![point.0.into(), point.1.into()]));
+                }
+            }
+            ops.push(Operation::new("h", vec![]));
+        }
+        if !ops.is_empty() {
+            ops.push(Operation::new("f", vec![]));
         }
 
         ops
     }
 }
 
+/// Outlines of the filled samples in a square grid, traced with marching squares.
+///
+/// Samples sit at integer positions and samples outside `range` are empty. Points are
+/// returned doubled, so crossings halfway between samples stay exact. Loops run
+/// counter-clockwise around filled regions and clockwise around holes.
+fn trace_regions(range: std::ops::Range<i64>, filled: impl Fn(i64, i64) -> bool) -> Vec<Vec<(i64, i64)>> {
+    let inside = |i: i64, j: i64| range.contains(&i) && range.contains(&j) && filled(i, j);
+    // Ordered so outlines always start from the same point and identical configurations
+    // produce identical content, which pattern reuse depends on
+    let mut next: BTreeMap<(i64, i64), (i64, i64)> = BTreeMap::new();
+
+    for i in range.start - 1..range.end {
+        for j in range.start - 1..range.end {
+            // Corners counter-clockwise from the bottom left
+            let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+            let values = corners.map(|(x, y)| inside(x, y));
+            let crossings: Vec<((i64, i64), bool)> = (0..4)
+                .filter(|&k| values[k] != values[(k + 1) % 4])
+                .map(|k| {
+                    let (p, q) = (corners[k], corners[(k + 1) % 4]);
+                    ((p.0 + q.0, p.1 + q.1), values[k])
+                })
+                .collect();
+
+            // Leaving a filled corner, the outline runs to the next crossing, which keeps
+            // the filled side on its left and joins diagonal samples through the center
+            for (index, &(point, leaving)) in crossings.iter().enumerate() {
+                if leaving {
+                    next.insert(point, crossings[(index + 1) % crossings.len()].0);
+                }
+            }
+        }
+    }
+
+    let mut outlines = Vec::new();
+    while let Some((&start, _)) = next.first_key_value() {
+        let mut outline = vec![start];
+        let mut point = next.remove(&start).unwrap_or(start);
+        while point != start {
+            outline.push(point);
+            point = match next.remove(&point) {
+                Some(following) => following,
+                None => break,
+            };
+        }
+        outlines.push(outline);
+    }
+    outlines
+}
+
+/// The corners of an outline, dropping points in the middle of straight runs
+fn merge_straight_runs(outline: &[(i64, i64)]) -> Vec<(i64, i64)> {
+    let count = outline.len();
+    (0..count)
+        .filter(|&index| {
+            let (p, q, r) = (outline[(index + count - 1) % count], outline[index], outline[(index + 1) % count]);
+            (q.0 - p.0) * (r.1 - q.1) != (q.1 - p.1) * (r.0 - q.0)
+        })
+        .map(|index| outline[index])
+        .collect()
+}
+
 /// Helper functions for using patterns in content streams
 pub struct PatternOperations;
 
@@ -1074,11 +2179,624 @@ impl PatternOperations {
     pub fn set_stroke_pattern(pattern_name: &str) -> Operation {
         Operation::new("SCN", vec![Object::Name(pattern_name.as_bytes().to_vec())])
     }
+
+    /// Sets the fill color space for uncolored patterns; the page resources need
+    /// [`HatchingManager::add_uncolored_colorspace_to_resources`]
+    pub fn set_uncolored_pattern_fill_colorspace() -> Operation {
+        Operation::new("cs", vec![Object::Name(UNCOLORED_PATTERN_COLORSPACE.as_bytes().to_vec())])
+    }
+
+    /// Sets the stroke color space for uncolored patterns
+    pub fn set_uncolored_pattern_stroke_colorspace() -> Operation {
+        Operation::new("CS", vec![Object::Name(UNCOLORED_PATTERN_COLORSPACE.as_bytes().to_vec())])
+    }
+
+    /// Paints a shading over the current clipping area
+    pub fn paint_shading(shading_name: &str) -> Operation {
+        Operation::new("sh", vec![Object::Name(shading_name.as_bytes().to_vec())])
+    }
+
+    /// Sets an uncolored fill pattern and the RGB color it paints in
+    pub fn set_fill_pattern_with_color(pattern_name: &str, (r, g, b): (f32, f32, f32)) -> Operation {
+        Operation::new(
+            "scn",
+            vec![r.into(), g.into(), b.into(), Object::Name(pattern_name.as_bytes().to_vec())],
+        )
+    }
+
+    /// Sets an uncolored stroke pattern and the RGB color it paints in
+    pub fn set_stroke_pattern_with_color(pattern_name: &str, (r, g, b): (f32, f32, f32)) -> Operation {
+        Operation::new(
+            "SCN",
+            vec![r.into(), g.into(), b.into(), Object::Name(pattern_name.as_bytes().to_vec())],
+        )
+    }
+}
+
+/// A gradient color at a position between 0.0 (start) and 1.0 (end)
+pub type ColorStop = (f32, (f32, f32, f32));
+
+/// A mesh vertex: position and RGB color
+pub type MeshVertex = ((f32, f32), (f32, f32, f32));
+
+/// A Coons patch of a type 6 mesh shading
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoonsPatch {
+    /// Boundary control points going around the patch from a corner: each side is a
+    /// corner followed by two Bézier control points
+    pub points: [(f32, f32); 12],
+    /// Colors at the corners, points 1, 4, 7 and 10
+    pub colors: [(f32, f32, f32); 4],
+}
+
+impl CoonsPatch {
+    /// A rectangular patch blending between corner colors given bottom-left, top-left,
+    /// top-right and bottom-right
+    pub fn rectangle(x: f32, y: f32, width: f32, height: f32, colors: [(f32, f32, f32); 4]) -> Self {
+        let corners = [(x, y), (x, y + height), (x + width, y + height), (x + width, y)];
+        let mut points = [(0.0, 0.0); 12];
+        for (side, &corner) in corners.iter().enumerate() {
+            let next = corners[(side + 1) % 4];
+            let third = |t: f32| (corner.0 + (next.0 - corner.0) * t, corner.1 + (next.1 - corner.1) * t);
+            points[side * 3] = corner;
+            points[side * 3 + 1] = third(1.0 / 3.0);
+            points[side * 3 + 2] = third(2.0 / 3.0);
+        }
+        CoonsPatch { points, colors }
+    }
+}
+
+/// A smooth color transition, painted with the `sh` operator or as a shading pattern
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shading {
+    /// Linear gradient along the line from start to end
+    Axial {
+        start: (f32, f32),
+        end: (f32, f32),
+        stops: Vec<ColorStop>,
+        /// Whether to continue the end colors before the start and past the end
+        extend: (bool, bool),
+    },
+    /// Gradient between two circles
+    Radial {
+        start: (f32, f32),
+        start_radius: f32,
+        end: (f32, f32),
+        end_radius: f32,
+        stops: Vec<ColorStop>,
+        /// Whether to continue the end colors inside the start and outside the end circle
+        extend: (bool, bool),
+    },
+    /// Free-form triangles with colors blended between their vertices (type 4)
+    TriangleMesh(Vec<[MeshVertex; 3]>),
+    /// Coons patches with colors blended between their corners (type 6)
+    CoonsPatches(Vec<CoonsPatch>),
+}
+
+impl Shading {
+    /// Linear gradient, extended past both ends
+    pub fn axial(start: (f32, f32), end: (f32, f32), stops: Vec<ColorStop>) -> Self {
+        Shading::Axial {
+            start,
+            end,
+            stops,
+            extend: (true, true),
+        }
+    }
+
+    /// Gradient from the center out to a circle, extended past the circle
+    pub fn radial(center: (f32, f32), radius: f32, stops: Vec<ColorStop>) -> Self {
+        Shading::Radial {
+            start: center,
+            start_radius: 0.0,
+            end: center,
+            end_radius: radius,
+            stops,
+            extend: (true, true),
+        }
+    }
+
+    /// Builder method to set whether the gradient extends past its start and end
+    pub fn with_extend(mut self, before: bool, after: bool) -> Self {
+        if let Shading::Axial { ref mut extend, .. } | Shading::Radial { ref mut extend, .. } = self {
+            *extend = (before, after);
+        }
+        self
+    }
+
+    /// Encodes the shading as a dictionary (gradients) or stream (meshes)
+    fn to_object(&self) -> crate::Result<Object> {
+        let gradient = |shading_type: i32, coords: Vec<f32>, stops: &[ColorStop], extend: (bool, bool)| {
+            Ok(Object::Dictionary(dictionary! {
+                "ShadingType" => shading_type,
+                "ColorSpace" => "DeviceRGB",
+                "Coords" => coords.into_iter().map(Object::Real).collect::<Vec<Object>>(),
+                "Function" => stops_function(stops)?,
+                "Extend" => vec![extend.0.into(), extend.1.into()],
+            }))
+        };
+
+        match self {
+            Shading::Axial { start, end, stops, extend } => {
+                gradient(2, vec![start.0, start.1, end.0, end.1], stops, *extend)
+            }
+            Shading::Radial { start, start_radius, end, end_radius, stops, extend } => gradient(
+                3,
+                vec![start.0, start.1, *start_radius, end.0, end.1, *end_radius],
+                stops,
+                *extend,
+            ),
+            Shading::TriangleMesh(triangles) => {
+                let records: Vec<MeshRecord> = triangles
+                    .iter()
+                    .flatten()
+                    .map(|&(point, color)| (vec![point], vec![color]))
+                    .collect();
+                mesh_stream(4, &records)
+            }
+            Shading::CoonsPatches(patches) => {
+                let records: Vec<MeshRecord> = patches
+                    .iter()
+                    .map(|patch| (patch.points.to_vec(), patch.colors.to_vec()))
+                    .collect();
+                mesh_stream(6, &records)
+            }
+        }
+    }
+}
+
+/// Interpolation function through color stops: a single exponential function for two
+/// stops, otherwise exponential functions stitched together (type 3)
+fn stops_function(stops: &[ColorStop]) -> crate::Result<Object> {
+    let mut stops: Vec<ColorStop> = stops
+        .iter()
+        .filter(|(offset, _)| offset.is_finite())
+        .map(|&(offset, color)| (offset.clamp(0.0, 1.0), color))
+        .collect();
+    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let (first, last) = match (stops.first(), stops.last()) {
+        (Some(&first), Some(&last)) => (first, last),
+        _ => return Err("A gradient needs at least one color stop".into()),
+    };
+    if stops.len() == 1 {
+        // A single stop is a flat color
+        stops = vec![(0.0, first.1), (1.0, first.1)];
+    } else {
+        // Hold the outer colors up to the ends of the gradient
+        if first.0 > 0.0 {
+            stops.insert(0, (0.0, first.1));
+        }
+        if last.0 < 1.0 {
+            stops.push((1.0, last.1));
+        }
+    }
+
+    let color = |(r, g, b): (f32, f32, f32)| vec![Object::Real(r), Object::Real(g), Object::Real(b)];
+    let mut functions: Vec<Object> = stops
+        .windows(2)
+        .map(|pair| {
+            Object::Dictionary(dictionary! {
+                "FunctionType" => 2,
+                "Domain" => vec![0.into(), 1.into()],
+                "C0" => color(pair[0].1),
+                "C1" => color(pair[1].1),
+                "N" => 1,
+            })
+        })
+        .collect();
+    if functions.len() == 1 {
+        return Ok(functions.remove(0));
+    }
+
+    let bounds: Vec<Object> = stops[1..stops.len() - 1].iter().map(|stop| Object::Real(stop.0)).collect();
+    let encode: Vec<Object> = functions.iter().flat_map(|_| [0.into(), 1.into()]).collect();
+    Ok(Object::Dictionary(dictionary! {
+        "FunctionType" => 3,
+        "Domain" => vec![0.into(), 1.into()],
+        "Functions" => functions,
+        "Bounds" => bounds,
+        "Encode" => encode,
+    }))
+}
+
+/// Positions and colors written after one edge flag of a mesh shading
+type MeshRecord = (Vec<(f32, f32)>, Vec<(f32, f32, f32)>);
+
+/// Packs mesh records into a shading stream. Every record gets edge flag 0, so each
+/// triangle vertex or patch stands alone instead of sharing an edge with the previous one.
+fn mesh_stream(shading_type: i32, records: &[MeshRecord]) -> crate::Result<Object> {
+    if records.is_empty() {
+        return Err("A mesh shading needs at least one triangle or patch".into());
+    }
+
+    let positions: Vec<(f32, f32)> = records.iter().flat_map(|record| record.0.iter().copied()).collect();
+    let range = |values: &mut dyn Iterator<Item = f32>| {
+        let (min, max) = values.fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+        if max > min { (min, max) } else { (min, min + 1.0) }
+    };
+    let (min_x, max_x) = range(&mut positions.iter().map(|point| point.0));
+    let (min_y, max_y) = range(&mut positions.iter().map(|point| point.1));
+
+    let coordinate = |value: f32, min: f32, max: f32| {
+        let scaled = (value - min) as f64 / (max - min) as f64 * u32::MAX as f64;
+        (scaled.round().clamp(0.0, u32::MAX as f64) as u32).to_be_bytes()
+    };
+    let component = |value: f32| ((value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16).to_be_bytes();
+    let mut data = Vec::new();
+
+    for (points, colors) in records {
+        data.push(0);
+        for &(x, y) in points {
+            data.extend(coordinate(x, min_x, max_x));
+            data.extend(coordinate(y, min_y, max_y));
+        }
+        for &(r, g, b) in colors {
+            data.extend(component(r).into_iter().chain(component(g)).chain(component(b)));
+        }
+    }
+
+    let dict = dictionary! {
+        "ShadingType" => shading_type,
+        "ColorSpace" => "DeviceRGB",
+        "BitsPerCoordinate" => 32,
+        "BitsPerComponent" => 16,
+        "BitsPerFlag" => 8,
+        "Decode" => vec![
+            min_x.into(), max_x.into(), min_y.into(), max_y.into(),
+            0.into(), 1.into(), 0.into(), 1.into(), 0.into(), 1.into(),
+        ],
+    };
+    Ok(Object::Stream(Stream::new(dict, data)))
+}
+
+/// Rule deciding which parts of a boundary with several loops are inside
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillRule {
+    /// Inside where the loops wind around a point a nonzero number of times
+    #[default]
+    NonZero,
+    /// Inside where a point is enclosed by an odd number of loops
+    EvenOdd,
+}
+
+impl FillRule {
+    /// The path-filling operator for this rule
+    pub fn fill_operator(&self) -> &'static str {
+        match self {
+            FillRule::NonZero => "f",
+            FillRule::EvenOdd => "f*",
+        }
+    }
+
+    /// The clipping operator for this rule
+    pub fn clip_operator(&self) -> &'static str {
+        match self {
+            FillRule::NonZero => "W",
+            FillRule::EvenOdd => "W*",
+        }
+    }
+}
+
+/// A piece of a boundary loop, continuing from where the previous piece ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum BoundarySegment {
+    /// Straight line to a point
+    Line { to: (f32, f32) },
+    /// Circular arc, counterclockwise when `end_angle` is greater than `start_angle`.
+    /// Angles are in degrees; a line joins the previous point to the arc's start.
+    Arc {
+        center: (f32, f32),
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+    },
+    /// Cubic Bézier curve to a point
+    Curve {
+        control1: (f32, f32),
+        control2: (f32, f32),
+        to: (f32, f32),
+    },
+}
+
+impl BoundarySegment {
+    /// The point where the segment starts drawing, if it does not continue the previous one
+    fn start(&self) -> Option<(f32, f32)> {
+        match *self {
+            BoundarySegment::Arc { center, radius, start_angle, .. } => {
+                Some(point_on_circle(center, radius, start_angle))
+            }
+            _ => None,
+        }
+    }
+
+    fn end(&self) -> (f32, f32) {
+        match *self {
+            BoundarySegment::Line { to } | BoundarySegment::Curve { to, .. } => to,
+            BoundarySegment::Arc { center, radius, end_angle, .. } => {
+                point_on_circle(center, radius, end_angle)
+            }
+        }
+    }
+}
+
+/// A closed loop of a hatch boundary, such as a room outline or a column cutout
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundaryLoop {
+    pub start: (f32, f32),
+    pub segments: Vec<BoundarySegment>,
+}
+
+impl BoundaryLoop {
+    /// Starts a loop at a point
+    pub fn new(x: f32, y: f32) -> Self {
+        BoundaryLoop {
+            start: (x, y),
+            segments: Vec::new(),
+        }
+    }
+
+    /// A loop through the given points
+    pub fn polyline(points: &[(f32, f32)]) -> Self {
+        let (first, rest) = points.split_first().unwrap_or((&(0.0, 0.0), &[]));
+        rest.iter()
+            .fold(BoundaryLoop::new(first.0, first.1), |boundary, &(x, y)| boundary.line_to(x, y))
+    }
+
+    pub fn rectangle(x: f32, y: f32, width: f32, height: f32) -> Self {
+        BoundaryLoop::polyline(&[(x, y), (x + width, y), (x + width, y + height), (x, y + height)])
+    }
+
+    pub fn circle(cx: f32, cy: f32, r: f32) -> Self {
+        BoundaryLoop::new(cx + r, cy).arc(cx, cy, r, 0.0, 360.0)
+    }
+
+    pub fn line_to(mut self, x: f32, y: f32) -> Self {
+        self.segments.push(BoundarySegment::Line { to: (x, y) });
+        self
+    }
+
+    /// Adds a circular arc; angles are in degrees, counterclockwise when `end_angle > start_angle`
+    pub fn arc(mut self, cx: f32, cy: f32, radius: f32, start_angle: f32, end_angle: f32) -> Self {
+        self.segments.push(BoundarySegment::Arc {
+            center: (cx, cy),
+            radius,
+            start_angle,
+            end_angle,
+        });
+        self
+    }
+
+    pub fn curve_to(mut self, cx1: f32, cy1: f32, cx2: f32, cy2: f32, x: f32, y: f32) -> Self {
+        self.segments.push(BoundarySegment::Curve {
+            control1: (cx1, cy1),
+            control2: (cx2, cy2),
+            to: (x, y),
+        });
+        self
+    }
+
+    /// The same loop traversed in the opposite direction
+    pub fn reversed(&self) -> Self {
+        let mut current = self.start;
+        let mut pieces = Vec::with_capacity(self.segments.len());
+        for segment in &self.segments {
+            if let Some(start) = segment.start() {
+                pieces.push((current, BoundarySegment::Line { to: start }));
+            }
+            pieces.push((current, segment.clone()));
+            current = segment.end();
+        }
+
+        let mut reversed = BoundaryLoop::new(current.0, current.1);
+        for (from, segment) in pieces.into_iter().rev() {
+            reversed.segments.push(match segment {
+                BoundarySegment::Line { .. } => BoundarySegment::Line { to: from },
+                BoundarySegment::Arc { center, radius, start_angle, end_angle } => BoundarySegment::Arc {
+                    center,
+                    radius,
+                    start_angle: end_angle,
+                    end_angle: start_angle,
+                },
+                BoundarySegment::Curve { control1, control2, .. } => BoundarySegment::Curve {
+                    control1: control2,
+                    control2: control1,
+                    to: from,
+                },
+            });
+        }
+        reversed
+    }
+
+    /// Enclosed area, positive when the loop runs counterclockwise
+    pub fn signed_area(&self) -> f32 {
+        let points = self.flatten(0.1);
+        let n = points.len();
+        (0..n)
+            .map(|i| {
+                let (x0, y0) = points[i];
+                let (x1, y1) = points[(i + 1) % n];
+                x0 * y1 - x1 * y0
+            })
+            .sum::<f32>()
+            / 2.0
+    }
+
+    /// Approximates the loop by a polygon whose distance from the curves stays within `tolerance`
+    pub fn flatten(&self, tolerance: f32) -> Vec<(f32, f32)> {
+        let tolerance = tolerance.max(1e-4);
+        let mut points = vec![self.start];
+        let mut current = self.start;
+        for segment in &self.segments {
+            match *segment {
+                BoundarySegment::Line { to } => points.push(to),
+                BoundarySegment::Arc { center, radius, start_angle, end_angle } => {
+                    let sweep = (end_angle - start_angle).to_radians();
+                    let max_step = 2.0 * (1.0 - tolerance / radius.max(tolerance)).clamp(-1.0, 1.0).acos();
+                    let steps = (sweep.abs() / max_step.max(1e-3)).ceil().clamp(1.0, 1024.0) as usize;
+                    for step in 0..=steps {
+                        let angle = start_angle + (end_angle - start_angle) * step as f32 / steps as f32;
+                        points.push(point_on_circle(center, radius, angle));
+                    }
+                }
+                BoundarySegment::Curve { control1, control2, to } => {
+                    let hull = distance(current, control1) + distance(control1, control2) + distance(control2, to);
+                    let steps = (hull / tolerance).sqrt().ceil().clamp(1.0, 256.0) as usize;
+                    for step in 1..=steps {
+                        points.push(cubic_point(current, control1, control2, to, step as f32 / steps as f32));
+                    }
+                }
+            }
+            current = segment.end();
+        }
+
+        // Drop repeated points, including a closing point equal to the start
+        points.dedup_by(|a, b| distance(*a, *b) < 1e-6);
+        if points.len() > 1 && distance(points[0], points[points.len() - 1]) < 1e-6 {
+            points.pop();
+        }
+        points
+    }
+
+    /// Path construction operations for the loop, closed with `h`
+    pub fn to_operations(&self) -> Vec<Operation> {
+        let point = |(x, y): (f32, f32)| vec![x.into(), y.into()];
+        let mut ops = vec![Operation::new("m", point(self.start))];
+        for segment in &self.segments {
+            match *segment {
+                BoundarySegment::Line { to } => ops.push(Operation::new("l", point(to))),
+                BoundarySegment::Arc { center, radius, start_angle, end_angle } => {
+                    ops.push(Operation::new("l", point(point_on_circle(center, radius, start_angle))));
+                    // Béziers approximate arcs well up to a quarter turn
+                    let pieces = ((end_angle - start_angle).abs() / 90.0).ceil().max(1.0) as usize;
+                    let step = (end_angle - start_angle) / pieces as f32;
+                    for piece in 0..pieces {
+                        let from = start_angle + step * piece as f32;
+                        let (c1, c2, to) = arc_bezier(center, radius, from, from + step);
+                        ops.push(Operation::new(
+                            "c",
+                            vec![c1.0.into(), c1.1.into(), c2.0.into(), c2.1.into(), to.0.into(), to.1.into()],
+                        ));
+                    }
+                }
+                BoundarySegment::Curve { control1, control2, to } => ops.push(Operation::new(
+                    "c",
+                    vec![
+                        control1.0.into(),
+                        control1.1.into(),
+                        control2.0.into(),
+                        control2.1.into(),
+                        to.0.into(),
+                        to.1.into(),
+                    ],
+                )),
+            }
+        }
+        ops.push(Operation::new("h", vec![]));
+        ops
+    }
+}
+
+/// A region to hatch: one or more closed loops combined with a fill rule, like the
+/// boundary picked by a CAD HATCH command
+#[derive(Debug, Clone, PartialEq)]
+pub struct HatchBoundary {
+    pub loops: Vec<BoundaryLoop>,
+    pub fill_rule: FillRule,
+}
+
+impl HatchBoundary {
+    /// A boundary with a single outer loop
+    pub fn new(outer: BoundaryLoop) -> Self {
+        HatchBoundary {
+            loops: vec![outer],
+            fill_rule: FillRule::NonZero,
+        }
+    }
+
+    /// Adds an island, a loop left unhatched inside the outer loop.
+    ///
+    /// The island runs opposite to the outer loop so it stays a hole under the nonzero
+    /// rule; islands inside islands are hatched again, as with the even-odd rule.
+    pub fn with_island(mut self, island: BoundaryLoop) -> Self {
+        let outer_area = self.loops.first().map(BoundaryLoop::signed_area).unwrap_or(0.0);
+        let island = if outer_area * island.signed_area() > 0.0 {
+            island.reversed()
+        } else {
+            island
+        };
+        self.loops.push(island);
+        self
+    }
+
+    /// Adds a loop as given, leaving its inside to the fill rule
+    pub fn with_loop(mut self, boundary_loop: BoundaryLoop) -> Self {
+        self.loops.push(boundary_loop);
+        self
+    }
+
+    pub fn with_fill_rule(mut self, fill_rule: FillRule) -> Self {
+        self.fill_rule = fill_rule;
+        self
+    }
+
+    /// Path construction operations for every loop, without a painting operator
+    pub fn to_operations(&self) -> Vec<Operation> {
+        self.loops.iter().flat_map(BoundaryLoop::to_operations).collect()
+    }
+
+    /// Bounding box of the flattened loops as (min_x, min_y, max_x, max_y)
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        self.loops
+            .iter()
+            .flat_map(|boundary_loop| boundary_loop.flatten(0.1))
+            .fold(
+                (f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+                |(x0, y0, x1, y1), (x, y)| (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            )
+    }
+}
+
+fn point_on_circle(center: (f32, f32), radius: f32, angle: f32) -> (f32, f32) {
+    let angle = angle.to_radians();
+    (center.0 + radius * angle.cos(), center.1 + radius * angle.sin())
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+fn cubic_point(p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), p3: (f32, f32), t: f32) -> (f32, f32) {
+    let u = 1.0 - t;
+    let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+    (
+        a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+        a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+    )
+}
+
+/// Control points and end point of a Bézier approximating an arc of at most 90 degrees
+fn arc_bezier(
+    center: (f32, f32),
+    radius: f32,
+    from: f32,
+    to: f32,
+) -> ((f32, f32), (f32, f32), (f32, f32)) {
+    let k = 4.0 / 3.0 * ((to - from).to_radians() / 4.0).tan() * radius;
+    let (a, b) = (from.to_radians(), to.to_radians());
+    let start = point_on_circle(center, radius, from);
+    let end = point_on_circle(center, radius, to);
+    (
+        (start.0 - k * a.sin(), start.1 + k * a.cos()),
+        (end.0 + k * b.sin(), end.1 - k * b.cos()),
+        end,
+    )
 }
 
 /// Convenience builder for creating shapes with patterns
 pub struct PatternedShapeBuilder {
     operations: Vec<Operation>,
+    /// Color for uncolored patterns, None for colored patterns
+    pattern_color: Option<(f32, f32, f32)>,
 }
 
 impl Default for PatternedShapeBuilder {
@@ -1091,6 +2809,31 @@ impl PatternedShapeBuilder {
     pub fn new() -> Self {
         PatternedShapeBuilder {
             operations: Vec::new(),
+            pattern_color: None,
+        }
+    }
+
+    /// Paints the following shapes' uncolored patterns in a color, or None for colored patterns
+    pub fn set_pattern_color(&mut self, color: Option<(f32, f32, f32)>) -> &mut Self {
+        self.pattern_color = color;
+        self
+    }
+
+    /// Selects the fill pattern for the next shape
+    fn select_pattern(&mut self, pattern_name: &str) {
+        match self.pattern_color {
+            Some(color) => {
+                self.operations
+                    .push(PatternOperations::set_uncolored_pattern_fill_colorspace());
+                self.operations
+                    .push(PatternOperations::set_fill_pattern_with_color(pattern_name, color));
+            }
+            None => {
+                self.operations
+                    .push(PatternOperations::set_pattern_fill_colorspace());
+                self.operations
+                    .push(PatternOperations::set_fill_pattern(pattern_name));
+            }
         }
     }
 
@@ -1103,10 +2846,7 @@ impl PatternedShapeBuilder {
         height: f32,
         pattern_name: &str,
     ) -> &mut Self {
-        self.operations
-            .push(PatternOperations::set_pattern_fill_colorspace());
-        self.operations
-            .push(PatternOperations::set_fill_pattern(pattern_name));
+        self.select_pattern(pattern_name);
         self.operations.push(Operation::new(
             "re",
             vec![x.into(), y.into(), width.into(), height.into()],
@@ -1117,10 +2857,7 @@ impl PatternedShapeBuilder {
 
     /// Circle with pattern fill
     pub fn circle(&mut self, cx: f32, cy: f32, r: f32, pattern_name: &str) -> &mut Self {
-        self.operations
-            .push(PatternOperations::set_pattern_fill_colorspace());
-        self.operations
-            .push(PatternOperations::set_fill_pattern(pattern_name));
+        self.select_pattern(pattern_name);
 
         let k = 0.552_284_8;
         self.operations
@@ -1174,6 +2911,7 @@ impl PatternedShapeBuilder {
     }
 
     /// Triangle with pattern fill
+    #[allow(clippy::too_many_arguments)]
     pub fn triangle(
         &mut self,
         x1: f32,
@@ -1184,10 +2922,7 @@ impl PatternedShapeBuilder {
         y3: f32,
         pattern_name: &str,
     ) -> &mut Self {
-        self.operations
-            .push(PatternOperations::set_pattern_fill_colorspace());
-        self.operations
-            .push(PatternOperations::set_fill_pattern(pattern_name));
+        self.select_pattern(pattern_name);
         self.operations
             .push(Operation::new("m", vec![x1.into(), y1.into()]));
         self.operations
@@ -1199,8 +2934,144 @@ impl PatternedShapeBuilder {
         self
     }
 
+    /// Hatches an arbitrary boundary, honoring its islands and fill rule
+    pub fn boundary(&mut self, boundary: &HatchBoundary, pattern_name: &str) -> &mut Self {
+        self.select_pattern(pattern_name);
+        self.operations.extend(boundary.to_operations());
+        self.operations
+            .push(Operation::new(boundary.fill_rule.fill_operator(), vec![]));
+        self
+    }
+
+    /// Paints a shading clipped to a boundary, honoring its islands and fill rule
+    pub fn shaded_boundary(&mut self, boundary: &HatchBoundary, shading_name: &str) -> &mut Self {
+        self.operations.push(Operation::new("q", vec![]));
+        self.operations.extend(boundary.to_operations());
+        self.operations
+            .push(Operation::new(boundary.fill_rule.clip_operator(), vec![]));
+        self.operations.push(Operation::new("n", vec![]));
+        self.operations
+            .push(PatternOperations::paint_shading(shading_name));
+        self.operations.push(Operation::new("Q", vec![]));
+        self
+    }
+
     /// Builds the operations
     pub fn build(self) -> Vec<Operation> {
         self.operations
     }
 }
+
+/// A hatch pattern read from an AutoCAD `.pat` file
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatPattern {
+    /// Pattern name from the `*NAME` header
+    pub name: String,
+    /// Description following the name, if any
+    pub description: String,
+    /// One line family per data line, in file units
+    pub families: Vec<HatchLineFamily>,
+}
+
+impl PatPattern {
+    /// The pattern as a hatch style; `HatchConfig` scale and angle apply on top
+    pub fn to_style(&self) -> HatchStyle {
+        HatchStyle::LineFamilies(self.families.clone())
+    }
+}
+
+/// A set of hatch patterns parsed from `.pat` definitions.
+///
+/// Pattern coordinates are kept in file units (inches or millimetres, depending on the
+/// file), so set `HatchConfig::scale` to convert them to points.
+#[derive(Debug, Clone, Default)]
+pub struct PatternLibrary {
+    patterns: Vec<PatPattern>,
+}
+
+impl PatternLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses `.pat` text: `*NAME, description` headers, each followed by
+    /// `angle, x, y, dx, dy[, dash...]` lines. Text after `;` is a comment.
+    pub fn parse(text: &str) -> crate::Result<Self> {
+        let mut library = PatternLibrary::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('*') {
+                let (name, description) = header.split_once(',').unwrap_or((header, ""));
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err(format!("Line {}: pattern header without a name", number + 1).into());
+                }
+                library.patterns.push(PatPattern {
+                    name: name.to_string(),
+                    description: description.trim().to_string(),
+                    families: Vec::new(),
+                });
+                continue;
+            }
+
+            let values = line
+                .split(',')
+                .map(|value| value.trim().parse::<f32>())
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|e| format!("Line {}: invalid number ({})", number + 1, e))?;
+            if values.len() < 5 {
+                return Err(format!(
+                    "Line {}: expected angle, origin and offset, found {} values",
+                    number + 1,
+                    values.len()
+                )
+                .into());
+            }
+            let pattern = library
+                .patterns
+                .last_mut()
+                .ok_or_else(|| format!("Line {}: line family before any pattern header", number + 1))?;
+            pattern.families.push(
+                HatchLineFamily::new(values[0], (values[1], values[2]), (values[3], values[4]))
+                    .with_dashes(values[5..].to_vec()),
+            );
+        }
+
+        Ok(library)
+    }
+
+    /// Reads and parses a `.pat` file
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> crate::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Adds the patterns of another library, replacing patterns with the same name
+    pub fn extend(&mut self, other: PatternLibrary) {
+        for pattern in other.patterns {
+            self.patterns.retain(|existing| !existing.name.eq_ignore_ascii_case(&pattern.name));
+            self.patterns.push(pattern);
+        }
+    }
+
+    /// Finds a pattern by name, ignoring case as AutoCAD does
+    pub fn get(&self, name: &str) -> Option<&PatPattern> {
+        self.patterns
+            .iter()
+            .find(|pattern| pattern.name.eq_ignore_ascii_case(name))
+    }
+
+    /// All patterns in file order
+    pub fn patterns(&self) -> &[PatPattern] {
+        &self.patterns
+    }
+
+    /// A hatch config drawing the named pattern with default color and line width
+    pub fn hatch_config(&self, name: &str) -> Option<HatchConfig> {
+        self.get(name).map(|pattern| HatchConfig::new(pattern.to_style()))
+    }
+}
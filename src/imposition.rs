@@ -0,0 +1,449 @@
+//! N-up and booklet imposition
+//!
+//! This module flows the pages of a loaded PDF across as many printer sheets as needed.
+//! Each sheet is divided into a grid of cells and every page is scaled into its cell
+//! with the same calculation [`PdfEmbedder`] uses for its layouts. Sheets can carry
+//! crop and registration marks, and booklets can compensate for creep.
+
+use crate::embed_pdf::{EmbedOptions, GridFillOrder, MultiPageLayout, PageBox, PdfEmbedder};
+use crate::pages;
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Dictionary, Document, Object, ObjectId, Stream,
+};
+use std::io::{Error, ErrorKind, Result};
+
+/// Bézier control point distance for approximating a quarter circle
+const KAPPA: f32 = 0.552_284_8;
+
+/// How source pages are assigned to the cells of a sheet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImpositionLayout {
+    /// Pages in reading order, `columns × rows` per sheet
+    NUp {
+        columns: usize,
+        rows: usize,
+        fill_order: GridFillOrder,
+    },
+    /// Saddle-stitch booklet with two pages side by side on each sheet side.
+    ///
+    /// Output pages alternate between the front and back of each physical sheet, ordered
+    /// so that the sheets read in sequence once nested and folded.
+    Booklet,
+}
+
+/// Options for imposing a PDF onto printer sheets
+#[derive(Debug, Clone)]
+pub struct ImpositionOptions {
+    /// How pages are assigned to cells
+    pub layout: ImpositionLayout,
+    /// Sheet size (width, height) in points
+    pub sheet_size: (f32, f32),
+    /// Space between the sheet edges and the cells, where marks are drawn
+    pub margin: f32,
+    /// Space between neighbouring cells (horizontal, vertical)
+    pub gutter: (f32, f32),
+    /// Fixed scale for every page, or None to fit each page into its cell
+    pub scale: Option<f32>,
+    /// Booklets only: shift toward the spine added per sheet, counted from the outer sheet
+    pub creep: f32,
+    /// Draw crop marks at the corners of every placed page
+    pub crop_marks: bool,
+    /// Draw registration targets in the middle of each sheet margin
+    pub registration_marks: bool,
+    /// Length of crop marks and diameter of registration targets
+    pub mark_length: f32,
+    /// Distance between a page corner and its crop marks
+    pub mark_offset: f32,
+    /// Which page boundary is placed in the cell
+    pub page_box: PageBox,
+}
+
+impl Default for ImpositionOptions {
+    fn default() -> Self {
+        ImpositionOptions {
+            layout: ImpositionLayout::NUp {
+                columns: 2,
+                rows: 1,
+                fill_order: GridFillOrder::RowFirst,
+            },
+            sheet_size: (842.0, 595.0), // A4 landscape
+            margin: 36.0,
+            gutter: (0.0, 0.0),
+            scale: None,
+            creep: 0.0,
+            crop_marks: false,
+            registration_marks: false,
+            mark_length: 12.0,
+            mark_offset: 3.0,
+            page_box: PageBox::TrimBox,
+        }
+    }
+}
+
+impl ImpositionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// N-up imposition in reading order
+    pub fn n_up(columns: usize, rows: usize) -> Self {
+        ImpositionOptions {
+            layout: ImpositionLayout::NUp {
+                columns,
+                rows,
+                fill_order: GridFillOrder::RowFirst,
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Saddle-stitch booklet imposition
+    pub fn booklet() -> Self {
+        ImpositionOptions {
+            layout: ImpositionLayout::Booklet,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_fill_order(mut self, order: GridFillOrder) -> Self {
+        if let ImpositionLayout::NUp {
+            ref mut fill_order, ..
+        } = self.layout
+        {
+            *fill_order = order;
+        }
+        self
+    }
+
+    pub fn with_sheet_size(mut self, width: f32, height: f32) -> Self {
+        self.sheet_size = (width, height);
+        self
+    }
+
+    pub fn with_margin(mut self, margin: f32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    pub fn with_gutter(mut self, horizontal: f32, vertical: f32) -> Self {
+        self.gutter = (horizontal, vertical);
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    pub fn with_creep(mut self, creep: f32) -> Self {
+        self.creep = creep;
+        self
+    }
+
+    pub fn with_crop_marks(mut self, enabled: bool) -> Self {
+        self.crop_marks = enabled;
+        self
+    }
+
+    pub fn with_registration_marks(mut self, enabled: bool) -> Self {
+        self.registration_marks = enabled;
+        self
+    }
+
+    pub fn with_marks(mut self, length: f32, offset: f32) -> Self {
+        self.mark_length = length;
+        self.mark_offset = offset;
+        self
+    }
+
+    pub fn with_page_box(mut self, page_box: PageBox) -> Self {
+        self.page_box = page_box;
+        self
+    }
+
+    /// Grid size (columns, rows) of every sheet
+    fn grid(&self) -> (usize, usize) {
+        match self.layout {
+            ImpositionLayout::NUp { columns, rows, .. } => (columns, rows),
+            ImpositionLayout::Booklet => (2, 1),
+        }
+    }
+}
+
+/// A source page assigned to a cell of a sheet
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Slot {
+    /// 0-indexed source page
+    page: usize,
+    /// Cell column, from the left
+    column: usize,
+    /// Cell row, from the top
+    row: usize,
+    /// Horizontal shift applied after placing the page
+    shift: f32,
+    /// Horizontal alignment inside the cell: 0.0 left, 0.5 centered, 1.0 right
+    align: f32,
+}
+
+/// Assign source pages to the cells of each output sheet, in output order
+fn plan_sheets(options: &ImpositionOptions, page_count: usize) -> Vec<Vec<Slot>> {
+    match options.layout {
+        ImpositionLayout::NUp {
+            columns,
+            rows,
+            fill_order,
+        } => {
+            let per_sheet = columns * rows;
+            (0..page_count.div_ceil(per_sheet))
+                .map(|sheet| {
+                    (0..per_sheet)
+                        .map(|cell| (sheet * per_sheet + cell, cell))
+                        .filter(|&(page, _)| page < page_count)
+                        .map(|(page, cell)| {
+                            let (row, column) = match fill_order {
+                                GridFillOrder::RowFirst => (cell / columns, cell % columns),
+                                GridFillOrder::ColumnFirst => (cell % rows, cell / rows),
+                            };
+                            Slot {
+                                page,
+                                column,
+                                row,
+                                shift: 0.0,
+                                align: 0.5,
+                            }
+                        })
+                        .collect()
+                })
+                .collect()
+        }
+        ImpositionLayout::Booklet => {
+            // Pad to a multiple of four; padding pages stay blank
+            let padded = page_count.div_ceil(4) * 4;
+            let mut sides = Vec::new();
+
+            for sheet in 0..padded / 4 {
+                // Inner sheets push out at the fore-edge when folded, so their
+                // pages move toward the spine
+                let shift = options.creep * sheet as f32;
+                let front = [padded - 1 - 2 * sheet, 2 * sheet];
+                let back = [2 * sheet + 1, padded - 2 - 2 * sheet];
+
+                for [left, right] in [front, back] {
+                    let mut side = Vec::new();
+                    if left < page_count {
+                        side.push(Slot {
+                            page: left,
+                            column: 0,
+                            row: 0,
+                            shift,
+                            align: 1.0,
+                        });
+                    }
+                    if right < page_count {
+                        side.push(Slot {
+                            page: right,
+                            column: 1,
+                            row: 0,
+                            shift: -shift,
+                            align: 0.0,
+                        });
+                    }
+                    sides.push(side);
+                }
+            }
+
+            sides
+        }
+    }
+}
+
+/// Impose the pages of a loaded PDF onto new sheets appended to the target document.
+///
+/// Returns the IDs of the new sheet pages in order.
+pub fn impose(
+    embedder: &mut PdfEmbedder,
+    target_doc: &mut Document,
+    source_identifier: &str,
+    options: &ImpositionOptions,
+) -> Result<Vec<ObjectId>> {
+    let (columns, rows) = options.grid();
+    if columns == 0 || rows == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Imposition grid needs at least one column and one row",
+        ));
+    }
+
+    let (sheet_w, sheet_h) = options.sheet_size;
+    let (gutter_x, gutter_y) = options.gutter;
+    let cell_w = (sheet_w - 2.0 * options.margin - (columns - 1) as f32 * gutter_x) / columns as f32;
+    let cell_h = (sheet_h - 2.0 * options.margin - (rows - 1) as f32 * gutter_y) / rows as f32;
+    if cell_w <= 0.0 || cell_h <= 0.0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Sheet is too small for the margin, gutters and grid",
+        ));
+    }
+
+    let info = embedder.get_pdf_info_for_box(source_identifier, options.page_box)?;
+    let registration = if options.crop_marks || options.registration_marks {
+        Some(target_doc.add_object(registration_color_space()))
+    } else {
+        None
+    };
+
+    let mut sheets = Vec::new();
+    for slots in plan_sheets(options, info.page_count) {
+        let mut operations = Vec::new();
+        let mut resources = Dictionary::new();
+        let mut placed = Vec::new();
+
+        for slot in slots {
+            let cell_x = options.margin + slot.column as f32 * (cell_w + gutter_x);
+            let cell_y = sheet_h - options.margin - (slot.row + 1) as f32 * cell_h - slot.row as f32 * gutter_y;
+
+            // Fit into the cell, or use the fixed scale while still never overflowing it
+            let (page_w, page_h) = info.page_dimensions[slot.page];
+            let mut embed_options = EmbedOptions::new()
+                .with_layout(MultiPageLayout::SpecificPage(slot.page))
+                .with_page_box(options.page_box)
+                .with_max_size(cell_w, cell_h)
+                .preserve_aspect_ratio(true);
+            embed_options = match options.scale {
+                Some(scale) => embed_options.with_scale(scale),
+                None => embed_options.with_scale_xy(cell_w / page_w, cell_h / page_h),
+            };
+
+            let (_, _, _, scale_x, scale_y) =
+                embedder.calculate_page_positions(&[slot.page], &info, &embed_options)[0];
+            let (placed_w, placed_h) = (page_w * scale_x, page_h * scale_y);
+            let x = cell_x + (cell_w - placed_w) * slot.align + slot.shift;
+            let y = cell_y + (cell_h - placed_h) / 2.0;
+
+            let result = embedder.embed_pdf(target_doc, source_identifier, &embed_options.at_position(x, y))?;
+            operations.extend(result.operations.iter().cloned());
+            result.add_to_resources(&mut resources);
+            placed.push((x, y, placed_w, placed_h));
+        }
+
+        if let Some(color_space) = registration {
+            resources.set(
+                "ColorSpace",
+                dictionary! { "Reg" => Object::Reference(color_space) },
+            );
+            operations.extend(begin_marks());
+            if options.crop_marks {
+                for &rect in &placed {
+                    operations.extend(crop_marks(rect, options.mark_length, options.mark_offset));
+                }
+            }
+            if options.registration_marks {
+                let radius = options.mark_length / 2.0;
+                let inset = options.margin / 2.0;
+                for center in [
+                    (sheet_w / 2.0, inset),
+                    (sheet_w / 2.0, sheet_h - inset),
+                    (inset, sheet_h / 2.0),
+                    (sheet_w - inset, sheet_h / 2.0),
+                ] {
+                    operations.extend(registration_target(center, radius));
+                }
+            }
+            operations.push(Operation::new("Q", vec![]));
+        }
+
+        let content = Content { operations };
+        let content_id = target_doc.add_object(Stream::new(
+            dictionary! {},
+            content.encode().map_err(|e| {
+                Error::new(ErrorKind::InvalidData, format!("Failed to encode sheet content: {}", e))
+            })?,
+        ));
+        sheets.push(target_doc.add_object(dictionary! {
+            "Type" => "Page",
+            "MediaBox" => vec![0.into(), 0.into(), sheet_w.into(), sheet_h.into()],
+            "Resources" => resources,
+            "Contents" => content_id,
+        }));
+    }
+
+    let mut all_pages = pages::page_ids(target_doc);
+    all_pages.extend(&sheets);
+    pages::set_page_order(target_doc, &all_pages)?;
+
+    Ok(sheets)
+}
+
+/// Separation colour space that prints on every plate, used for printer marks
+fn registration_color_space() -> Object {
+    Object::Array(vec![
+        "Separation".into(),
+        "All".into(),
+        "DeviceCMYK".into(),
+        Object::Dictionary(dictionary! {
+            "FunctionType" => 2,
+            "Domain" => vec![0.into(), 1.into()],
+            "C0" => vec![0.into(), 0.into(), 0.into(), 0.into()],
+            "C1" => vec![1.into(), 1.into(), 1.into(), 1.into()],
+            "N" => 1,
+        }),
+    ])
+}
+
+/// Hairline stroke in the registration colour; closed by a `Q`
+fn begin_marks() -> Vec<Operation> {
+    vec![
+        Operation::new("q", vec![]),
+        Operation::new("CS", vec![Object::Name(b"Reg".to_vec())]),
+        Operation::new("SCN", vec![1.into()]),
+        Operation::new("w", vec![0.25.into()]),
+    ]
+}
+
+/// Eight crop marks around a page, pointing away from its corners
+fn crop_marks((x, y, width, height): (f32, f32, f32, f32), length: f32, offset: f32) -> Vec<Operation> {
+    let mut ops = Vec::new();
+    let mut line = |x0: f32, y0: f32, x1: f32, y1: f32| {
+        ops.push(Operation::new("m", vec![x0.into(), y0.into()]));
+        ops.push(Operation::new("l", vec![x1.into(), y1.into()]));
+    };
+
+    for (corner_x, dir_x) in [(x, -1.0), (x + width, 1.0)] {
+        for (corner_y, dir_y) in [(y, -1.0), (y + height, 1.0)] {
+            // Horizontal mark level with the edge, vertical mark in line with the side
+            line(corner_x + dir_x * offset, corner_y, corner_x + dir_x * (offset + length), corner_y);
+            line(corner_x, corner_y + dir_y * offset, corner_x, corner_y + dir_y * (offset + length));
+        }
+    }
+
+    ops.push(Operation::new("S", vec![]));
+    ops
+}
+
+/// Circle with a crosshair centred on `(cx, cy)`
+fn registration_target((cx, cy): (f32, f32), radius: f32) -> Vec<Operation> {
+    let k = radius * KAPPA;
+    let reach = radius * 1.5;
+    let point = |x: f32, y: f32| -> Vec<Object> { vec![x.into(), y.into()] };
+    let curve = |points: [(f32, f32); 3]| {
+        Operation::new(
+            "c",
+            points.iter().flat_map(|&(x, y)| point(x, y)).collect(),
+        )
+    };
+
+    vec![
+        Operation::new("m", point(cx + radius, cy)),
+        curve([(cx + radius, cy + k), (cx + k, cy + radius), (cx, cy + radius)]),
+        curve([(cx - k, cy + radius), (cx - radius, cy + k), (cx - radius, cy)]),
+        curve([(cx - radius, cy - k), (cx - k, cy - radius), (cx, cy - radius)]),
+        curve([(cx + k, cy - radius), (cx + radius, cy - k), (cx + radius, cy)]),
+        Operation::new("h", vec![]),
+        Operation::new("m", point(cx - reach, cy)),
+        Operation::new("l", point(cx + reach, cy)),
+        Operation::new("m", point(cx, cy - reach)),
+        Operation::new("l", point(cx, cy + reach)),
+        Operation::new("S", vec![]),
+    ]
+}
@@ -33,11 +33,15 @@
 //! - [`ocg`] - Optional Content Groups (layers) functionality
 //! - [`layer`] - Layer management and utilities
 //! - [`hatching`] - Hatching and pattern support for PDF documents
+//! - [`pages`] - Reordering, deleting and inserting pages, and appending page content
+//! - [`imposition`] - N-up and booklet imposition with printer marks
 
 pub mod embed_pdf;
 pub mod hatching;
 pub mod ocg;
 pub mod blocks;
+pub mod pages;
+pub mod imposition;
 
 pub use lopdf;
 
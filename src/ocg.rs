@@ -4,8 +4,17 @@
 //! allowing you to organize content into groups that can be toggled on/off
 //! in PDF viewers.
 
-use lopdf::{content::Operation, dictionary, Dictionary, Document, Object, ObjectId};
-use std::collections::HashMap;
+use crate::pages;
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Dictionary, Document, Object, ObjectId, Stream,
+};
+use std::collections::{HashMap, HashSet};
+
+use std::ops::{BitAnd, BitOr, Not};
+
+/// Maximum nesting followed when reading `/Order` arrays
+const MAX_ORDER_DEPTH: usize = 32;
 
 /// Represents a single Optional Content Group (layer) in a PDF
 #[derive(Debug, Clone)]
@@ -18,6 +27,14 @@ pub struct Layer {
     pub default_visible: bool,
     /// The resource tag used in content streams (e.g., "L0", "L1")
     pub tag: Option<String>,
+    /// Intents of this layer (e.g., ["View", "Design"]); empty means the default View
+    pub intent: Vec<String>,
+    /// Name of the parent layer or group heading in the layer panel
+    pub parent: Option<String>,
+    /// Whether viewers prevent the user from changing this layer's visibility
+    pub locked: bool,
+    /// How the layer is treated when viewing, printing, exporting and zooming
+    pub usage: LayerUsage,
 }
 
 impl Layer {
@@ -28,6 +45,10 @@ impl Layer {
             name: name.into(),
             default_visible,
             tag: None,
+            intent: Vec::new(),
+            parent: None,
+            locked: false,
+            usage: LayerUsage::default(),
         }
     }
 
@@ -36,6 +57,510 @@ impl Layer {
         self.default_visible = visible;
         self
     }
+
+    /// Sets the intents of this layer
+    pub fn with_intent(mut self, intent: Vec<String>) -> Self {
+        self.intent = intent;
+        self
+    }
+
+    /// Nests this layer under a parent layer or group heading
+    pub fn with_parent(mut self, parent: impl Into<String>) -> Self {
+        self.parent = Some(parent.into());
+        self
+    }
+
+    /// Locks the visibility of this layer in viewers
+    pub fn with_locked(mut self, locked: bool) -> Self {
+        self.locked = locked;
+        self
+    }
+
+    /// Sets how the layer is treated when viewing, printing, exporting and zooming
+    pub fn with_usage(mut self, usage: LayerUsage) -> Self {
+        self.usage = usage;
+        self
+    }
+}
+
+/// A label-only heading in the layer panel that groups layers without being a layer itself
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerGroup {
+    /// The heading shown in the layer panel
+    pub label: String,
+    /// Name of the parent layer or group heading
+    pub parent: Option<String>,
+    /// Number of layers added before this group, used to keep creation order
+    pub(crate) position: usize,
+}
+
+impl LayerGroup {
+    /// Creates a top-level group heading
+    pub fn new(label: impl Into<String>) -> Self {
+        LayerGroup {
+            label: label.into(),
+            parent: None,
+            position: 0,
+        }
+    }
+
+    /// Nests this group under a parent layer or group heading
+    pub fn with_parent(mut self, parent: impl Into<String>) -> Self {
+        self.parent = Some(parent.into());
+        self
+    }
+}
+
+/// An entry of the layer panel's `/Order` array
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderItem {
+    /// A layer, identified by its OCG object ID
+    Layer(ObjectId),
+    /// A nested list. With a label it is a heading that cannot be toggled;
+    /// without one, its items are nested under the layer just before it.
+    Group {
+        label: Option<String>,
+        items: Vec<OrderItem>,
+    },
+}
+
+/// Kind of user a layer is meant for (the `/Type` of a `/User` usage entry)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserKind {
+    /// Named individuals
+    Individual,
+    /// Job titles
+    Title,
+    /// Organisations
+    Organisation,
+}
+
+impl UserKind {
+    fn as_name(&self) -> &'static str {
+        match self {
+            UserKind::Individual => "Ind",
+            UserKind::Title => "Ttl",
+            UserKind::Organisation => "Org",
+        }
+    }
+
+    fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"Ind" => Some(UserKind::Individual),
+            b"Ttl" => Some(UserKind::Title),
+            b"Org" => Some(UserKind::Organisation),
+            _ => None,
+        }
+    }
+}
+
+/// How a layer should be treated in different situations (the OCG `/Usage` dictionary).
+///
+/// Viewers apply these through the auto-state (`/AS`) entries written with each
+/// configuration. Unset entries leave the layer's state alone.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LayerUsage {
+    /// Whether the layer is shown on screen
+    pub view: Option<bool>,
+    /// Whether the layer is printed
+    pub print: Option<bool>,
+    /// Whether the layer is included when exporting to other formats
+    pub export: Option<bool>,
+    /// Smallest magnification (1.0 = 100%) at which the layer is shown
+    pub zoom_min: Option<f32>,
+    /// Magnification at and above which the layer is hidden
+    pub zoom_max: Option<f32>,
+    /// Language of the content, and whether it is shown when the language matches
+    pub language: Option<(String, bool)>,
+    /// Users the content is meant for
+    pub user: Option<(UserKind, Vec<String>)>,
+}
+
+impl LayerUsage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Layers shown on screen but never printed
+    pub fn screen_only() -> Self {
+        Self::new().with_view(true).with_print(false)
+    }
+
+    pub fn with_view(mut self, visible: bool) -> Self {
+        self.view = Some(visible);
+        self
+    }
+
+    pub fn with_print(mut self, printed: bool) -> Self {
+        self.print = Some(printed);
+        self
+    }
+
+    pub fn with_export(mut self, exported: bool) -> Self {
+        self.export = Some(exported);
+        self
+    }
+
+    /// Limits the layer to a magnification range; None leaves that end open
+    pub fn with_zoom(mut self, min: Option<f32>, max: Option<f32>) -> Self {
+        self.zoom_min = min;
+        self.zoom_max = max;
+        self
+    }
+
+    pub fn with_language(mut self, language: impl Into<String>, preferred: bool) -> Self {
+        self.language = Some((language.into(), preferred));
+        self
+    }
+
+    pub fn with_user(mut self, kind: UserKind, names: Vec<String>) -> Self {
+        self.user = Some((kind, names));
+        self
+    }
+
+    /// Whether no usage entries are set
+    pub fn is_empty(&self) -> bool {
+        *self == LayerUsage::default()
+    }
+
+    /// Whether the layer takes part in the given auto-state category
+    fn has_category(&self, category: &str) -> bool {
+        match category {
+            "View" => self.view.is_some(),
+            "Print" => self.print.is_some(),
+            "Export" => self.export.is_some(),
+            "Zoom" => self.zoom_min.is_some() || self.zoom_max.is_some(),
+            "Language" => self.language.is_some(),
+            "User" => self.user.is_some(),
+            _ => false,
+        }
+    }
+
+    /// Encodes the usage as a `/Usage` dictionary
+    fn to_dictionary(&self) -> Dictionary {
+        let state = |on: bool| Object::Name(if on { b"ON".to_vec() } else { b"OFF".to_vec() });
+        let mut usage = Dictionary::new();
+
+        if let Some(view) = self.view {
+            usage.set("View", dictionary! { "ViewState" => state(view) });
+        }
+        if let Some(print) = self.print {
+            usage.set("Print", dictionary! { "PrintState" => state(print) });
+        }
+        if let Some(export) = self.export {
+            usage.set("Export", dictionary! { "ExportState" => state(export) });
+        }
+        if self.zoom_min.is_some() || self.zoom_max.is_some() {
+            let mut zoom = Dictionary::new();
+            if let Some(min) = self.zoom_min {
+                zoom.set("min", min);
+            }
+            if let Some(max) = self.zoom_max {
+                zoom.set("max", max);
+            }
+            usage.set("Zoom", zoom);
+        }
+        if let Some((ref lang, preferred)) = self.language {
+            usage.set(
+                "Language",
+                dictionary! {
+                    "Lang" => encode_text_string(lang),
+                    "Preferred" => state(preferred),
+                },
+            );
+        }
+        if let Some((kind, ref names)) = self.user {
+            let names: Vec<Object> = names.iter().map(|name| encode_text_string(name)).collect();
+            usage.set(
+                "User",
+                dictionary! {
+                    "Type" => kind.as_name(),
+                    "Name" => names,
+                },
+            );
+        }
+
+        usage
+    }
+
+    /// Reads a `/Usage` dictionary, ignoring entries this crate does not model
+    fn from_dictionary(doc: &Document, usage: &Dictionary) -> Self {
+        let entry = |key: &[u8]| {
+            usage
+                .get(key)
+                .and_then(|value| doc.dereference(value))
+                .and_then(|(_, value)| value.as_dict())
+                .ok()
+        };
+        let state = |dict: Option<&Dictionary>, key: &[u8]| {
+            dict.and_then(|dict| dict.get(key).and_then(Object::as_name).ok())
+                .map(|name| name == b"ON")
+        };
+
+        let zoom = entry(b"Zoom");
+        let zoom_value = |key: &[u8]| {
+            zoom.and_then(|zoom| zoom.get(key).and_then(Object::as_float).ok())
+        };
+
+        let language = entry(b"Language").and_then(|language| {
+            let lang = language.get(b"Lang").and_then(Object::as_str).ok()?;
+            let preferred = language
+                .get(b"Preferred")
+                .and_then(Object::as_name)
+                .map(|name| name == b"ON")
+                .unwrap_or(false);
+            Some((decode_text_string(lang), preferred))
+        });
+
+        let user = entry(b"User").and_then(|user| {
+            let kind = UserKind::from_name(user.get(b"Type").and_then(Object::as_name).ok()?)?;
+            let names = match user.get(b"Name").ok()? {
+                Object::String(name, _) => vec![decode_text_string(name)],
+                Object::Array(names) => names
+                    .iter()
+                    .filter_map(|name| name.as_str().ok())
+                    .map(decode_text_string)
+                    .collect(),
+                _ => Vec::new(),
+            };
+            Some((kind, names))
+        });
+
+        LayerUsage {
+            view: state(entry(b"View"), b"ViewState"),
+            print: state(entry(b"Print"), b"PrintState"),
+            export: state(entry(b"Export"), b"ExportState"),
+            zoom_min: zoom_value(b"min"),
+            zoom_max: zoom_value(b"max"),
+            language,
+            user,
+        }
+    }
+}
+
+/// A named alternate configuration (an entry of `/Configs`), such as a review set that
+/// shows a different combination of layers than the default
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerConfiguration {
+    /// Name shown by viewers that let users pick a configuration
+    pub name: String,
+    /// Name of the application or feature that created the configuration
+    pub creator: Option<String>,
+    /// State of layers not listed in `layer_states` ("ON", "OFF" or "Unchanged")
+    pub base_state: String,
+    /// Explicit layer states by layer name
+    pub layer_states: Vec<(String, bool)>,
+    /// The dictionary read from a document, whose other entries are kept on write
+    pub(crate) source: Option<Dictionary>,
+}
+
+impl LayerConfiguration {
+    /// Creates a configuration where every layer starts on
+    pub fn new(name: impl Into<String>) -> Self {
+        LayerConfiguration {
+            name: name.into(),
+            creator: None,
+            base_state: "ON".to_string(),
+            layer_states: Vec::new(),
+            source: None,
+        }
+    }
+
+    pub fn with_creator(mut self, creator: impl Into<String>) -> Self {
+        self.creator = Some(creator.into());
+        self
+    }
+
+    pub fn with_base_state(mut self, base_state: impl Into<String>) -> Self {
+        self.base_state = base_state.into();
+        self
+    }
+
+    /// Turns a layer on in this configuration
+    pub fn show(mut self, layer: impl Into<String>) -> Self {
+        self.layer_states.push((layer.into(), true));
+        self
+    }
+
+    /// Turns a layer off in this configuration
+    pub fn hide(mut self, layer: impl Into<String>) -> Self {
+        self.layer_states.push((layer.into(), false));
+        self
+    }
+}
+
+/// How an OCMD's layers decide its visibility (the `/P` entry)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VisibilityPolicy {
+    /// Visible if any of the layers is on
+    #[default]
+    AnyOn,
+    /// Visible only if all of the layers are on
+    AllOn,
+    /// Visible if any of the layers is off
+    AnyOff,
+    /// Visible only if all of the layers are off
+    AllOff,
+}
+
+impl VisibilityPolicy {
+    /// The PDF name used for the `/P` entry
+    pub fn as_name(&self) -> &'static str {
+        match self {
+            VisibilityPolicy::AnyOn => "AnyOn",
+            VisibilityPolicy::AllOn => "AllOn",
+            VisibilityPolicy::AnyOff => "AnyOff",
+            VisibilityPolicy::AllOff => "AllOff",
+        }
+    }
+}
+
+/// A visibility expression over layers (the `/VE` entry of an OCMD).
+///
+/// Expressions combine with `&`, `|` and `!`:
+/// `VisibilityExpression::layer("Dimensions") & VisibilityExpression::layer("Floor 2")`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VisibilityExpression {
+    /// On when the named layer is on
+    Layer(String),
+    /// On when every operand is on
+    And(Vec<VisibilityExpression>),
+    /// On when any operand is on
+    Or(Vec<VisibilityExpression>),
+    /// On when the operand is off
+    Not(Box<VisibilityExpression>),
+}
+
+impl VisibilityExpression {
+    /// An expression that is on when the named layer is on
+    pub fn layer(name: impl Into<String>) -> Self {
+        VisibilityExpression::Layer(name.into())
+    }
+
+    /// On when every expression is on
+    pub fn all(operands: Vec<VisibilityExpression>) -> Self {
+        VisibilityExpression::And(operands)
+    }
+
+    /// On when any expression is on
+    pub fn any(operands: Vec<VisibilityExpression>) -> Self {
+        VisibilityExpression::Or(operands)
+    }
+
+    /// Names of all layers the expression refers to, in order of first use
+    pub fn layer_names(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.collect_layer_names(&mut names);
+        names
+    }
+
+    fn collect_layer_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            VisibilityExpression::Layer(name) => {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+            VisibilityExpression::And(operands) | VisibilityExpression::Or(operands) => {
+                for operand in operands {
+                    operand.collect_layer_names(names);
+                }
+            }
+            VisibilityExpression::Not(operand) => operand.collect_layer_names(names),
+        }
+    }
+
+    /// Encodes the expression as a `/VE` array, resolving layer names to OCGs
+    fn to_object(&self, manager: &OCGManager) -> crate::Result<Object> {
+        let (operator, operands) = match self {
+            VisibilityExpression::Layer(name) => {
+                return manager.initialized_layer_id(name).map(Object::Reference)
+            }
+            VisibilityExpression::And(operands) => ("And", operands.as_slice()),
+            VisibilityExpression::Or(operands) => ("Or", operands.as_slice()),
+            VisibilityExpression::Not(operand) => ("Not", std::slice::from_ref(operand.as_ref())),
+        };
+        if operands.is_empty() {
+            return Err(format!("Visibility expression {} needs at least one operand", operator).into());
+        }
+
+        let mut array = vec![Object::Name(operator.as_bytes().to_vec())];
+        for operand in operands {
+            array.push(operand.to_object(manager)?);
+        }
+        Ok(Object::Array(array))
+    }
+}
+
+impl BitAnd for VisibilityExpression {
+    type Output = VisibilityExpression;
+
+    fn bitand(self, rhs: Self) -> Self {
+        match self {
+            VisibilityExpression::And(mut operands) => {
+                operands.push(rhs);
+                VisibilityExpression::And(operands)
+            }
+            lhs => VisibilityExpression::And(vec![lhs, rhs]),
+        }
+    }
+}
+
+impl BitOr for VisibilityExpression {
+    type Output = VisibilityExpression;
+
+    fn bitor(self, rhs: Self) -> Self {
+        match self {
+            VisibilityExpression::Or(mut operands) => {
+                operands.push(rhs);
+                VisibilityExpression::Or(operands)
+            }
+            lhs => VisibilityExpression::Or(vec![lhs, rhs]),
+        }
+    }
+}
+
+impl Not for VisibilityExpression {
+    type Output = VisibilityExpression;
+
+    fn not(self) -> Self {
+        VisibilityExpression::Not(Box::new(self))
+    }
+}
+
+/// An Optional Content Membership Dictionary: content whose visibility depends on
+/// several layers at once
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerMembership {
+    /// Names of the layers the policy applies to
+    pub layers: Vec<String>,
+    /// How the layers decide visibility
+    pub policy: VisibilityPolicy,
+    /// Visibility expression, which takes precedence over the policy in PDF 1.6+ viewers
+    pub expression: Option<VisibilityExpression>,
+}
+
+impl LayerMembership {
+    /// Membership decided by a policy over a set of layers
+    pub fn new(policy: VisibilityPolicy, layers: &[&str]) -> Self {
+        LayerMembership {
+            layers: layers.iter().map(|name| name.to_string()).collect(),
+            policy,
+            expression: None,
+        }
+    }
+
+    /// Membership decided by a visibility expression.
+    ///
+    /// The expression's layers are also listed with the AnyOn policy for viewers that
+    /// predate visibility expressions.
+    pub fn expression(expression: VisibilityExpression) -> Self {
+        LayerMembership {
+            layers: expression.layer_names().iter().map(|name| name.to_string()).collect(),
+            policy: VisibilityPolicy::AnyOn,
+            expression: Some(expression),
+        }
+    }
 }
 
 /// Configuration for the OCG system
@@ -49,218 +574,2070 @@ pub struct OCGConfig {
     pub intent: Vec<String>,
 }
 
-impl Default for OCGConfig {
-    fn default() -> Self {
-        OCGConfig {
-            base_state: "ON".to_string(),
-            create_panel_ui: true,
-            intent: vec!["View".to_string()],
-        }
+impl Default for OCGConfig {
+    fn default() -> Self {
+        OCGConfig {
+            base_state: "ON".to_string(),
+            create_panel_ui: true,
+            intent: vec!["View".to_string()],
+        }
+    }
+}
+
+/// Main manager for Optional Content Groups in a PDF document
+pub struct OCGManager {
+    /// All layers in the document
+    pub(crate) layers: Vec<Layer>,
+    /// Configuration for the OCG system
+    pub config: OCGConfig,
+    /// The object ID of the OCProperties dictionary
+    pub(crate) oc_properties_id: Option<ObjectId>,
+    /// Mapping from the OCGs of written layers to their index
+    layer_index: HashMap<ObjectId, usize>,
+    /// Layer panel order read from a document; None lists the layers flat
+    pub(crate) order: Option<Vec<OrderItem>>,
+    /// OCGs removed since loading, deleted from the document on write
+    removed_layers: Vec<ObjectId>,
+    /// Label-only headings of the layer panel
+    groups: Vec<LayerGroup>,
+    /// Sets of layer names of which at most one may be visible
+    radio_groups: Vec<Vec<String>>,
+    /// OCMDs written to the document, with their resource tags
+    memberships: Vec<(String, ObjectId)>,
+    /// Named alternate configurations written to `/Configs`
+    configurations: Vec<LayerConfiguration>,
+}
+
+impl Default for OCGManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OCGManager {
+    /// Creates a new OCGManager with default configuration
+    pub fn new() -> Self {
+        Self::with_config(OCGConfig::default())
+    }
+
+    /// Creates a new OCGManager with custom configuration
+    pub fn with_config(config: OCGConfig) -> Self {
+        OCGManager {
+            layers: Vec::new(),
+            config,
+            oc_properties_id: None,
+            layer_index: HashMap::new(),
+            order: None,
+            removed_layers: Vec::new(),
+            groups: Vec::new(),
+            radio_groups: Vec::new(),
+            memberships: Vec::new(),
+            configurations: Vec::new(),
+        }
+    }
+
+    /// Reads the layers of an existing document.
+    ///
+    /// Layer names, default visibility, intents and the layer panel order are taken from
+    /// the catalog's `/OCProperties` and its default configuration. A document without
+    /// layers gives an empty manager, and `/OCGs` entries that do not lead to a dictionary
+    /// are skipped. Call [`write_to_document`](Self::write_to_document) to store edits.
+    pub fn from_document(doc: &Document) -> crate::Result<Self> {
+        let mut manager = OCGManager::new();
+
+        let catalog = doc.catalog()?;
+        let (oc_properties_id, oc_properties) = match catalog.get(b"OCProperties") {
+            Ok(Object::Reference(id)) => (Some(*id), doc.get_dictionary(*id)?),
+            Ok(Object::Dictionary(dict)) => (None, dict),
+            _ => return Ok(manager),
+        };
+        manager.oc_properties_id = oc_properties_id;
+
+        let default_config = oc_properties
+            .get(b"D")
+            .and_then(|d| doc.dereference(d))
+            .and_then(|(_, d)| d.as_dict())
+            .ok();
+
+        let base_state = default_config
+            .and_then(|d| d.get(b"BaseState").and_then(Object::as_name).ok())
+            .map(|name| String::from_utf8_lossy(name).to_string())
+            .unwrap_or_else(|| "ON".to_string());
+        let on = default_config
+            .map(|d| reference_set(doc, d, b"ON"))
+            .unwrap_or_default();
+        let off = default_config
+            .map(|d| reference_set(doc, d, b"OFF"))
+            .unwrap_or_default();
+
+        manager.config = OCGConfig {
+            base_state: base_state.clone(),
+            create_panel_ui: default_config.is_some_and(|d| d.has(b"ListMode")),
+            intent: read_intent(doc, oc_properties)
+                .or_else(|| default_config.and_then(|d| read_intent(doc, d)))
+                .unwrap_or_default(),
+        };
+
+        // A missing list means no layers; entries that are not references to OCG
+        // dictionaries, such as dangling ones, are skipped
+        let ocgs = oc_properties
+            .get(b"OCGs")
+            .and_then(|ocgs| doc.dereference(ocgs))
+            .and_then(|(_, ocgs)| ocgs.as_array())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for ocg in ocgs {
+            let Some((id, ocg_dict)) = ocg
+                .as_reference()
+                .ok()
+                .and_then(|id| Some((id, doc.get_dictionary(id).ok()?)))
+            else {
+                continue;
+            };
+            let name = ocg_dict
+                .get(b"Name")
+                .and_then(|name| doc.dereference(name))
+                .and_then(|(_, name)| name.as_str())
+                .map(decode_text_string)
+                .unwrap_or_default();
+
+            // Unchanged base state is treated like ON
+            let default_visible = if base_state == "OFF" {
+                on.contains(&id)
+            } else {
+                !off.contains(&id)
+            };
+
+            manager.add_layer(Layer {
+                id,
+                name,
+                default_visible,
+                tag: None,
+                intent: read_intent(doc, ocg_dict).unwrap_or_default(),
+                parent: None,
+                locked: false,
+                usage: ocg_dict
+                    .get(b"Usage")
+                    .and_then(|usage| doc.dereference(usage))
+                    .and_then(|(_, usage)| usage.as_dict())
+                    .map(|usage| LayerUsage::from_dictionary(doc, usage))
+                    .unwrap_or_default(),
+            });
+        }
+
+        if let Some(d) = default_config {
+            for id in reference_set(doc, d, b"Locked") {
+                if let Some(layer) = manager.get_layer_by_id_mut(id) {
+                    layer.locked = true;
+                }
+            }
+
+            if let Ok((_, Object::Array(rb_groups))) = d.get(b"RBGroups").and_then(|g| doc.dereference(g)) {
+                for group in rb_groups {
+                    let members = match doc.dereference(group) {
+                        Ok((_, Object::Array(members))) => members,
+                        _ => continue,
+                    };
+                    let names: Vec<String> = members
+                        .iter()
+                        .filter_map(|member| member.as_reference().ok())
+                        .filter_map(|id| manager.get_layer_by_id(id))
+                        .map(|layer| layer.name.clone())
+                        .collect();
+                    if !names.is_empty() {
+                        manager.radio_groups.push(names);
+                    }
+                }
+            }
+        }
+
+        let layer_ids: HashSet<ObjectId> = manager.layers.iter().map(|layer| layer.id).collect();
+        manager.order = default_config
+            .and_then(|d| d.get(b"Order").ok())
+            .and_then(|order| doc.dereference(order).ok())
+            .and_then(|(_, order)| order.as_array().ok())
+            .map(|order| read_order(doc, order, &layer_ids, 0));
+        if let Some(order) = manager.order.take() {
+            manager.assign_parents(&order, None);
+            manager.order = Some(order);
+        }
+
+        if let Ok((_, Object::Array(configs))) = oc_properties.get(b"Configs").and_then(|c| doc.dereference(c)) {
+            for config in configs {
+                if let Ok((_, Object::Dictionary(config))) = doc.dereference(config) {
+                    let configuration = manager.read_configuration(doc, config);
+                    manager.configurations.push(configuration);
+                }
+            }
+        }
+
+        Ok(manager)
+    }
+
+    /// Reads an alternate configuration dictionary
+    fn read_configuration(&self, doc: &Document, config: &Dictionary) -> LayerConfiguration {
+        let text = |key: &[u8]| {
+            config
+                .get(key)
+                .and_then(Object::as_str)
+                .map(decode_text_string)
+                .ok()
+        };
+        let mut configuration = LayerConfiguration::new(text(b"Name").unwrap_or_default());
+        configuration.creator = text(b"Creator");
+        if let Ok(base_state) = config.get(b"BaseState").and_then(Object::as_name) {
+            configuration.base_state = String::from_utf8_lossy(base_state).to_string();
+        }
+
+        for (key, visible) in [(b"ON".as_slice(), true), (b"OFF".as_slice(), false)] {
+            for id in reference_set(doc, config, key) {
+                if let Some(layer) = self.get_layer_by_id(id) {
+                    configuration.layer_states.push((layer.name.clone(), visible));
+                }
+            }
+        }
+        configuration.source = Some(config.clone());
+        configuration
+    }
+
+    /// Sets layer parents and group headings from an order tree read from a document
+    fn assign_parents(&mut self, items: &[OrderItem], parent: Option<String>) {
+        let mut previous_layer: Option<String> = None;
+        for item in items {
+            match item {
+                OrderItem::Layer(id) => {
+                    if let Some(layer) = self.get_layer_by_id_mut(*id) {
+                        layer.parent = parent.clone();
+                        previous_layer = Some(layer.name.clone());
+                    }
+                }
+                OrderItem::Group {
+                    label: Some(label),
+                    items,
+                } => {
+                    self.groups.push(LayerGroup {
+                        label: label.clone(),
+                        parent: parent.clone(),
+                        position: self.layers.len(),
+                    });
+                    self.assign_parents(items, Some(label.clone()));
+                }
+                OrderItem::Group { label: None, items } => {
+                    let nested_parent = previous_layer.clone().or_else(|| parent.clone());
+                    self.assign_parents(items, nested_parent);
+                }
+            }
+        }
+    }
+
+    /// The parent of every layer in an order tree, read like [`assign_parents`](Self::assign_parents)
+    fn order_parents(
+        &self,
+        items: &[OrderItem],
+        parent: Option<&str>,
+        parents: &mut HashMap<ObjectId, Option<String>>,
+    ) {
+        let mut previous_layer: Option<&str> = None;
+        for item in items {
+            match item {
+                OrderItem::Layer(id) => {
+                    parents.insert(*id, parent.map(str::to_string));
+                    previous_layer = self.get_layer_by_id(*id).map(|layer| layer.name.as_str());
+                }
+                OrderItem::Group { label: Some(label), items } => self.order_parents(items, Some(label), parents),
+                OrderItem::Group { label: None, items } => {
+                    self.order_parents(items, previous_layer.or(parent), parents);
+                }
+            }
+        }
+    }
+
+    /// Moves layers whose parent differs from the one in the loaded order under their new
+    /// parent, taking the layers nested under them along
+    fn move_reparented_layers(&mut self) {
+        let Some(ref order) = self.order else {
+            return;
+        };
+        let mut parents = HashMap::new();
+        self.order_parents(order, None, &mut parents);
+        let moved: Vec<(ObjectId, Option<String>)> = self
+            .layers
+            .iter()
+            .filter(|layer| parents.get(&layer.id).is_some_and(|parent| *parent != layer.parent))
+            .map(|layer| (layer.id, layer.parent.clone()))
+            .collect();
+
+        for (id, parent) in moved {
+            let parent = parent.as_deref().and_then(|parent| self.resolve_parent(parent));
+            let Some(order) = self.order.as_mut() else {
+                return;
+            };
+            let children = take_from_order(order, id);
+            insert_into_order(order, OrderItem::Layer(id), parent.as_ref());
+            for child in children {
+                insert_into_order(order, child, Some(&OrderParent::Layer(id)));
+            }
+        }
+    }
+
+    /// Adds a new layer to the manager
+    ///
+    /// # Arguments
+    /// * `layer` - The layer to add
+    ///
+    /// # Returns
+    /// The index of the added layer
+    pub fn add_layer(&mut self, layer: Layer) -> usize {
+        let index = self.layers.len();
+        if layer.id != (0, 0) {
+            self.layer_index.insert(layer.id, index);
+        }
+        self.layers.push(layer);
+        index
+    }
+
+    /// Adds a label-only group heading to the layer panel.
+    ///
+    /// Layers and groups are nested under it by naming its label as their parent.
+    /// Labels share a namespace with layer names.
+    pub fn add_group(&mut self, mut group: LayerGroup) {
+        group.position = self.layers.len();
+        let parent = group.parent.as_deref().and_then(|parent| self.resolve_parent(parent));
+        if let Some(order) = self.order.as_mut() {
+            let item = OrderItem::Group {
+                label: Some(group.label.clone()),
+                items: Vec::new(),
+            };
+            insert_into_order(order, item, parent.as_ref());
+        }
+        self.groups.push(group);
+    }
+
+    /// Adds a named alternate configuration.
+    ///
+    /// New configurations share the default configuration's panel order, locks and radio
+    /// groups. Configurations read from a document keep their own.
+    pub fn add_configuration(&mut self, configuration: LayerConfiguration) {
+        self.configurations.push(configuration);
+    }
+
+    /// Named alternate configurations
+    pub fn configurations(&self) -> &[LayerConfiguration] {
+        &self.configurations
+    }
+
+    /// Label-only group headings of the layer panel
+    pub fn groups(&self) -> &[LayerGroup] {
+        &self.groups
+    }
+
+    /// Makes the named layers mutually exclusive, like radio buttons.
+    ///
+    /// If several of them are visible by default, only the first stays visible.
+    pub fn add_radio_group(&mut self, names: &[&str]) {
+        let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        let mut seen_visible = false;
+        for name in &names {
+            if let Some(layer) = self.get_layer_mut(name) {
+                if layer.default_visible && seen_visible {
+                    layer.default_visible = false;
+                }
+                seen_visible |= layer.default_visible;
+            }
+        }
+        self.radio_groups.push(names);
+    }
+
+    /// Sets of mutually exclusive layer names
+    pub fn radio_groups(&self) -> &[Vec<String>] {
+        &self.radio_groups
+    }
+
+    /// Hides the other members of every radio group containing `name`
+    fn enforce_radio_groups(&mut self, name: &str) {
+        let others: Vec<String> = self
+            .radio_groups
+            .iter()
+            .filter(|group| group.iter().any(|member| member == name))
+            .flatten()
+            .filter(|member| *member != name)
+            .cloned()
+            .collect();
+        for other in others {
+            if let Some(layer) = self.get_layer_mut(&other) {
+                layer.default_visible = false;
+            }
+        }
+    }
+
+    /// How a parent name appears in the order tree
+    fn resolve_parent(&self, parent: &str) -> Option<OrderParent> {
+        match self.get_layer(parent) {
+            Some(layer) => Some(OrderParent::Layer(layer.id)),
+            None => self
+                .groups
+                .iter()
+                .any(|group| group.label == parent)
+                .then(|| OrderParent::Label(parent.to_string())),
+        }
+    }
+
+    /// Index of the first layer with the given name
+    fn position(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|layer| layer.name == name)
+    }
+
+    /// Gets a layer by name.
+    ///
+    /// Documents may give several OCGs the same name; this returns the first of them.
+    /// Use [`get_layer_by_id`](Self::get_layer_by_id) to reach the others.
+    pub fn get_layer(&self, name: &str) -> Option<&Layer> {
+        self.position(name).map(|idx| &self.layers[idx])
+    }
+
+    /// Gets a mutable layer by name, the first one if several share it
+    pub fn get_layer_mut(&mut self, name: &str) -> Option<&mut Layer> {
+        self.position(name).map(|idx| &mut self.layers[idx])
+    }
+
+    /// Index of the written layer with the given OCG
+    fn id_position(&self, id: ObjectId) -> Option<usize> {
+        match self.layer_index.get(&id) {
+            Some(&idx) if self.layers.get(idx).is_some_and(|layer| layer.id == id) => Some(idx),
+            // The index is stale if an ID was changed through `get_layer_mut`
+            _ => self.layers.iter().position(|layer| id != (0, 0) && layer.id == id),
+        }
+    }
+
+    /// Gets a written layer by its OCG object ID
+    pub fn get_layer_by_id(&self, id: ObjectId) -> Option<&Layer> {
+        self.id_position(id).map(|idx| &self.layers[idx])
+    }
+
+    /// Gets a mutable written layer by its OCG object ID
+    pub fn get_layer_by_id_mut(&mut self, id: ObjectId) -> Option<&mut Layer> {
+        self.id_position(id).map(|idx| &mut self.layers[idx])
+    }
+
+    /// Renames a layer. Returns false if the layer does not exist or the new name is taken.
+    pub fn rename_layer(&mut self, name: &str, new_name: impl Into<String>) -> bool {
+        let new_name = new_name.into();
+        if self.position(&new_name).is_some() {
+            return false;
+        }
+        match self.position(name) {
+            Some(idx) => {
+                self.layers[idx].name = new_name.clone();
+                self.replace_parent(name, Some(&new_name));
+                let members = self.radio_groups.iter_mut().flatten().chain(
+                    self.configurations
+                        .iter_mut()
+                        .flat_map(|config| config.layer_states.iter_mut().map(|(member, _)| member)),
+                );
+                for member in members {
+                    if member == name {
+                        *member = new_name.clone();
+                    }
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Points children of `old` at a new parent
+    fn replace_parent(&mut self, old: &str, new: Option<&str>) {
+        let parents = self
+            .layers
+            .iter_mut()
+            .map(|layer| &mut layer.parent)
+            .chain(self.groups.iter_mut().map(|group| &mut group.parent));
+        for parent in parents {
+            if parent.as_deref() == Some(old) {
+                *parent = new.map(str::to_string);
+            }
+        }
+    }
+
+    /// Removes a layer, returning it if it existed.
+    ///
+    /// When written back, the OCG is deleted from the document along with the
+    /// `/Properties` entries and `/OC` entries that point to it. Content marked with the
+    /// layer stays, no longer on a layer.
+    pub fn remove_layer(&mut self, name: &str) -> Option<Layer> {
+        let idx = self.position(name)?;
+        self.remove_layer_at(idx)
+    }
+
+    /// Removes a written layer by its OCG object ID, like [`remove_layer`](Self::remove_layer)
+    pub fn remove_layer_by_id(&mut self, id: ObjectId) -> Option<Layer> {
+        let idx = self.id_position(id)?;
+        self.remove_layer_at(idx)
+    }
+
+    fn remove_layer_at(&mut self, idx: usize) -> Option<Layer> {
+        let layer = self.layers.remove(idx);
+        self.rebuild_index();
+
+        // Children move up to the removed layer's place
+        self.replace_parent(&layer.name, layer.parent.as_deref());
+        for group in &mut self.radio_groups {
+            group.retain(|member| *member != layer.name);
+        }
+        self.radio_groups.retain(|group| !group.is_empty());
+        let removed = HashSet::from([layer.id]);
+        for config in &mut self.configurations {
+            config.layer_states.retain(|(member, _)| *member != layer.name);
+            if let Some(source) = config.source.as_mut() {
+                for (_, value) in source.iter_mut() {
+                    strip_references(value, &removed);
+                }
+            }
+        }
+
+        if layer.id != (0, 0) {
+            self.removed_layers.push(layer.id);
+        }
+        if let Some(order) = self.order.as_mut() {
+            remove_from_order(order, layer.id);
+        }
+        Some(layer)
+    }
+
+    /// Sets whether a layer is visible by default. Returns false if the layer does not exist.
+    ///
+    /// Showing a layer hides the other members of its radio groups.
+    pub fn set_layer_visibility(&mut self, name: &str, visible: bool) -> bool {
+        match self.get_layer_mut(name) {
+            Some(layer) => {
+                layer.default_visible = visible;
+                if visible {
+                    self.enforce_radio_groups(name);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Flips the default visibility of a layer, returning the new visibility
+    pub fn toggle_layer(&mut self, name: &str) -> Option<bool> {
+        let visible = !self.get_layer(name)?.default_visible;
+        self.set_layer_visibility(name, visible);
+        Some(visible)
+    }
+
+    /// All layers in creation or document order
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// The layer panel order read from a document, if any
+    pub fn order(&self) -> Option<&[OrderItem]> {
+        self.order.as_deref()
+    }
+
+    /// Get the number of layers
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Check if there are no layers
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Check if OCProperties has been initialized
+    pub fn has_oc_properties(&self) -> bool {
+        self.oc_properties_id.is_some()
+    }
+
+    /// Initializes all layers in the PDF document
+    /// This should be called after all layers have been added but before they are used
+    pub fn initialize(&mut self, doc: &mut Document) {
+        // Create OCG objects for each layer
+        for layer in &mut self.layers {
+            layer.id = doc.add_object(ocg_dictionary(layer));
+        }
+        self.rebuild_index();
+
+        // Create the OCProperties dictionary
+        self.create_oc_properties(doc);
+    }
+
+    /// Writes an OCMD to the document and returns its resource tag (e.g., "M0").
+    ///
+    /// The tag works with [`LayerContentBuilder::begin_layer`] like a layer tag. Layers must
+    /// be initialized first, and memberships must be added before
+    /// [`setup_page_resources`](Self::setup_page_resources) so the tag is registered.
+    pub fn add_membership(
+        &mut self,
+        doc: &mut Document,
+        membership: &LayerMembership,
+    ) -> crate::Result<String> {
+        let ocgs = membership
+            .layers
+            .iter()
+            .map(|name| self.initialized_layer_id(name).map(Object::Reference))
+            .collect::<crate::Result<Vec<Object>>>()?;
+        if ocgs.is_empty() {
+            return Err("A layer membership needs at least one layer".into());
+        }
+
+        let mut ocmd = dictionary! {
+            "Type" => "OCMD",
+            "OCGs" => ocgs,
+            "P" => membership.policy.as_name(),
+        };
+        if let Some(ref expression) = membership.expression {
+            ocmd.set("VE", expression.to_object(self)?);
+        }
+
+        let tag = format!("M{}", self.memberships.len());
+        let id = doc.add_object(ocmd);
+        self.memberships.push((tag.clone(), id));
+        Ok(tag)
+    }
+
+    /// The OCMD object written for a membership tag
+    pub fn get_membership(&self, tag: &str) -> Option<ObjectId> {
+        self.memberships
+            .iter()
+            .find(|(membership_tag, _)| membership_tag == tag)
+            .map(|(_, id)| *id)
+    }
+
+    /// Makes an annotation or an image or form XObject visible only while a layer is on
+    pub fn assign_layer(&self, doc: &mut Document, object_id: ObjectId, layer_name: &str) -> crate::Result<()> {
+        let layer_id = self.initialized_layer_id(layer_name)?;
+        set_optional_content(doc, object_id, layer_id)
+    }
+
+    /// Makes an annotation or an image or form XObject follow a membership's visibility
+    pub fn assign_membership(&self, doc: &mut Document, object_id: ObjectId, membership_tag: &str) -> crate::Result<()> {
+        let membership_id = self
+            .get_membership(membership_tag)
+            .ok_or_else(|| format!("Unknown membership '{}'", membership_tag))?;
+        set_optional_content(doc, object_id, membership_id)
+    }
+
+    /// Removes the document's layers, keeping the content of the given layers and hiding
+    /// the rest.
+    ///
+    /// Content outside any layer stays. Marked sections are followed through nesting,
+    /// form XObjects, OCMD memberships and `/OC` entries on annotations and XObjects.
+    pub fn flatten_layers(&self, doc: &mut Document, visible_layers: &[&str]) -> crate::Result<()> {
+        let visible = visible_layers
+            .iter()
+            .map(|name| self.initialized_layer_id(name))
+            .collect::<crate::Result<HashSet<ObjectId>>>()?;
+        LayerFilter { visible: &visible, keep_unlayered: true, forms: HashMap::new() }.apply(doc)
+    }
+
+    /// A copy of the document with only the content of one layer
+    pub fn extract_layer(&self, doc: &Document, layer_name: &str) -> crate::Result<Document> {
+        let visible = HashSet::from([self.initialized_layer_id(layer_name)?]);
+        let mut extracted = doc.clone();
+        LayerFilter { visible: &visible, keep_unlayered: false, forms: HashMap::new() }.apply(&mut extracted)?;
+        Ok(extracted)
+    }
+
+    /// Splits the document into one document per layer, in layer order
+    pub fn split_by_layer(&self, doc: &Document) -> crate::Result<Vec<(String, Document)>> {
+        self.layers
+            .iter()
+            .map(|layer| Ok((layer.name.clone(), self.extract_layer(doc, &layer.name)?)))
+            .collect()
+    }
+
+    /// The OCG of a layer that has been written to the document
+    fn initialized_layer_id(&self, name: &str) -> crate::Result<ObjectId> {
+        match self.get_layer(name) {
+            Some(layer) if layer.id != (0, 0) => Ok(layer.id),
+            Some(_) => Err(format!("Layer '{}' has not been initialized", name).into()),
+            None => Err(format!("Unknown layer '{}'", name).into()),
+        }
+    }
+
+    /// Prepares a page's resources dictionary to use layers
+    ///
+    /// Existing `/Properties` entries are kept. Layers get the tags `L0`, `L1`, ... unless
+    /// the page already uses a tag for something else, or already has a tag for the layer.
+    /// An indirect `/Properties` dictionary is replaced; use
+    /// [`setup_page_resources_in`](Self::setup_page_resources_in) to keep its entries.
+    ///
+    /// # Arguments
+    /// * `resources` - The page's resources dictionary
+    ///
+    /// # Returns
+    /// A map from layer names to their resource tags
+    pub fn setup_page_resources(&mut self, resources: &mut Dictionary) -> HashMap<String, String> {
+        let properties = resources
+            .get(b"Properties")
+            .and_then(Object::as_dict)
+            .cloned()
+            .unwrap_or_default();
+        self.assign_page_tags(resources, properties)
+    }
+
+    /// Prepares a page's resources dictionary to use layers like
+    /// [`setup_page_resources`](Self::setup_page_resources), looking up an indirect
+    /// `/Properties` dictionary in `doc` and copying its entries into `resources`
+    pub fn setup_page_resources_in(&mut self, doc: &Document, resources: &mut Dictionary) -> HashMap<String, String> {
+        let properties = resources
+            .get(b"Properties")
+            .and_then(|properties| doc.dereference(properties))
+            .and_then(|(_, properties)| properties.as_dict())
+            .cloned()
+            .unwrap_or_default();
+        self.assign_page_tags(resources, properties)
+    }
+
+    /// Merges the layers into a page's `/Properties` and records each layer's tag
+    fn assign_page_tags(&mut self, resources: &mut Dictionary, mut properties: Dictionary) -> HashMap<String, String> {
+        let tags = self.merge_properties(&mut properties);
+
+        // Tags come in layer order, so layers sharing a name still get their own tag
+        for (layer, (_, tag)) in self.layers.iter_mut().zip(&tags) {
+            layer.tag = Some(tag.clone());
+        }
+
+        resources.set("Properties", properties);
+        tag_map(tags)
+    }
+
+    /// Prepares the resources of a page already in a document to use layers.
+    ///
+    /// Works like [`setup_page_resources`](Self::setup_page_resources) for pages whose
+    /// resources are inherited or shared: the page gets its own copy of its resources and
+    /// `/Properties`, so other pages are unaffected. Returns tags by layer name, and
+    /// membership tags (as returned by [`add_membership`](Self::add_membership)) mapped to
+    /// the tag used on this page.
+    pub fn merge_page_resources(
+        &self,
+        doc: &mut Document,
+        page_id: ObjectId,
+    ) -> crate::Result<HashMap<String, String>> {
+        let page = doc.get_dictionary(page_id)?;
+        let mut resources = pages::get_inherited_attribute(doc, page, b"Resources")
+            .and_then(|resources| doc.dereference(resources).ok())
+            .and_then(|(_, resources)| resources.as_dict().ok())
+            .cloned()
+            .unwrap_or_default();
+        let mut properties = resources
+            .get(b"Properties")
+            .and_then(|properties| doc.dereference(properties))
+            .and_then(|(_, properties)| properties.as_dict())
+            .cloned()
+            .unwrap_or_default();
+
+        let tags = self.merge_properties(&mut properties);
+        resources.set("Properties", properties);
+        doc.get_dictionary_mut(page_id)?.set("Resources", resources);
+        Ok(tag_map(tags))
+    }
+
+    /// Adds the layers and memberships to a `/Properties` dictionary without replacing
+    /// its entries, returning the tag used for each layer, in layer order, then for each
+    /// membership tag
+    fn merge_properties(&self, properties: &mut Dictionary) -> Vec<(String, String)> {
+        let entries = self
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| (layer.name.clone(), format!("L{}", i), layer.id))
+            .chain(
+                self.memberships
+                    .iter()
+                    .map(|(tag, id)| (tag.clone(), tag.clone(), *id)),
+            );
+
+        let mut tags = Vec::new();
+        for (key, preferred, id) in entries {
+            let reference = Object::Reference(id);
+            // Layers that are not initialized yet all share the placeholder id
+            let existing = properties
+                .iter()
+                .find(|(_, value)| id != (0, 0) && **value == reference)
+                .map(|(tag, _)| String::from_utf8_lossy(tag).into_owned());
+            let tag = existing.unwrap_or_else(|| {
+                let tag = (0..)
+                    .map(|n| if n == 0 { preferred.clone() } else { format!("{}_{}", preferred, n) })
+                    .find(|tag| !properties.has(tag.as_bytes()))
+                    .unwrap_or(preferred);
+                properties.set(tag.clone(), reference);
+                tag
+            });
+            tags.push((key, tag));
+        }
+        tags
+    }
+
+    /// Updates the document catalog to include OCProperties
+    pub fn update_catalog(&self, doc: &mut Document) {
+        if let Some(oc_props_id) = self.oc_properties_id {
+            // Find the catalog by checking the trailer root
+            if let Ok(Object::Reference(catalog_id)) = doc.trailer.get(b"Root") {
+                if let Ok(Object::Dictionary(ref mut catalog)) = doc.get_object_mut(*catalog_id) {
+                    catalog.set("OCProperties", Object::Reference(oc_props_id));
+                }
+            }
+        }
+    }
+
+    /// Writes the layers back to the document they were read from.
+    ///
+    /// Layers added since loading get new OCGs, renamed and toggled layers are updated
+    /// and removed layers are deleted. Entries of `/OCProperties` this manager does not
+    /// model are kept. Alternate configurations read from the document get their name,
+    /// creator, base state and layer states rewritten and keep everything else, such as
+    /// their own `/Order`, `/Locked`, `/RBGroups`, `/AS` and `/Intent`. Layers whose parent
+    /// was changed since loading move, with the layers nested under them, to their new
+    /// place in the panel order.
+    pub fn write_to_document(&mut self, doc: &mut Document) {
+        self.move_reparented_layers();
+        for idx in 0..self.layers.len() {
+            let layer = &mut self.layers[idx];
+            if layer.id == (0, 0) || !doc.has_object(layer.id) {
+                layer.id = doc.add_object(ocg_dictionary(layer));
+                let item = OrderItem::Layer(layer.id);
+                let parent = layer.parent.clone();
+                let parent = parent.as_deref().and_then(|parent| self.resolve_parent(parent));
+                if let Some(order) = self.order.as_mut() {
+                    insert_into_order(order, item, parent.as_ref());
+                }
+            } else if let Ok(ocg) = doc.get_dictionary_mut(layer.id) {
+                ocg.set("Name", encode_text_string(&layer.name));
+                if layer.intent.is_empty() {
+                    ocg.remove(b"Intent");
+                } else {
+                    ocg.set("Intent", intent_object(&layer.intent));
+                }
+                if layer.usage.is_empty() {
+                    ocg.remove(b"Usage");
+                } else {
+                    ocg.set("Usage", layer.usage.to_dictionary());
+                }
+            }
+        }
+        self.rebuild_index();
+
+        let removed: HashSet<ObjectId> = self.removed_layers.drain(..).collect();
+        if !removed.is_empty() {
+            unmark_removed_layers(doc, &removed);
+            for id in &removed {
+                doc.objects.remove(id);
+            }
+            remove_property_references(doc, &removed);
+        }
+
+        // Start from the existing dictionary so unknown entries survive
+        let existing = match self.oc_properties_id {
+            Some(id) => doc.get_dictionary(id).ok().cloned(),
+            None => doc
+                .catalog()
+                .and_then(|catalog| catalog.get(b"OCProperties"))
+                .and_then(Object::as_dict)
+                .ok()
+                .cloned(),
+        };
+        let mut oc_properties = existing.unwrap_or_default();
+        for (_, value) in oc_properties.iter_mut() {
+            strip_references(value, &removed);
+        }
+
+        let default_config = oc_properties
+            .get(b"D")
+            .and_then(Object::as_dict)
+            .ok()
+            .cloned()
+            .unwrap_or_default();
+        let oc_properties = self.build_oc_properties(oc_properties, default_config);
+
+        match self.oc_properties_id {
+            Some(id) => doc.set_object(id, oc_properties),
+            None => self.oc_properties_id = Some(doc.add_object(oc_properties)),
+        }
+        self.update_catalog(doc);
+    }
+
+    /// Auto-state entries telling viewers to apply each layer's usage settings
+    fn auto_state(&self) -> Vec<Object> {
+        const CATEGORIES: [(&str, &str); 6] = [
+            ("View", "View"),
+            ("View", "Zoom"),
+            ("View", "Language"),
+            ("View", "User"),
+            ("Print", "Print"),
+            ("Export", "Export"),
+        ];
+
+        CATEGORIES
+            .iter()
+            .filter_map(|&(event, category)| {
+                let ocgs: Vec<Object> = self
+                    .layers
+                    .iter()
+                    .filter(|layer| layer.usage.has_category(category))
+                    .map(|layer| Object::Reference(layer.id))
+                    .collect();
+                (!ocgs.is_empty()).then(|| {
+                    Object::Dictionary(dictionary! {
+                        "Event" => event,
+                        "Category" => vec![Object::Name(category.as_bytes().to_vec())],
+                        "OCGs" => ocgs,
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Writes the name and layer states of an alternate configuration into `dict`
+    fn configuration_dictionary(&self, config: &LayerConfiguration, mut dict: Dictionary) -> Dictionary {
+        dict.set("Name", encode_text_string(&config.name));
+        dict.set("BaseState", Object::Name(config.base_state.as_bytes().to_vec()));
+        match config.creator {
+            Some(ref creator) => dict.set("Creator", encode_text_string(creator)),
+            None => {
+                dict.remove(b"Creator");
+            }
+        }
+
+        for (key, visible) in [("ON", true), ("OFF", false)] {
+            let refs: Vec<Object> = config
+                .layer_states
+                .iter()
+                .filter(|(_, state)| *state == visible)
+                .filter_map(|(name, _)| self.get_layer(name))
+                .map(|layer| Object::Reference(layer.id))
+                .collect();
+            dict.remove(key.as_bytes());
+            if !refs.is_empty() {
+                dict.set(key, refs);
+            }
+        }
+        dict
+    }
+
+    /// Builds the layer panel tree from layer and group parents, keeping creation order
+    fn build_order(&self) -> Vec<OrderItem> {
+        let mut nodes = Vec::new();
+        for (idx, layer) in self.layers.iter().enumerate() {
+            nodes.extend(
+                self.groups
+                    .iter()
+                    .filter(|group| group.position == idx)
+                    .map(OrderNode::Group),
+            );
+            nodes.push(OrderNode::Layer(layer));
+        }
+        nodes.extend(
+            self.groups
+                .iter()
+                .filter(|group| group.position >= self.layers.len())
+                .map(OrderNode::Group),
+        );
+
+        let known: HashSet<&str> = nodes.iter().map(OrderNode::name).collect();
+        let mut visited = HashSet::new();
+        let mut order = order_children(&nodes, None, &known, &mut visited);
+
+        // Layers caught in a parent cycle are listed at the top level
+        for (idx, node) in nodes.iter().enumerate() {
+            if let OrderNode::Layer(layer) = node {
+                if !visited.contains(&idx) {
+                    order.push(OrderItem::Layer(layer.id));
+                }
+            }
+        }
+        order
+    }
+
+    fn rebuild_index(&mut self) {
+        self.layer_index = self
+            .layers
+            .iter()
+            .enumerate()
+            .filter(|(_, layer)| layer.id != (0, 0))
+            .map(|(idx, layer)| (layer.id, idx))
+            .collect();
+    }
+
+    /// Creates the OCProperties dictionary in the document
+    fn create_oc_properties(&mut self, doc: &mut Document) {
+        let oc_properties = self.build_oc_properties(Dictionary::new(), Dictionary::new());
+        self.oc_properties_id = Some(doc.add_object(oc_properties));
+    }
+
+    /// Fills in the OCGs list and default configuration, keeping other entries
+    fn build_oc_properties(&self, mut oc_properties: Dictionary, mut default_dict: Dictionary) -> Dictionary {
+        let ocg_refs: Vec<Object> = self
+            .layers
+            .iter()
+            .map(|layer| Object::Reference(layer.id))
+            .collect();
+
+        let on_refs: Vec<Object> = self
+            .layers
+            .iter()
+            .filter(|layer| layer.default_visible)
+            .map(|layer| Object::Reference(layer.id))
+            .collect();
+
+        let off_refs: Vec<Object> = self
+            .layers
+            .iter()
+            .filter(|layer| !layer.default_visible)
+            .map(|layer| Object::Reference(layer.id))
+            .collect();
+
+        let order = match self.order {
+            Some(ref order) => write_order(order),
+            None => write_order(&self.build_order()),
+        };
+        default_dict.set("Order", order);
+
+        let locked: Vec<Object> = self
+            .layers
+            .iter()
+            .filter(|layer| layer.locked)
+            .map(|layer| Object::Reference(layer.id))
+            .collect();
+        default_dict.remove(b"Locked");
+        if !locked.is_empty() {
+            default_dict.set("Locked", locked);
+        }
+
+        let rb_groups: Vec<Object> = self
+            .radio_groups
+            .iter()
+            .map(|group| {
+                Object::Array(
+                    group
+                        .iter()
+                        .filter_map(|name| self.get_layer(name))
+                        .map(|layer| Object::Reference(layer.id))
+                        .collect(),
+                )
+            })
+            .filter(|group| group.as_array().is_ok_and(|members| !members.is_empty()))
+            .collect();
+        default_dict.remove(b"RBGroups");
+        if !rb_groups.is_empty() {
+            default_dict.set("RBGroups", rb_groups.clone());
+        }
+
+        let auto_state = self.auto_state();
+        default_dict.remove(b"AS");
+        if !auto_state.is_empty() {
+            default_dict.set("AS", auto_state.clone());
+        }
+
+        oc_properties.remove(b"Configs");
+        if !self.configurations.is_empty() {
+            let configs: Vec<Object> = self
+                .configurations
+                .iter()
+                .map(|config| {
+                    if let Some(ref source) = config.source {
+                        return Object::Dictionary(self.configuration_dictionary(config, source.clone()));
+                    }
+                    let mut dict = self.configuration_dictionary(config, Dictionary::new());
+                    // Alternate configurations do not inherit these from the default
+                    for key in [b"Order".as_slice(), b"Locked"] {
+                        if let Ok(value) = default_dict.get(key) {
+                            dict.set(key, value.clone());
+                        }
+                    }
+                    if self.config.create_panel_ui {
+                        let list_mode = default_dict
+                            .get(b"ListMode")
+                            .cloned()
+                            .unwrap_or_else(|_| "AllPages".into());
+                        dict.set("ListMode", list_mode);
+                    }
+                    if !rb_groups.is_empty() {
+                        dict.set("RBGroups", rb_groups.clone());
+                    }
+                    if !auto_state.is_empty() {
+                        dict.set("AS", auto_state.clone());
+                    }
+                    Object::Dictionary(dict)
+                })
+                .collect();
+            oc_properties.set("Configs", configs);
+        }
+
+        if !self.config.base_state.is_empty() {
+            default_dict.set(
+                "BaseState",
+                Object::Name(self.config.base_state.as_bytes().to_vec()),
+            );
+        }
+
+        default_dict.remove(b"ON");
+        if !on_refs.is_empty() {
+            default_dict.set("ON", on_refs);
+        }
+
+        default_dict.remove(b"OFF");
+        if !off_refs.is_empty() {
+            default_dict.set("OFF", off_refs);
+        }
+
+        if self.config.create_panel_ui {
+            if !default_dict.has(b"ListMode") {
+                default_dict.set("ListMode", "AllPages");
+            }
+        } else {
+            default_dict.remove(b"ListMode");
+        }
+
+        if !self.config.intent.is_empty() {
+            let intents = intent_object(&self.config.intent);
+            if default_dict.has(b"Intent") {
+                default_dict.set("Intent", intents.clone());
+            }
+            oc_properties.set("Intent", intents);
+        } else {
+            oc_properties.remove(b"Intent");
+        }
+
+        oc_properties.set("OCGs", ocg_refs);
+        oc_properties.set("D", default_dict);
+        oc_properties
+    }
+}
+
+/// A layer or group heading while building the order tree
+enum OrderNode<'a> {
+    Layer(&'a Layer),
+    Group(&'a LayerGroup),
+}
+
+impl OrderNode<'_> {
+    fn name(&self) -> &str {
+        match self {
+            OrderNode::Layer(layer) => &layer.name,
+            OrderNode::Group(group) => &group.label,
+        }
+    }
+
+    fn parent(&self) -> Option<&str> {
+        match self {
+            OrderNode::Layer(layer) => layer.parent.as_deref(),
+            OrderNode::Group(group) => group.parent.as_deref(),
+        }
+    }
+}
+
+/// The order items of every node whose parent is `parent`, recursively
+fn order_children(
+    nodes: &[OrderNode],
+    parent: Option<&str>,
+    known: &HashSet<&str>,
+    visited: &mut HashSet<usize>,
+) -> Vec<OrderItem> {
+    let mut items = Vec::new();
+    for (idx, node) in nodes.iter().enumerate() {
+        // Parents that do not exist put the node at the top level
+        let node_parent = node.parent().filter(|name| known.contains(name));
+        if node_parent != parent || !visited.insert(idx) {
+            continue;
+        }
+
+        match node {
+            OrderNode::Layer(layer) => {
+                items.push(OrderItem::Layer(layer.id));
+                let children = order_children(nodes, Some(&layer.name), known, visited);
+                if !children.is_empty() {
+                    items.push(OrderItem::Group {
+                        label: None,
+                        items: children,
+                    });
+                }
+            }
+            OrderNode::Group(group) => items.push(OrderItem::Group {
+                label: Some(group.label.clone()),
+                items: order_children(nodes, Some(&group.label), known, visited),
+            }),
+        }
+    }
+    items
+}
+
+/// Where an item is attached in an existing order tree
+enum OrderParent {
+    /// Nested under a layer
+    Layer(ObjectId),
+    /// Inside a labelled group
+    Label(String),
+}
+
+/// Adds an item under its parent in an order tree, or at the top level if the parent is
+/// not in the tree
+fn insert_into_order(order: &mut Vec<OrderItem>, item: OrderItem, parent: Option<&OrderParent>) {
+    if let Some(parent) = parent {
+        if let Err(item) = insert_under(order, item, parent) {
+            order.push(item);
+        }
+    } else {
+        order.push(item);
+    }
+}
+
+/// Inserts an item under `parent`, handing it back if the parent was not found
+fn insert_under(order: &mut Vec<OrderItem>, item: OrderItem, parent: &OrderParent) -> Result<(), OrderItem> {
+    let mut item = item;
+    for idx in 0..order.len() {
+        if let (OrderItem::Layer(id), OrderParent::Layer(parent_id)) = (&order[idx], parent) {
+            if id == parent_id {
+                // Children of a layer live in the unlabelled list right after it
+                match order.get_mut(idx + 1) {
+                    Some(OrderItem::Group { label: None, items }) => items.push(item),
+                    _ => order.insert(
+                        idx + 1,
+                        OrderItem::Group {
+                            label: None,
+                            items: vec![item],
+                        },
+                    ),
+                }
+                return Ok(());
+            }
+        }
+
+        if let OrderItem::Group { label, items } = &mut order[idx] {
+            if let (Some(label), OrderParent::Label(parent_label)) = (label.as_deref(), parent) {
+                if label == parent_label {
+                    items.push(item);
+                    return Ok(());
+                }
+            }
+            item = match insert_under(items, item, parent) {
+                Ok(()) => return Ok(()),
+                Err(item) => item,
+            };
+        }
+    }
+    Err(item)
+}
+
+/// Sets the `/OC` entry of an annotation or an image or form XObject.
+///
+/// `optional_content` must be an OCG or OCMD. The object is shown only while that
+/// layer or membership is visible, without BDC/EMC around the places it is used.
+pub fn set_optional_content(doc: &mut Document, object_id: ObjectId, optional_content: ObjectId) -> crate::Result<()> {
+    let oc_type = doc
+        .get_dictionary(optional_content)
+        .and_then(|dict| dict.get(b"Type"))
+        .and_then(Object::as_name)
+        .map(|name| name.to_vec())
+        .unwrap_or_default();
+    if oc_type != b"OCG" && oc_type != b"OCMD" {
+        return Err("Optional content must be an OCG or OCMD".into());
+    }
+
+    let dict = match doc.get_object_mut(object_id)? {
+        Object::Dictionary(dict) => dict,
+        Object::Stream(stream) => &mut stream.dict,
+        _ => return Err("Only dictionaries and streams can carry optional content".into()),
+    };
+
+    let is_annotation = dict.has(b"Rect") && dict.has(b"Subtype");
+    let is_xobject = matches!(
+        dict.get(b"Subtype").and_then(Object::as_name),
+        Ok(b"Form") | Ok(b"Image")
+    );
+    if !is_annotation && !is_xobject {
+        return Err("Optional content applies to annotations and image or form XObjects".into());
+    }
+
+    dict.set("OC", Object::Reference(optional_content));
+    Ok(())
+}
+
+/// Tags by layer name or membership tag; with duplicate names the first layer's tag wins
+fn tag_map(tags: Vec<(String, String)>) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    for (key, tag) in tags {
+        map.entry(key).or_insert(tag);
+    }
+    map
+}
+
+/// Creates the OCG dictionary for a layer
+fn ocg_dictionary(layer: &Layer) -> Dictionary {
+    let mut ocg = dictionary! {
+        "Type" => "OCG",
+        "Name" => encode_text_string(&layer.name),
+    };
+    if !layer.intent.is_empty() {
+        ocg.set("Intent", intent_object(&layer.intent));
+    }
+    if !layer.usage.is_empty() {
+        ocg.set("Usage", layer.usage.to_dictionary());
+    }
+    ocg
+}
+
+/// Intent names as an array of names
+fn intent_object(intent: &[String]) -> Object {
+    let names: Vec<Object> = intent
+        .iter()
+        .map(|s| Object::Name(s.as_bytes().to_vec()))
+        .collect();
+    Object::Array(names)
+}
+
+/// Reads an `/Intent` entry, which may be a single name or an array of names
+fn read_intent(doc: &Document, dict: &Dictionary) -> Option<Vec<String>> {
+    let (_, intent) = doc.dereference(dict.get(b"Intent").ok()?).ok()?;
+    let names = match intent {
+        Object::Name(name) => vec![name.clone()],
+        Object::Array(items) => items
+            .iter()
+            .filter_map(|item| item.as_name().ok())
+            .map(|name| name.to_vec())
+            .collect(),
+        _ => return None,
+    };
+    Some(
+        names
+            .iter()
+            .map(|name| String::from_utf8_lossy(name).to_string())
+            .collect(),
+    )
+}
+
+/// The object IDs listed in an array entry of a dictionary
+fn reference_set(doc: &Document, dict: &Dictionary, key: &[u8]) -> HashSet<ObjectId> {
+    dict.get(key)
+        .and_then(|value| doc.dereference(value))
+        .and_then(|(_, value)| value.as_array())
+        .map(|items| items.iter().filter_map(|item| item.as_reference().ok()).collect())
+        .unwrap_or_default()
+}
+
+/// Parses an `/Order` array, skipping references to objects that are not layers
+fn read_order(doc: &Document, items: &[Object], layer_ids: &HashSet<ObjectId>, depth: usize) -> Vec<OrderItem> {
+    if depth > MAX_ORDER_DEPTH {
+        return Vec::new();
+    }
+
+    items
+        .iter()
+        .filter_map(|item| match item {
+            Object::Reference(id) if layer_ids.contains(id) => Some(OrderItem::Layer(*id)),
+            Object::Reference(id) => match doc.get_object(*id) {
+                Ok(Object::Array(nested)) => Some(read_order_group(doc, nested, layer_ids, depth)),
+                _ => None,
+            },
+            Object::Array(nested) => Some(read_order_group(doc, nested, layer_ids, depth)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parses a nested `/Order` array, whose first element may be a label
+fn read_order_group(doc: &Document, nested: &[Object], layer_ids: &HashSet<ObjectId>, depth: usize) -> OrderItem {
+    match nested.first() {
+        Some(Object::String(label, _)) => OrderItem::Group {
+            label: Some(decode_text_string(label)),
+            items: read_order(doc, &nested[1..], layer_ids, depth + 1),
+        },
+        _ => OrderItem::Group {
+            label: None,
+            items: read_order(doc, nested, layer_ids, depth + 1),
+        },
     }
 }
 
-/// Main manager for Optional Content Groups in a PDF document
-pub struct OCGManager {
-    /// All layers in the document
-    pub(crate) layers: Vec<Layer>,
-    /// Configuration for the OCG system
-    pub config: OCGConfig,
-    /// The object ID of the OCProperties dictionary
-    pub(crate) oc_properties_id: Option<ObjectId>,
-    /// Mapping from layer names to their index
-    layer_index: HashMap<String, usize>,
+/// Encodes an order tree as an `/Order` array
+fn write_order(order: &[OrderItem]) -> Vec<Object> {
+    order
+        .iter()
+        .map(|item| match item {
+            OrderItem::Layer(id) => Object::Reference(*id),
+            OrderItem::Group { label, items } => {
+                let mut array = Vec::new();
+                if let Some(label) = label {
+                    array.push(encode_text_string(label));
+                }
+                array.extend(write_order(items));
+                Object::Array(array)
+            }
+        })
+        .collect()
 }
 
-impl Default for OCGManager {
-    fn default() -> Self {
-        Self::new()
+/// Removes a layer from an order tree. Layers nested under it move up to its place.
+fn remove_from_order(order: &mut Vec<OrderItem>, id: ObjectId) {
+    let mut idx = 0;
+    while idx < order.len() {
+        if order[idx] == OrderItem::Layer(id) {
+            order.remove(idx);
+            if let Some(OrderItem::Group { label: None, .. }) = order.get(idx) {
+                if let OrderItem::Group { items, .. } = order.remove(idx) {
+                    order.splice(idx..idx, items);
+                }
+            }
+            continue;
+        }
+        if let OrderItem::Group { items, .. } = &mut order[idx] {
+            remove_from_order(items, id);
+        }
+        idx += 1;
     }
 }
 
-impl OCGManager {
-    /// Creates a new OCGManager with default configuration
-    pub fn new() -> Self {
-        OCGManager {
-            layers: Vec::new(),
-            config: OCGConfig::default(),
-            oc_properties_id: None,
-            layer_index: HashMap::new(),
+/// Removes a layer from an order tree along with the layers nested under it, returning
+/// those nested items
+fn take_from_order(order: &mut Vec<OrderItem>, id: ObjectId) -> Vec<OrderItem> {
+    let mut idx = 0;
+    while idx < order.len() {
+        if order[idx] == OrderItem::Layer(id) {
+            order.remove(idx);
+            return match order.get(idx) {
+                Some(OrderItem::Group { label: None, .. }) => match order.remove(idx) {
+                    OrderItem::Group { items, .. } => items,
+                    OrderItem::Layer(_) => Vec::new(),
+                },
+                _ => Vec::new(),
+            };
         }
+        if let OrderItem::Group { label, items } = &mut order[idx] {
+            let before = items.len();
+            let children = take_from_order(items, id);
+            if items.len() != before {
+                if items.is_empty() && label.is_none() {
+                    order.remove(idx);
+                }
+                return children;
+            }
+        }
+        idx += 1;
     }
+    Vec::new()
+}
 
-    /// Creates a new OCGManager with custom configuration
-    pub fn with_config(config: OCGConfig) -> Self {
-        OCGManager {
-            layers: Vec::new(),
-            config,
-            oc_properties_id: None,
-            layer_index: HashMap::new(),
+/// Drops references to the given objects from every array, recursively
+fn strip_references(obj: &mut Object, removed: &HashSet<ObjectId>) {
+    match obj {
+        Object::Array(items) => {
+            items.retain(|item| !matches!(item, Object::Reference(id) if removed.contains(id)));
+            items.iter_mut().for_each(|item| strip_references(item, removed));
+        }
+        Object::Dictionary(dict) => {
+            for (_, value) in dict.iter_mut() {
+                strip_references(value, removed);
+            }
         }
+        _ => {}
     }
+}
 
-    /// Adds a new layer to the manager
-    ///
-    /// # Arguments
-    /// * `layer` - The layer to add
-    ///
-    /// # Returns
-    /// The index of the added layer
-    pub fn add_layer(&mut self, layer: Layer) -> usize {
-        let index = self.layers.len();
-        self.layer_index.insert(layer.name.clone(), index);
-        self.layers.push(layer);
-        index
+/// Removes `/Properties` resource entries that point to the given objects
+fn remove_property_references(doc: &mut Document, removed: &HashSet<ObjectId>) {
+    fn remove_stale(properties: &mut Dictionary, removed: &HashSet<ObjectId>) {
+        let stale: Vec<Vec<u8>> = properties
+            .iter()
+            .filter(|(_, value)| matches!(value, Object::Reference(id) if removed.contains(id)))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in stale {
+            properties.remove(&key);
+        }
     }
 
-    /// Gets a layer by name
-    pub fn get_layer(&self, name: &str) -> Option<&Layer> {
-        self.layer_index
-            .get(name)
-            .and_then(|&idx| self.layers.get(idx))
+    fn clean(dict: &mut Dictionary, removed: &HashSet<ObjectId>, indirect: &mut Vec<ObjectId>) {
+        match dict.get_mut(b"Properties") {
+            Ok(Object::Dictionary(properties)) => remove_stale(properties, removed),
+            Ok(Object::Reference(id)) => indirect.push(*id),
+            _ => {}
+        }
+        if let Ok(Object::Dictionary(resources)) = dict.get_mut(b"Resources") {
+            clean(resources, removed, indirect);
+        }
     }
 
-    /// Gets a mutable layer by name
-    pub fn get_layer_mut(&mut self, name: &str) -> Option<&mut Layer> {
-        if let Some(&idx) = self.layer_index.get(name) {
-            self.layers.get_mut(idx)
-        } else {
-            None
+    let mut indirect = Vec::new();
+    for obj in doc.objects.values_mut() {
+        match obj {
+            Object::Dictionary(dict) => clean(dict, removed, &mut indirect),
+            Object::Stream(stream) => clean(&mut stream.dict, removed, &mut indirect),
+            _ => {}
         }
     }
-
-    /// Get the number of layers
-    pub fn len(&self) -> usize {
-        self.layers.len()
+    for id in indirect {
+        if let Ok(properties) = doc.get_dictionary_mut(id) {
+            remove_stale(properties, removed);
+        }
     }
+}
 
-    /// Check if there are no layers
-    pub fn is_empty(&self) -> bool {
-        self.layers.is_empty()
+/// Moves content marked with the given OCGs off its layer before the OCGs are deleted.
+///
+/// `BDC /OC` sections tagged with them lose their markers, keeping what they draw, and
+/// `/OC` entries on annotations and XObjects and `/OCGs` entries of OCMDs stop naming them.
+/// Streams that cannot be decoded are left alone.
+fn unmark_removed_layers(doc: &mut Document, removed: &HashSet<ObjectId>) {
+    let page_ids: Vec<ObjectId> = doc.page_iter().collect();
+    for page_id in page_ids {
+        let Ok(page) = doc.get_dictionary(page_id) else {
+            continue;
+        };
+        let resources = pages::get_inherited_attribute(doc, page, b"Resources")
+            .and_then(|resources| doc.dereference(resources).ok())
+            .and_then(|(_, resources)| resources.as_dict().ok());
+        let tags = removed_tags(doc, resources, removed);
+        if tags.is_empty() {
+            continue;
+        }
+        let Ok(content) = doc.get_page_content(page_id).and_then(|content| Content::decode(&content)) else {
+            continue;
+        };
+        let operations = strip_marked_sections(content.operations, &tags);
+        let Ok(encoded) = (Content { operations }).encode() else {
+            continue;
+        };
+        let content_id = doc.add_object(Stream::new(Dictionary::new(), encoded));
+        if let Ok(page) = doc.get_dictionary_mut(page_id) {
+            page.set("Contents", content_id);
+        }
     }
 
-    /// Check if OCProperties has been initialized
-    pub fn has_oc_properties(&self) -> bool {
-        self.oc_properties_id.is_some()
+    let form_ids: Vec<ObjectId> = doc
+        .objects
+        .iter()
+        .filter(|(_, obj)| {
+            matches!(
+                obj.as_stream().and_then(|stream| stream.dict.get(b"Subtype")).and_then(Object::as_name),
+                Ok(b"Form")
+            )
+        })
+        .map(|(id, _)| *id)
+        .collect();
+    for form_id in form_ids {
+        let Ok(form) = doc.get_object(form_id).and_then(Object::as_stream) else {
+            continue;
+        };
+        let resources = form
+            .dict
+            .get(b"Resources")
+            .and_then(|resources| doc.dereference(resources))
+            .and_then(|(_, resources)| resources.as_dict())
+            .ok();
+        let tags = removed_tags(doc, resources, removed);
+        if tags.is_empty() {
+            continue;
+        }
+        let Ok(content) = form.get_plain_content().and_then(|content| Content::decode(&content)) else {
+            continue;
+        };
+        let operations = strip_marked_sections(content.operations, &tags);
+        let Ok(encoded) = (Content { operations }).encode() else {
+            continue;
+        };
+        if let Ok(Object::Stream(form)) = doc.get_object_mut(form_id) {
+            form.set_plain_content(encoded);
+        }
     }
 
-    /// Initializes all layers in the PDF document
-    /// This should be called after all layers have been added but before they are used
-    pub fn initialize(&mut self, doc: &mut Document) {
-        // Create OCG objects for each layer
-        for layer in &mut self.layers {
-            let ocg_dict = dictionary! {
-                "Type" => "OCG",
-                "Name" => Object::string_literal(layer.name.as_bytes().to_vec()),
-            };
-            layer.id = doc.add_object(ocg_dict);
+    for obj in doc.objects.values_mut() {
+        let dict = match obj {
+            Object::Dictionary(dict) => dict,
+            Object::Stream(stream) => &mut stream.dict,
+            _ => continue,
+        };
+        if matches!(dict.get(b"OC"), Ok(Object::Reference(id)) if removed.contains(id)) {
+            dict.remove(b"OC");
+        }
+        if dict.get(b"Type").and_then(Object::as_name).ok() == Some(b"OCMD".as_slice()) {
+            match dict.get_mut(b"OCGs") {
+                Ok(Object::Reference(id)) if removed.contains(id) => {
+                    dict.remove(b"OCGs");
+                }
+                Ok(ocgs @ Object::Array(_)) => strip_references(ocgs, removed),
+                _ => {}
+            }
         }
-
-        // Create the OCProperties dictionary
-        self.create_oc_properties(doc);
     }
+}
 
-    /// Prepares a page's resources dictionary to use layers
-    ///
-    /// # Arguments
-    /// * `resources` - The page's resources dictionary
-    ///
-    /// # Returns
-    /// A map from layer names to their resource tags
-    pub fn setup_page_resources(&mut self, resources: &mut Dictionary) -> HashMap<String, String> {
-        let mut properties = Dictionary::new();
-        let mut layer_map = HashMap::new();
+/// The `/Properties` tags of a resources dictionary that name one of the given OCGs
+fn removed_tags(doc: &Document, resources: Option<&Dictionary>, removed: &HashSet<ObjectId>) -> HashSet<Vec<u8>> {
+    resources
+        .and_then(|resources| resources.get(b"Properties").ok())
+        .and_then(|properties| doc.dereference(properties).ok())
+        .and_then(|(_, properties)| properties.as_dict().ok())
+        .map(|properties| {
+            properties
+                .iter()
+                .filter(|(_, value)| matches!(value, Object::Reference(id) if removed.contains(id)))
+                .map(|(tag, _)| tag.clone())
+                .collect()
+        })
+        .unwrap_or_default()
+}
 
-        for (i, layer) in self.layers.iter_mut().enumerate() {
-            let tag = format!("L{}", i);
-            properties.set(tag.clone(), Object::Reference(layer.id));
-            layer.tag = Some(tag.clone());
-            layer_map.insert(layer.name.clone(), tag);
-        }
+/// Drops the `BDC /OC` markers of sections using the given tags, and their `EMC`
+fn strip_marked_sections(operations: Vec<Operation>, tags: &HashSet<Vec<u8>>) -> Vec<Operation> {
+    let mut sections = Vec::new();
+    operations
+        .into_iter()
+        .filter(|op| match op.operator.as_str() {
+            "BDC" if op.operands.first().and_then(|tag| tag.as_name().ok()) == Some(b"OC") => {
+                let stripped = op
+                    .operands
+                    .get(1)
+                    .and_then(|tag| tag.as_name().ok())
+                    .is_some_and(|tag| tags.contains(tag));
+                sections.push(stripped);
+                !stripped
+            }
+            "BMC" | "BDC" => {
+                sections.push(false);
+                true
+            }
+            "EMC" => sections.pop() != Some(true),
+            _ => true,
+        })
+        .collect()
+}
 
-        resources.set("Properties", properties);
-        layer_map
-    }
+/// Path-painting operators, replaced by `n` when their path is hidden
+const PATH_PAINTING_OPERATORS: [&str; 9] = ["S", "s", "f", "F", "f*", "B", "B*", "b", "b*"];
 
-    /// Updates the document catalog to include OCProperties
-    pub fn update_catalog(&self, doc: &mut Document) {
-        if let Some(oc_props_id) = self.oc_properties_id {
-            // Find the catalog by checking the trailer root
-            if let Ok(Object::Reference(catalog_id)) = doc.trailer.get(b"Root") {
-                if let Ok(Object::Dictionary(ref mut catalog)) = doc.get_object_mut(*catalog_id) {
-                    catalog.set("OCProperties", Object::Reference(oc_props_id));
-                }
+/// Forms copied while filtering, under their new resource names
+type RenamedXObjects = Vec<(Vec<u8>, ObjectId)>;
+
+/// Kind of marked-content section open while filtering a content stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MarkedSection {
+    /// Optional content that is hidden, or any section nested in it
+    Hidden,
+    /// Optional content that is visible; its markers are dropped
+    Layer,
+    /// Other marked content, such as `/Span` or `/Artifact`, which is kept
+    Marked,
+}
+
+/// Rewrites content so only the content of visible layers is drawn.
+///
+/// Hidden content keeps its graphics state operators, as viewers still apply them,
+/// and loses only the operators that paint; hidden text is drawn invisibly instead.
+struct LayerFilter<'a> {
+    visible: &'a HashSet<ObjectId>,
+    /// Whether content outside any layer is drawn
+    keep_unlayered: bool,
+    /// Filtered form XObjects, keyed by form and whether unlayered content in it is drawn
+    forms: HashMap<(ObjectId, bool), ObjectId>,
+}
+
+impl LayerFilter<'_> {
+    /// Filters the pages, annotations and forms of a document and removes its optional content
+    fn apply(&mut self, doc: &mut Document) -> crate::Result<()> {
+        let page_ids: Vec<ObjectId> = doc.page_iter().collect();
+        for page_id in page_ids {
+            let page = doc.get_dictionary(page_id)?;
+            let resources = pages::get_inherited_attribute(doc, page, b"Resources")
+                .and_then(|resources| doc.dereference(resources).ok())
+                .and_then(|(_, resources)| resources.as_dict().ok())
+                .cloned()
+                .unwrap_or_default();
+
+            let content = Content::decode(&doc.get_page_content(page_id)?)?;
+            let (operations, renamed) = self.filter_operations(doc, content.operations, &resources, false)?;
+            let content_id = doc.add_object(Stream::new(
+                Dictionary::new(),
+                Content { operations }.encode()?,
+            ));
+
+            let annotations = self.visible_annotations(doc, page_id);
+            let page = doc.get_dictionary_mut(page_id)?;
+            page.set("Contents", content_id);
+            if let Some(annotations) = annotations {
+                page.set("Annots", annotations);
+            }
+            if !renamed.is_empty() {
+                page.set("Resources", with_xobjects(resources, renamed));
             }
         }
-    }
 
-    /// Creates the OCProperties dictionary in the document
-    fn create_oc_properties(&mut self, doc: &mut Document) {
-        let ocg_refs: Vec<Object> = self
-            .layers
+        // Nothing optional is left: drop the layers and every reference to them
+        let optional_content: HashSet<ObjectId> = doc
+            .objects
             .iter()
-            .map(|layer| Object::Reference(layer.id))
+            .filter(|(_, obj)| {
+                matches!(
+                    obj.as_dict().and_then(|dict| dict.get(b"Type")).and_then(Object::as_name),
+                    Ok(b"OCG") | Ok(b"OCMD")
+                )
+            })
+            .map(|(id, _)| *id)
             .collect();
+        remove_property_references(doc, &optional_content);
+        for obj in doc.objects.values_mut() {
+            match obj {
+                Object::Dictionary(dict) => dict.remove(b"OC"),
+                Object::Stream(stream) => stream.dict.remove(b"OC"),
+                _ => None,
+            };
+        }
+        if let Ok(catalog) = doc.catalog_mut() {
+            catalog.remove(b"OCProperties");
+        }
+        doc.prune_objects();
+        Ok(())
+    }
 
-        let on_refs: Vec<Object> = self
-            .layers
-            .iter()
-            .filter(|layer| layer.default_visible)
-            .map(|layer| Object::Reference(layer.id))
-            .collect();
+    /// The page's annotations that stay, or None when the page has none
+    fn visible_annotations(&self, doc: &Document, page_id: ObjectId) -> Option<Vec<Object>> {
+        let annotations = doc
+            .get_dictionary(page_id)
+            .ok()?
+            .get(b"Annots")
+            .and_then(|annots| doc.dereference(annots))
+            .and_then(|(_, annots)| annots.as_array())
+            .ok()?;
 
-        let off_refs: Vec<Object> = self
-            .layers
+        let kept = annotations
             .iter()
-            .filter(|layer| !layer.default_visible)
-            .map(|layer| Object::Reference(layer.id))
+            .filter(|annotation| {
+                let oc = doc
+                    .dereference(annotation)
+                    .and_then(|(_, annotation)| annotation.as_dict())
+                    .and_then(|annotation| annotation.get(b"OC"));
+                match oc {
+                    Ok(oc) => is_content_visible(doc, oc, self.visible),
+                    Err(_) => self.keep_unlayered,
+                }
+            })
+            .cloned()
             .collect();
+        Some(kept)
+    }
 
-        let mut default_dict = dictionary! {
-            "Order" => ocg_refs.clone(),
-        };
+    /// Filters a content stream's operations.
+    ///
+    /// Returns the new operations and the forms that had to be copied, under their new
+    /// resource names. `in_layer` marks content drawn by a form invoked inside a layer.
+    fn filter_operations(
+        &mut self,
+        doc: &mut Document,
+        operations: Vec<Operation>,
+        resources: &Dictionary,
+        in_layer: bool,
+    ) -> crate::Result<(Vec<Operation>, RenamedXObjects)> {
+        let mut filtered = Vec::with_capacity(operations.len());
+        let mut renamed = Vec::new();
+        let mut sections: Vec<MarkedSection> = Vec::new();
+        // Text rendering mode, saved and restored with the graphics state; forms are
+        // assumed to start in the default fill mode
+        let mut render_mode = Object::Integer(0);
+        let mut saved_modes = Vec::new();
 
-        if !self.config.base_state.is_empty() {
-            default_dict.set(
-                "BaseState",
-                Object::Name(self.config.base_state.as_bytes().to_vec()),
-            );
-        }
+        for op in operations {
+            let hidden = sections.contains(&MarkedSection::Hidden);
+            let layered = in_layer || sections.contains(&MarkedSection::Layer);
+            let drawn = !hidden && (layered || self.keep_unlayered);
 
-        if !on_refs.is_empty() {
-            default_dict.set("ON", on_refs);
+            match op.operator.as_str() {
+                "q" => saved_modes.push(render_mode.clone()),
+                "Q" => render_mode = saved_modes.pop().unwrap_or(Object::Integer(0)),
+                "Tr" => render_mode = op.operands.first().cloned().unwrap_or(Object::Integer(0)),
+                _ => {}
+            }
+
+            match op.operator.as_str() {
+                "BDC" if op.operands.first().and_then(|tag| tag.as_name().ok()) == Some(b"OC") => {
+                    let visible = op
+                        .operands
+                        .get(1)
+                        .and_then(|properties| lookup_properties(doc, resources, properties))
+                        .map(|oc| is_content_visible(doc, &oc, self.visible))
+                        .unwrap_or(true);
+                    sections.push(if hidden || !visible {
+                        MarkedSection::Hidden
+                    } else {
+                        MarkedSection::Layer
+                    });
+                }
+                "BMC" | "BDC" => {
+                    if hidden {
+                        sections.push(MarkedSection::Hidden);
+                    } else {
+                        sections.push(MarkedSection::Marked);
+                        filtered.push(op);
+                    }
+                }
+                "EMC" => {
+                    // Unbalanced EMC operators are dropped
+                    if sections.pop() == Some(MarkedSection::Marked) {
+                        filtered.push(op);
+                    }
+                }
+                "MP" | "DP" => {
+                    if !hidden {
+                        filtered.push(op);
+                    }
+                }
+                "Do" => {
+                    if hidden {
+                        continue;
+                    }
+                    let Some(name) = op.operands.first().and_then(|name| name.as_name().ok()) else {
+                        continue;
+                    };
+                    let xobject_id = resources
+                        .get(b"XObject")
+                        .and_then(|xobjects| doc.dereference(xobjects))
+                        .and_then(|(_, xobjects)| xobjects.as_dict())
+                        .and_then(|xobjects| xobjects.get(name))
+                        .and_then(Object::as_reference);
+                    let Ok(xobject_id) = xobject_id else {
+                        if drawn {
+                            filtered.push(op);
+                        }
+                        continue;
+                    };
+
+                    let (is_form, oc) = match doc.get_object(xobject_id).and_then(Object::as_stream) {
+                        Ok(stream) => (
+                            matches!(stream.dict.get(b"Subtype").and_then(Object::as_name), Ok(b"Form")),
+                            stream.dict.get(b"OC").ok().cloned(),
+                        ),
+                        Err(_) => (false, None),
+                    };
+                    if oc.as_ref().is_some_and(|oc| !is_content_visible(doc, oc, self.visible)) {
+                        continue;
+                    }
+
+                    if is_form {
+                        let context_drawn = layered || oc.is_some() || self.keep_unlayered;
+                        let form_id = self.filter_form(doc, xobject_id, context_drawn)?;
+                        if form_id == xobject_id {
+                            filtered.push(op);
+                        } else {
+                            let name = format!("{}_{}", String::from_utf8_lossy(name), form_id.0).into_bytes();
+                            filtered.push(Operation::new("Do", vec![Object::Name(name.clone())]));
+                            renamed.push((name, form_id));
+                        }
+                    } else if drawn || oc.is_some() {
+                        filtered.push(op);
+                    }
+                }
+                operator if PATH_PAINTING_OPERATORS.contains(&operator) => {
+                    if drawn {
+                        filtered.push(op);
+                    } else {
+                        // End the path without painting it
+                        filtered.push(Operation::new("n", vec![]));
+                    }
+                }
+                "sh" | "BI" => {
+                    if drawn {
+                        filtered.push(op);
+                    }
+                }
+                "Tj" | "TJ" | "'" | "\"" if !drawn => {
+                    // Hidden text is kept but made invisible, so text after it in the
+                    // same text object still starts where the hidden text ended
+                    filtered.push(Operation::new("Tr", vec![3.into()]));
+                    filtered.push(op);
+                    filtered.push(Operation::new("Tr", vec![render_mode.clone()]));
+                }
+                _ => filtered.push(op),
+            }
         }
 
-        if !off_refs.is_empty() {
-            default_dict.set("OFF", off_refs);
+        Ok((filtered, renamed))
+    }
+
+    /// Filters a form XObject and returns the form to draw instead.
+    ///
+    /// Forms drawn where unlayered content is shown are filtered in place; elsewhere
+    /// they are copied, since the same form may be drawn in both places.
+    fn filter_form(&mut self, doc: &mut Document, form_id: ObjectId, context_drawn: bool) -> crate::Result<ObjectId> {
+        if let Some(&filtered_id) = self.forms.get(&(form_id, context_drawn)) {
+            return Ok(filtered_id);
         }
+        let filtered_id = if context_drawn { form_id } else { doc.new_object_id() };
+        // Registered before filtering so forms that draw themselves terminate
+        self.forms.insert((form_id, context_drawn), filtered_id);
 
-        if self.config.create_panel_ui {
-            default_dict.set("ListMode", "AllPages");
+        let mut form = doc.get_object(form_id)?.as_stream()?.clone();
+        let resources = form
+            .dict
+            .get(b"Resources")
+            .and_then(|resources| doc.dereference(resources))
+            .and_then(|(_, resources)| resources.as_dict())
+            .cloned()
+            .unwrap_or_default();
+
+        let content = Content::decode(&form.get_plain_content()?)?;
+        let (operations, renamed) = self.filter_operations(doc, content.operations, &resources, context_drawn)?;
+        // `/OC` stays until the final cleanup: other pages may still draw this form
+        form.set_plain_content(Content { operations }.encode()?);
+        if !renamed.is_empty() {
+            form.dict.set("Resources", with_xobjects(resources, renamed));
         }
 
-        let mut oc_properties = dictionary! {
-            "OCGs" => ocg_refs,
-            "D" => default_dict,
-        };
+        doc.objects.insert(filtered_id, Object::Stream(form));
+        Ok(filtered_id)
+    }
+}
 
-        if !self.config.intent.is_empty() {
-            let intents: Vec<Object> = self
-                .config
-                .intent
+/// Adds XObject entries to a resources dictionary
+fn with_xobjects(mut resources: Dictionary, xobjects: RenamedXObjects) -> Dictionary {
+    let mut xobject_dict = resources
+        .get(b"XObject")
+        .and_then(Object::as_dict)
+        .cloned()
+        .unwrap_or_default();
+    for (name, id) in xobjects {
+        xobject_dict.set(name, Object::Reference(id));
+    }
+    resources.set("XObject", xobject_dict);
+    resources
+}
+
+/// Resolves the properties operand of a `BDC` operator to its OCG or OCMD
+fn lookup_properties(doc: &Document, resources: &Dictionary, properties: &Object) -> Option<Object> {
+    match properties {
+        Object::Name(name) => resources
+            .get(b"Properties")
+            .and_then(|properties| doc.dereference(properties))
+            .and_then(|(_, properties)| properties.as_dict())
+            .and_then(|properties| properties.get(name))
+            .ok()
+            .cloned(),
+        Object::Dictionary(_) => Some(properties.clone()),
+        _ => None,
+    }
+}
+
+/// Whether content tagged with an OCG or OCMD is visible when exactly the given OCGs are on
+fn is_content_visible(doc: &Document, oc: &Object, visible: &HashSet<ObjectId>) -> bool {
+    let (id, dict) = match oc {
+        Object::Reference(id) => (Some(*id), doc.get_dictionary(*id).ok()),
+        Object::Dictionary(dict) => (None, Some(dict)),
+        _ => (None, None),
+    };
+    let Some(dict) = dict else {
+        return true;
+    };
+
+    match dict.get(b"Type").and_then(Object::as_name) {
+        Ok(b"OCG") => id.is_some_and(|id| visible.contains(&id)),
+        Ok(b"OCMD") => {
+            if let Ok(expression) = dict.get(b"VE") {
+                return evaluate_expression(doc, expression, visible, 0);
+            }
+            let ocgs: Vec<bool> = match dict.get(b"OCGs") {
+                Ok(Object::Reference(id)) => vec![visible.contains(id)],
+                Ok(Object::Array(items)) => items
+                    .iter()
+                    .filter_map(|item| item.as_reference().ok())
+                    .map(|id| visible.contains(&id))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            if ocgs.is_empty() {
+                return true;
+            }
+            match dict.get(b"P").and_then(Object::as_name) {
+                Ok(b"AllOn") => ocgs.iter().all(|&on| on),
+                Ok(b"AnyOff") => ocgs.iter().any(|&on| !on),
+                Ok(b"AllOff") => ocgs.iter().all(|&on| !on),
+                _ => ocgs.iter().any(|&on| on),
+            }
+        }
+        _ => true,
+    }
+}
+
+/// Evaluates an OCMD visibility expression (`/VE`)
+fn evaluate_expression(doc: &Document, expression: &Object, visible: &HashSet<ObjectId>, depth: usize) -> bool {
+    if depth > MAX_ORDER_DEPTH {
+        return true;
+    }
+    match expression {
+        Object::Reference(id) => match doc.get_object(*id) {
+            Ok(nested @ Object::Array(_)) => evaluate_expression(doc, nested, visible, depth + 1),
+            _ => visible.contains(id),
+        },
+        Object::Array(items) => {
+            let mut operands = items[1.min(items.len())..]
                 .iter()
-                .map(|s| Object::Name(s.as_bytes().to_vec()))
-                .collect();
-            oc_properties.set("Intent", intents);
+                .map(|operand| evaluate_expression(doc, operand, visible, depth + 1));
+            match items.first().and_then(|operator| operator.as_name().ok()) {
+                Some(b"And") => operands.all(|on| on),
+                Some(b"Or") => operands.any(|on| on),
+                Some(b"Not") => !operands.next().unwrap_or(false),
+                _ => true,
+            }
         }
+        _ => true,
+    }
+}
 
-        self.oc_properties_id = Some(doc.add_object(oc_properties));
+/// Decodes a PDF text string: UTF-16BE with a byte order mark, UTF-8 as written by
+/// earlier versions of this crate, or otherwise PDFDocEncoding
+pub(crate) fn decode_text_string(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else if let Ok(text) = std::str::from_utf8(bytes) {
+        text.to_string()
+    } else {
+        // Close enough to PDFDocEncoding for names outside the ASCII range
+        bytes.iter().map(|&b| b as char).collect()
+    }
+}
+
+/// Encodes a PDF text string, using UTF-16BE when it is not plain ASCII
+pub(crate) fn encode_text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        Object::string_literal(text)
+    } else {
+        let mut bytes = vec![0xFE, 0xFF];
+        bytes.extend(text.encode_utf16().flat_map(|unit| unit.to_be_bytes()));
+        Object::String(bytes, lopdf::StringFormat::Hexadecimal)
+    }
+}
+
+/// A marked-content section open in a [`LayerContentBuilder`]
+#[derive(Debug, Clone, PartialEq)]
+enum OpenSection {
+    /// Optional content opened with `BDC /OC`, by resource tag
+    Layer(String),
+    /// Other marked content, by marked-content tag
+    Marked(String),
+}
+
+impl OpenSection {
+    fn describe(&self) -> String {
+        match self {
+            OpenSection::Layer(tag) => format!("layer /{}", tag),
+            OpenSection::Marked(tag) => format!("marked content /{}", tag),
+        }
     }
 }
 
-/// Builder for creating layered content in a PDF content stream
+/// Builder for creating layered content in a PDF content stream.
+///
+/// Sections form a stack: a layer begun inside another is nested in it, and each
+/// `end_*` call closes the innermost section.
 pub struct LayerContentBuilder {
     operations: Vec<Operation>,
-    current_layer: Option<String>,
+    open_sections: Vec<OpenSection>,
+    /// First nesting mistake, reported by [`build`](Self::build)
+    unbalanced: Option<String>,
 }
 
 impl Default for LayerContentBuilder {
@@ -274,19 +2651,16 @@ impl LayerContentBuilder {
     pub fn new() -> Self {
         LayerContentBuilder {
             operations: Vec::new(),
-            current_layer: None,
+            open_sections: Vec::new(),
+            unbalanced: None,
         }
     }
 
-    /// Begins content for a specific layer
+    /// Begins content for a specific layer, nested in any section already open
     ///
     /// # Arguments
     /// * `layer_tag` - The resource tag for the layer (from setup_page_resources)
     pub fn begin_layer(&mut self, layer_tag: &str) -> &mut Self {
-        if self.current_layer.is_some() {
-            self.end_layer();
-        }
-
         self.operations.push(Operation::new(
             "BDC",
             vec![
@@ -294,15 +2668,82 @@ impl LayerContentBuilder {
                 Object::Name(layer_tag.as_bytes().to_vec()),
             ],
         ));
-        self.current_layer = Some(layer_tag.to_string());
+        self.open_sections.push(OpenSection::Layer(layer_tag.to_string()));
         self
     }
 
-    /// Ends the current layer
+    /// Ends the innermost layer
     pub fn end_layer(&mut self) -> &mut Self {
-        if self.current_layer.is_some() {
-            self.operations.push(Operation::new("EMC", vec![]));
-            self.current_layer = None;
+        self.close_section(|section| matches!(section, OpenSection::Layer(_)), "end_layer")
+    }
+
+    /// Adds content to a layer, closing the layer afterwards
+    pub fn with_layer(&mut self, layer_tag: &str, content: impl FnOnce(&mut Self)) -> &mut Self {
+        self.begin_layer(layer_tag);
+        content(self);
+        self.end_layer()
+    }
+
+    /// Begins a marked-content section without properties (`BMC`), such as `/Artifact`
+    pub fn begin_marked_content(&mut self, tag: &str) -> &mut Self {
+        self.operations
+            .push(Operation::new("BMC", vec![Object::Name(tag.as_bytes().to_vec())]));
+        self.open_sections.push(OpenSection::Marked(tag.to_string()));
+        self
+    }
+
+    /// Begins a marked-content section with properties (`BDC`).
+    ///
+    /// `properties` is either an inline property list dictionary or the name of one in
+    /// the resources' `/Properties`.
+    pub fn begin_marked_content_with_properties(&mut self, tag: &str, properties: impl Into<Object>) -> &mut Self {
+        self.operations.push(Operation::new(
+            "BDC",
+            vec![Object::Name(tag.as_bytes().to_vec()), properties.into()],
+        ));
+        self.open_sections.push(OpenSection::Marked(tag.to_string()));
+        self
+    }
+
+    /// Ends the innermost marked-content section
+    pub fn end_marked_content(&mut self) -> &mut Self {
+        self.close_section(|section| matches!(section, OpenSection::Marked(_)), "end_marked_content")
+    }
+
+    /// Adds content to a marked-content section, closing the section afterwards
+    pub fn with_marked_content(&mut self, tag: &str, content: impl FnOnce(&mut Self)) -> &mut Self {
+        self.begin_marked_content(tag);
+        content(self);
+        self.end_marked_content()
+    }
+
+    /// Number of sections currently open
+    pub fn depth(&self) -> usize {
+        self.open_sections.len()
+    }
+
+    /// Tag of the innermost open layer, if any
+    pub fn current_layer(&self) -> Option<&str> {
+        self.open_sections.iter().rev().find_map(|section| match section {
+            OpenSection::Layer(tag) => Some(tag.as_str()),
+            OpenSection::Marked(_) => None,
+        })
+    }
+
+    /// Emits `EMC` for the innermost section, noting a mistake if it is not of the expected kind
+    fn close_section(&mut self, expected: impl Fn(&OpenSection) -> bool, call: &str) -> &mut Self {
+        match self.open_sections.pop() {
+            Some(section) => {
+                if !expected(&section) && self.unbalanced.is_none() {
+                    self.unbalanced = Some(format!("{} closed {}", call, section.describe()));
+                }
+                self.operations.push(Operation::new("EMC", vec![]));
+            }
+            None => {
+                if self.unbalanced.is_none() {
+                    self.unbalanced = Some(format!("{} called with no open section", call));
+                }
+            }
         }
         self
     }
@@ -319,12 +2760,18 @@ impl LayerContentBuilder {
         self
     }
 
-    /// Builds the final operations list, ensuring all layers are closed
-    pub fn build(mut self) -> Vec<Operation> {
-        if self.current_layer.is_some() {
-            self.end_layer();
+    /// Builds the final operations list.
+    ///
+    /// Fails if a section was left open or closed with the wrong `end_*` call.
+    pub fn build(self) -> crate::Result<Vec<Operation>> {
+        if let Some(mistake) = self.unbalanced {
+            return Err(format!("Unbalanced marked content: {}", mistake).into());
         }
-        self.operations
+        if !self.open_sections.is_empty() {
+            let open: Vec<String> = self.open_sections.iter().map(OpenSection::describe).collect();
+            return Err(format!("Unclosed marked content: {}", open.join(", ")).into());
+        }
+        Ok(self.operations)
     }
 }
 
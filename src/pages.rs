@@ -0,0 +1,451 @@
+//! Page-level document operations
+//!
+//! This module provides functions for restructuring the page tree of a document:
+//! reordering, deleting and inserting pages, and appending content to existing pages.
+//! Merging pages from other PDFs and splitting a PDF into several documents is done
+//! through [`PdfEmbedder`], which shares its loaded-document cache with the embedding API.
+
+use crate::embed_pdf::{PageRange, PdfEmbedder};
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Dictionary, Document, Object, ObjectId, Stream,
+};
+use std::collections::HashSet;
+use std::io::{Error, ErrorKind, Result};
+
+/// Page attributes that may be inherited from ancestor Pages nodes
+pub(crate) const INHERITABLE_PAGE_KEYS: [&[u8]; 4] =
+    [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Look up a page attribute, walking up the Pages tree for inheritable keys
+pub(crate) fn get_inherited_attribute<'a>(
+    doc: &'a Document,
+    page_dict: &'a Dictionary,
+    key: &[u8],
+) -> Option<&'a Object> {
+    let mut node = page_dict;
+    // Bounded walk so malformed, cyclic Parent chains terminate
+    for _ in 0..64 {
+        if let Ok(value) = node.get(key) {
+            return Some(value);
+        }
+        if !INHERITABLE_PAGE_KEYS.contains(&key) {
+            return None;
+        }
+        node = match node.get(b"Parent") {
+            Ok(Object::Reference(parent_id)) => doc.get_dictionary(*parent_id).ok()?,
+            _ => return None,
+        };
+    }
+    None
+}
+
+/// Copy inherited attributes into the page dictionary itself
+pub(crate) fn materialize_inherited_attributes(doc: &Document, page_dict: &mut Dictionary) {
+    let inherited: Vec<(&[u8], Object)> = INHERITABLE_PAGE_KEYS
+        .iter()
+        .filter(|key| !page_dict.has(key))
+        .filter_map(|key| {
+            get_inherited_attribute(doc, page_dict, key).map(|value| (*key, value.clone()))
+        })
+        .collect();
+
+    for (key, value) in inherited {
+        page_dict.set(key, value);
+    }
+}
+
+/// Get the root Pages node, creating the Catalog and Pages dictionaries if needed
+pub fn ensure_page_tree(doc: &mut Document) -> ObjectId {
+    let catalog_id = match doc.trailer.get(b"Root") {
+        Ok(Object::Reference(id)) if doc.get_dictionary(*id).is_ok() => *id,
+        _ => {
+            let id = doc.add_object(dictionary! { "Type" => "Catalog" });
+            doc.trailer.set("Root", Object::Reference(id));
+            id
+        }
+    };
+
+    if let Ok(Object::Reference(pages_id)) = doc
+        .get_dictionary(catalog_id)
+        .and_then(|catalog| catalog.get(b"Pages"))
+    {
+        if doc.get_dictionary(*pages_id).is_ok() {
+            return *pages_id;
+        }
+    }
+
+    let pages_id = doc.add_object(dictionary! {
+        "Type" => "Pages",
+        "Kids" => Vec::<Object>::new(),
+        "Count" => 0,
+    });
+    if let Ok(catalog) = doc.get_dictionary_mut(catalog_id) {
+        catalog.set("Pages", Object::Reference(pages_id));
+    }
+    pages_id
+}
+
+/// Get the object IDs of all pages in display order
+pub fn page_ids(doc: &Document) -> Vec<ObjectId> {
+    doc.get_pages().into_values().collect()
+}
+
+/// Replace the page tree with a single flat Pages node holding `pages` in order.
+///
+/// Inherited attributes are copied into each page first, so flattening never changes
+/// how a page looks. Intermediate Pages nodes are removed from the document.
+pub fn set_page_order(doc: &mut Document, pages: &[ObjectId]) -> Result<ObjectId> {
+    let root_id = ensure_page_tree(doc);
+
+    for &page_id in pages {
+        let mut page_dict = doc
+            .get_dictionary(page_id)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, format!("Invalid page object: {}", e)))?
+            .clone();
+        materialize_inherited_attributes(doc, &mut page_dict);
+        page_dict.set("Parent", Object::Reference(root_id));
+        doc.set_object(page_id, page_dict);
+    }
+
+    // Collect intermediate Pages nodes below the root so they can be dropped
+    let mut intermediate = Vec::new();
+    let mut stack = vec![root_id];
+    while let Some(node_id) = stack.pop() {
+        let kids = doc
+            .get_dictionary(node_id)
+            .and_then(|node| node.get(b"Kids"))
+            .and_then(Object::as_array)
+            .map(|kids| kids.iter().filter_map(|kid| kid.as_reference().ok()).collect())
+            .unwrap_or_else(|_| Vec::new());
+        for kid_id in kids {
+            let is_pages_node = doc
+                .get_dictionary(kid_id)
+                .and_then(|kid| kid.get(b"Type"))
+                .and_then(Object::as_name)
+                .map(|name| name == b"Pages")
+                .unwrap_or(false);
+            if is_pages_node && kid_id != root_id && !intermediate.contains(&kid_id) {
+                intermediate.push(kid_id);
+                stack.push(kid_id);
+            }
+        }
+    }
+    for node_id in intermediate {
+        doc.objects.remove(&node_id);
+    }
+
+    let root = doc
+        .get_dictionary_mut(root_id)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("Invalid Pages node: {}", e)))?;
+    root.set(
+        "Kids",
+        pages
+            .iter()
+            .map(|&id| Object::Reference(id))
+            .collect::<Vec<Object>>(),
+    );
+    root.set("Count", pages.len() as i64);
+
+    Ok(root_id)
+}
+
+/// Reorder the pages of a document.
+///
+/// `order` lists 0-indexed page numbers in their new order and must contain every page
+/// exactly once.
+pub fn reorder_pages(doc: &mut Document, order: &[usize]) -> Result<()> {
+    let pages = page_ids(doc);
+
+    let mut seen = vec![false; pages.len()];
+    for &index in order {
+        match seen.get_mut(index) {
+            Some(seen) if !*seen => *seen = true,
+            Some(_) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Page {} appears more than once in the new order", index),
+                ))
+            }
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Page {} does not exist", index),
+                ))
+            }
+        }
+    }
+    if order.len() != pages.len() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "The new order must list every page exactly once",
+        ));
+    }
+
+    let reordered: Vec<ObjectId> = order.iter().map(|&index| pages[index]).collect();
+    set_page_order(doc, &reordered)?;
+    Ok(())
+}
+
+/// Delete pages from a document, returning how many were removed.
+///
+/// The page objects are removed, along with links on the remaining pages and outline
+/// entries that point at them. Outline entries that still have children are kept as
+/// headings without a destination. Objects only the deleted pages referenced (content
+/// streams, annotations) stay until [`Document::prune_objects`] is called.
+pub fn delete_pages(doc: &mut Document, range: &PageRange) -> Result<usize> {
+    let pages = page_ids(doc);
+    let to_delete = range.to_indices(pages.len())?;
+
+    let remaining: Vec<ObjectId> = pages
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !to_delete.contains(index))
+        .map(|(_, &id)| id)
+        .collect();
+
+    set_page_order(doc, &remaining)?;
+
+    let removed = pages.len() - remaining.len();
+    let deleted: HashSet<ObjectId> = pages
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| to_delete.contains(index))
+        .map(|(_, id)| id)
+        .collect();
+
+    for &page_id in &remaining {
+        remove_links_to(doc, page_id, &deleted);
+    }
+    let outlines = doc
+        .catalog()
+        .and_then(|catalog| catalog.get(b"Outlines"))
+        .and_then(Object::as_reference);
+    if let Ok(outlines) = outlines {
+        remove_outline_entries_to(doc, outlines, &deleted, &mut HashSet::new());
+    }
+
+    for page_id in &deleted {
+        doc.objects.remove(page_id);
+    }
+    Ok(removed)
+}
+
+/// The page an explicit, named or action destination points at
+fn destination_page(doc: &Document, dest: &Object) -> Option<ObjectId> {
+    PdfEmbedder::resolve_destination(doc, doc, dest)?
+        .as_array()
+        .ok()?
+        .first()?
+        .as_reference()
+        .ok()
+}
+
+/// The page a link annotation or outline item jumps to through `/Dest` or a GoTo action
+fn link_target(doc: &Document, dict: &Dictionary) -> Option<ObjectId> {
+    if let Ok(dest) = dict.get(b"Dest") {
+        return destination_page(doc, dest);
+    }
+    let action = dict
+        .get(b"A")
+        .and_then(|action| doc.dereference(action))
+        .and_then(|(_, action)| action.as_dict())
+        .ok()?;
+    if action.get(b"S").and_then(Object::as_name).ok() != Some(b"GoTo".as_slice()) {
+        return None;
+    }
+    destination_page(doc, action.get(b"D").ok()?)
+}
+
+/// Drop the Link annotations on a page that jump to one of the `deleted` pages
+fn remove_links_to(doc: &mut Document, page_id: ObjectId, deleted: &HashSet<ObjectId>) {
+    let annots = match doc
+        .get_dictionary(page_id)
+        .and_then(|page| page.get(b"Annots"))
+        .and_then(|annots| doc.dereference(annots))
+        .and_then(|(_, annots)| annots.as_array())
+    {
+        Ok(annots) => annots.clone(),
+        Err(_) => return,
+    };
+
+    let kept: Vec<Object> = annots
+        .iter()
+        .filter(|annot| {
+            let dangling = doc
+                .dereference(annot)
+                .and_then(|(_, annot)| annot.as_dict())
+                .ok()
+                .filter(|annot| annot.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Link".as_slice()))
+                .and_then(|annot| link_target(doc, annot))
+                .is_some_and(|target| deleted.contains(&target));
+            !dangling
+        })
+        .cloned()
+        .collect();
+
+    if kept.len() != annots.len() {
+        if let Ok(page) = doc.get_dictionary_mut(page_id) {
+            page.set("Annots", kept);
+        }
+    }
+}
+
+/// Remove the outline entries under `parent_id` that jump to one of the `deleted` pages,
+/// relinking their siblings and updating `/Count`
+fn remove_outline_entries_to(
+    doc: &mut Document,
+    parent_id: ObjectId,
+    deleted: &HashSet<ObjectId>,
+    visited: &mut HashSet<ObjectId>,
+) {
+    if !visited.insert(parent_id) {
+        return;
+    }
+
+    let mut children = Vec::new();
+    let mut current = doc
+        .get_dictionary(parent_id)
+        .and_then(|parent| parent.get(b"First"))
+        .and_then(Object::as_reference)
+        .ok();
+    while let Some(child_id) = current {
+        if children.contains(&child_id) {
+            break;
+        }
+        children.push(child_id);
+        current = doc
+            .get_dictionary(child_id)
+            .and_then(|child| child.get(b"Next"))
+            .and_then(Object::as_reference)
+            .ok();
+    }
+
+    let mut kept = Vec::new();
+    let mut visible = 0;
+    for child_id in children {
+        remove_outline_entries_to(doc, child_id, deleted, visited);
+        let child = match doc.get_dictionary(child_id) {
+            Ok(child) => child,
+            Err(_) => continue,
+        };
+        let dangling = link_target(doc, child).is_some_and(|target| deleted.contains(&target));
+        let has_children = child.has(b"First");
+        let open_count = child.get(b"Count").and_then(Object::as_i64).unwrap_or(0).max(0);
+
+        if dangling {
+            if !has_children {
+                doc.objects.remove(&child_id);
+                continue;
+            }
+            if let Ok(child) = doc.get_dictionary_mut(child_id) {
+                child.remove(b"Dest");
+                child.remove(b"A");
+            }
+        }
+        kept.push(child_id);
+        visible += 1 + open_count;
+    }
+
+    for (index, &child_id) in kept.iter().enumerate() {
+        if let Ok(child) = doc.get_dictionary_mut(child_id) {
+            match index.checked_sub(1) {
+                Some(previous) => child.set("Prev", Object::Reference(kept[previous])),
+                None => {
+                    child.remove(b"Prev");
+                }
+            }
+            match kept.get(index + 1) {
+                Some(&next) => child.set("Next", Object::Reference(next)),
+                None => {
+                    child.remove(b"Next");
+                }
+            }
+        }
+    }
+
+    if let Ok(parent) = doc.get_dictionary_mut(parent_id) {
+        match (kept.first(), kept.last()) {
+            (Some(&first), Some(&last)) => {
+                parent.set("First", Object::Reference(first));
+                parent.set("Last", Object::Reference(last));
+                // A negative count marks a closed entry and stays negative
+                let closed = parent.get(b"Count").and_then(Object::as_i64).is_ok_and(|count| count < 0);
+                parent.set("Count", if closed { -visible } else { visible });
+            }
+            _ => {
+                parent.remove(b"First");
+                parent.remove(b"Last");
+                if parent.get(b"Type").and_then(Object::as_name).ok() == Some(b"Outlines".as_slice()) {
+                    parent.set("Count", 0);
+                } else {
+                    parent.remove(b"Count");
+                }
+            }
+        }
+    }
+}
+
+/// Insert an empty page of the given size before the 0-indexed position `index`.
+///
+/// An index past the last page appends the page at the end.
+pub fn insert_blank_page(
+    doc: &mut Document,
+    index: usize,
+    width: f32,
+    height: f32,
+) -> Result<ObjectId> {
+    let mut pages = page_ids(doc);
+    let root_id = ensure_page_tree(doc);
+
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => root_id,
+        "MediaBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+        "Resources" => Dictionary::new(),
+    });
+
+    pages.insert(index.min(pages.len()), page_id);
+    set_page_order(doc, &pages)?;
+    Ok(page_id)
+}
+
+/// Append operations to a page's content, drawn on top of what is already there.
+///
+/// The existing content is wrapped in `q`/`Q` so graphics state it leaves behind does
+/// not apply to the appended operations. Existing content streams are not modified,
+/// so streams shared with other pages stay intact.
+pub fn append_content(doc: &mut Document, page_id: ObjectId, operations: Vec<Operation>) -> Result<()> {
+    let page = doc
+        .get_dictionary(page_id)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("Invalid page object: {}", e)))?;
+    let existing = match page.get(b"Contents") {
+        Ok(Object::Reference(id)) => match doc.get_object(*id) {
+            Ok(Object::Array(streams)) => streams.clone(),
+            _ => vec![Object::Reference(*id)],
+        },
+        Ok(Object::Array(streams)) => streams.clone(),
+        _ => Vec::new(),
+    };
+
+    let mut contents = Vec::with_capacity(existing.len() + 2);
+    let mut appended = operations;
+    if !existing.is_empty() {
+        contents.push(doc.add_object(Stream::new(Dictionary::new(), b"q\n".to_vec())).into());
+        contents.extend(existing);
+        appended.insert(0, Operation::new("Q", vec![]));
+    }
+    // Leading whitespace keeps tokens apart for readers that join the streams verbatim
+    let mut encoded = b"\n".to_vec();
+    encoded.extend(
+        Content { operations: appended }
+            .encode()
+            .map_err(|e| Error::new(ErrorKind::InvalidData, format!("Failed to encode content: {}", e)))?,
+    );
+    contents.push(doc.add_object(Stream::new(Dictionary::new(), encoded)).into());
+
+    doc.get_dictionary_mut(page_id)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("Invalid page object: {}", e)))?
+        .set("Contents", contents);
+    Ok(())
+}
@@ -2,7 +2,7 @@
 //!
 //! Tests for the block registration and instancing system.
 
-use hipdf::blocks::{Block, BlockInstance, BlockManager, Transform, merge_blocks};
+use hipdf::blocks::{AttributeDefinition, Block, BlockInstance, BlockManager, Transform, merge_blocks};
 use hipdf::lopdf::{content::{Content, Operation}, dictionary, Dictionary, Document, Object, Stream};
 
 use std::fs;
@@ -291,7 +291,7 @@ fn test_blocks_integration() {
     block_manager.register(Block::new("label", label_ops).with_bbox(0.0, 0.0, 80.0, 25.0));
 
     // Create XObjects for efficient reuse
-    block_manager.create_xobjects(&mut doc);
+    block_manager.create_xobjects(&mut doc).unwrap();
 
     // Define instances with various transformations
     let instances = vec![
@@ -334,14 +334,14 @@ fn test_blocks_integration() {
     ];
 
     // Build page content
-    let mut all_operations = Vec::new();
-
     // Add title
-    all_operations.push(Operation::new("BT", vec![]));
-    all_operations.push(Operation::new("Tf", vec![Object::Name(b"F1".to_vec()), 24.0.into()]));
-    all_operations.push(Operation::new("Td", vec![50.0.into(), 800.0.into()]));
-    all_operations.push(Operation::new("Tj", vec![Object::string_literal("PDF Block System Demo")]));
-    all_operations.push(Operation::new("ET", vec![]));
+    let mut all_operations = vec![
+        Operation::new("BT", vec![]),
+        Operation::new("Tf", vec![Object::Name(b"F1".to_vec()), 24.0.into()]),
+        Operation::new("Td", vec![50.0.into(), 800.0.into()]),
+        Operation::new("Tj", vec![Object::string_literal("PDF Block System Demo")]),
+        Operation::new("ET", vec![]),
+    ];
 
     // Add subtitle
     all_operations.push(Operation::new("BT", vec![]));
@@ -425,7 +425,7 @@ fn test_xobject_creation() {
     manager.register(Block::new("magenta_square", ops).with_bbox(0.0, 0.0, 50.0, 50.0));
     
     // Create XObjects
-    manager.create_xobjects(&mut doc);
+    manager.create_xobjects(&mut doc).unwrap();
 
     // Create instances and render as XObjects
     let instances = vec![
@@ -464,4 +464,343 @@ fn test_block_with_resources() {
 
     assert!(block.resources.is_some());
     assert_eq!(block.resources.unwrap(), resources);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_block_on_layer() {
+    let mut doc = Document::with_version("1.7");
+    let layer_id = doc.add_object(dictionary! {
+        "Type" => "OCG",
+        "Name" => Object::string_literal("Furniture"),
+    });
+
+    let ops = vec![
+        Operation::new("re", vec![0.0.into(), 0.0.into(), 20.0.into(), 20.0.into()]),
+        Operation::new("f", vec![]),
+    ];
+    let mut manager = BlockManager::new();
+    manager.register(
+        Block::new("chair", ops)
+            .with_bbox(0.0, 0.0, 20.0, 20.0)
+            .with_optional_content(layer_id),
+    );
+    manager.create_xobjects(&mut doc).unwrap();
+
+    let mut resources = Dictionary::new();
+    manager.render_instances_as_xobjects(&[BlockInstance::at("chair", 10.0, 10.0)], &mut resources);
+    let xobject_id = resources
+        .get(b"XObject")
+        .and_then(Object::as_dict)
+        .unwrap()
+        .iter()
+        .next()
+        .map(|(_, xobject)| xobject.as_reference().unwrap())
+        .unwrap();
+
+    // Every instance of the block follows the layer
+    let xobject = doc.get_object(xobject_id).unwrap().as_stream().unwrap();
+    assert_eq!(xobject.dict.get(b"OC").unwrap(), &Object::Reference(layer_id));
+}
+
+/// Strings shown with Tj, in drawing order
+fn shown_text(ops: &[Operation]) -> Vec<String> {
+    ops.iter()
+        .filter(|op| op.operator == "Tj")
+        .map(|op| String::from_utf8_lossy(op.operands[0].as_str().unwrap()).to_string())
+        .collect()
+}
+
+#[test]
+fn test_block_attributes() {
+    ensure_output_dir();
+    let mut doc = Document::with_version("1.7");
+    let pages_id = doc.new_object_id();
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+    let layer_id = doc.add_object(dictionary! {
+        "Type" => "OCG",
+        "Name" => Object::string_literal("Tags"),
+    });
+
+    // One door tag block serves every door; only the attributes differ
+    let tag_ops = vec![
+        Operation::new("re", vec![0.0.into(), 0.0.into(), 40.0.into(), 24.0.into()]),
+        Operation::new("S", vec![]),
+    ];
+    let mut manager = BlockManager::new();
+    manager.register(
+        Block::new("door_tag", tag_ops.clone())
+            .with_bbox(0.0, 0.0, 40.0, 24.0)
+            .with_attribute(AttributeDefinition::new("NUMBER", "?", 6.0, 12.0).with_font("F1", 9.0))
+            .with_attribute(
+                AttributeDefinition::new("TYPE", "", 6.0, 3.0)
+                    .with_font("F1", 6.0)
+                    .with_color(0.4, 0.4, 0.4),
+            ),
+    );
+    manager.register(
+        Block::new("room_label", tag_ops)
+            .with_bbox(0.0, 0.0, 40.0, 24.0)
+            .with_optional_content(layer_id)
+            .with_attribute(AttributeDefinition::new("NAME", "Room", 4.0, 8.0)),
+    );
+    manager.create_xobjects(&mut doc).unwrap();
+
+    let doors = [
+        BlockInstance::at("door_tag", 100.0, 700.0).with_attribute("NUMBER", "D101").with_attribute("TYPE", "Fire door"),
+        BlockInstance::at("door_tag", 200.0, 700.0).with_attribute("NUMBER", "D102"),
+        BlockInstance::new("door_tag", Transform::full(300.0, 700.0, 1.5, 1.5, 30.0)),
+    ];
+
+    // Values replace defaults, empty values are left out, and the text follows the instance
+    let inline = manager.render_instance(&doors[0]);
+    assert_eq!(shown_text(&inline), vec!["D101", "Fire door"]);
+    assert_eq!(inline.last().unwrap().operator, "Q");
+    assert_eq!(inline.iter().filter(|op| op.operator == "rg").count(), 1);
+
+    let mut resources = dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+        "Properties" => dictionary! { "L0" => layer_id },
+    };
+    // Attributes of a block on a layer are marked with the same layer
+    let instances: Vec<BlockInstance> = doors
+        .iter()
+        .cloned()
+        .chain([
+            BlockInstance::at("room_label", 100.0, 600.0).with_attribute("NAME", "Kitchen"),
+            BlockInstance::at("room_label", 200.0, 600.0),
+        ])
+        .collect();
+    let operations = manager.render_instances_as_xobjects(&instances, &mut resources);
+    assert_eq!(shown_text(&operations), vec!["D101", "Fire door", "D102", "?", "Kitchen", "Room"]);
+    assert_eq!(operations.iter().filter(|op| op.operator == "Do").count(), 5);
+    assert_eq!(operations.iter().filter(|op| op.operator == "BDC").count(), 2);
+    let properties = resources.get(b"Properties").and_then(Object::as_dict).unwrap();
+    assert_eq!(properties.len(), 2);
+    assert!(properties.has(b"L0"));
+
+    let content_id = doc.add_object(Stream::new(dictionary! {}, Content { operations }.encode().unwrap()));
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        "Contents" => content_id,
+        "Resources" => resources,
+    });
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![Object::Reference(page_id)],
+            "Count" => 1,
+        }),
+    );
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => Object::Reference(pages_id),
+    });
+    doc.trailer.set("Root", Object::Reference(catalog_id));
+
+    let output_path = format!("{}/block_attributes.pdf", TEST_OUTPUT_DIR);
+    doc.save(&output_path).expect("Failed to save PDF");
+    println!("✅ Block attributes test passed");
+    println!("📄 PDF created: {}", output_path);
+}
+
+#[test]
+fn test_nested_blocks() {
+    ensure_output_dir();
+    let mut doc = Document::with_version("1.7");
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+    let rect = |w: f32, h: f32| {
+        vec![
+            Operation::new("re", vec![0.0.into(), 0.0.into(), w.into(), h.into()]),
+            Operation::new("S", vec![]),
+        ]
+    };
+
+    // Window and door symbols share a pane and a labelled handle
+    let mut manager = BlockManager::new();
+    manager.register(Block::new("pane", rect(20.0, 40.0)).with_bbox(0.0, 0.0, 20.0, 40.0));
+    manager.register(
+        Block::new("handle", rect(4.0, 10.0))
+            .with_bbox(0.0, 0.0, 30.0, 10.0)
+            .with_resources(dictionary! { "Font" => dictionary! { "F1" => font_id } })
+            .with_attribute(AttributeDefinition::new("SIDE", "", 6.0, 2.0).with_font("F1", 6.0)),
+    );
+    manager.register(
+        Block::new("window", rect(50.0, 50.0))
+            .with_bbox(0.0, 0.0, 50.0, 50.0)
+            .with_resources(dictionary! { "Font" => dictionary! { "F1" => font_id } })
+            .with_child(BlockInstance::at("pane", 4.0, 5.0))
+            .with_child(BlockInstance::at("pane", 26.0, 5.0))
+            .with_child(BlockInstance::at("handle", 22.0, 20.0).with_attribute("SIDE", "L")),
+    );
+    manager.register(
+        Block::new("door", rect(40.0, 90.0))
+            .with_bbox(0.0, 0.0, 40.0, 90.0)
+            .with_child(BlockInstance::at("window", 5.0, 50.0).with_attribute("UNUSED", "x"))
+            .with_child(BlockInstance::at("handle", 32.0, 40.0)),
+    );
+
+    // Components come before the symbols built from them
+    let order = manager.dependency_order().unwrap();
+    let position = |id: &str| order.iter().position(|entry| entry == id).unwrap();
+    assert!(position("pane") < position("window") && position("handle") < position("window"));
+    assert!(position("window") < position("door"));
+
+    // Inline rendering draws nested content in place
+    let inline = manager.render_instance(&BlockInstance::at("door", 0.0, 0.0));
+    assert_eq!(inline.iter().filter(|op| op.operator == "re").count(), 6);
+    assert_eq!(
+        inline.iter().filter(|op| op.operator == "Tj").count(),
+        1,
+        "only the window's handle has a label"
+    );
+
+    // XObjects refer to their children's XObjects; the handle's label uses the window's font
+    manager.create_xobjects(&mut doc).unwrap();
+    let mut resources = Dictionary::new();
+    let operations = manager.render_instances_as_xobjects(
+        &[BlockInstance::at("door", 100.0, 600.0), BlockInstance::at("window", 200.0, 640.0)],
+        &mut resources,
+    );
+    let xobjects = resources.get(b"XObject").and_then(Object::as_dict).unwrap().clone();
+    let window_id = xobjects.get(b"Blk1").and_then(Object::as_reference).unwrap();
+    let window = doc.get_object(window_id).unwrap().as_stream().unwrap();
+    let window_resources = window.dict.get(b"Resources").and_then(Object::as_dict).unwrap();
+    let sub_xobjects = window_resources.get(b"XObject").and_then(Object::as_dict).unwrap();
+    assert_eq!(sub_xobjects.len(), 3);
+    assert_eq!(sub_xobjects.get(b"Sub0").unwrap(), sub_xobjects.get(b"Sub1").unwrap());
+    assert!(window_resources.get(b"Font").and_then(Object::as_dict).unwrap().has(b"F1"));
+    let window_ops = Content::decode(&window.content).unwrap().operations;
+    assert_eq!(window_ops.iter().filter(|op| op.operator == "Do").count(), 3);
+    assert_eq!(window_ops.iter().filter(|op| op.operator == "Tj").count(), 1);
+
+    let door_id = xobjects.get(b"Blk0").and_then(Object::as_reference).unwrap();
+    let door = doc.get_object(door_id).unwrap().as_stream().unwrap();
+    let door_xobjects = door
+        .dict
+        .get(b"Resources")
+        .and_then(Object::as_dict)
+        .and_then(|resources| resources.get(b"XObject"))
+        .and_then(Object::as_dict)
+        .unwrap();
+    assert_eq!(door_xobjects.get(b"Sub0").unwrap(), &Object::Reference(window_id));
+
+    // Cycles are reported and left without XObjects, along with blocks containing them
+    let mut cyclic = BlockManager::new();
+    cyclic.register(Block::new("a", rect(10.0, 10.0)).with_child(BlockInstance::at("b", 1.0, 1.0)));
+    cyclic.register(Block::new("b", rect(10.0, 10.0)).with_child(BlockInstance::at("a", 1.0, 1.0)));
+    cyclic.register(Block::new("c", rect(10.0, 10.0)).with_child(BlockInstance::at("a", 1.0, 1.0)));
+    cyclic.register(Block::new("d", rect(10.0, 10.0)).with_child(BlockInstance::at("missing", 1.0, 1.0)));
+    let error = cyclic.dependency_order().unwrap_err().to_string();
+    assert!(error.contains("a -> b -> a"), "{}", error);
+    assert_eq!(cyclic.render_instance(&BlockInstance::at("a", 0.0, 0.0)).iter().filter(|op| op.operator == "re").count(), 2);
+
+    let mut cyclic_doc = Document::with_version("1.7");
+    let error = cyclic.create_xobjects(&mut cyclic_doc).unwrap_err().to_string();
+    assert!(error.contains("a -> b -> a"), "{}", error);
+    let drawn = cyclic.render_instances_as_xobjects(
+        &["a", "b", "c", "d"].map(|id| BlockInstance::at(id, 0.0, 0.0)),
+        &mut Dictionary::new(),
+    );
+    assert_eq!(drawn.iter().filter(|op| op.operator == "Do").count(), 1);
+
+    // Child XObjects are named apart from the block's own, and conflicting fonts are refused
+    let mut clashing = BlockManager::new();
+    let mut clash_doc = Document::with_version("1.7");
+    let image_id = clash_doc.add_object(dictionary! {});
+    let (font_a, font_b) = (clash_doc.add_object(dictionary! {}), clash_doc.add_object(dictionary! {}));
+    clashing.register(Block::new("leaf", rect(5.0, 5.0)));
+    clashing.register(
+        Block::new("framed", rect(10.0, 10.0))
+            .with_resources(dictionary! { "XObject" => dictionary! { "Sub0" => image_id } })
+            .with_child(BlockInstance::at("leaf", 1.0, 1.0)),
+    );
+    clashing.create_xobjects(&mut clash_doc).unwrap();
+    let mut clash_resources = Dictionary::new();
+    clashing.render_instances_as_xobjects(&[BlockInstance::at("framed", 0.0, 0.0)], &mut clash_resources);
+    let framed_id = clash_resources
+        .get(b"XObject")
+        .and_then(Object::as_dict)
+        .and_then(|xobjects| xobjects.get(b"Blk0"))
+        .and_then(Object::as_reference)
+        .unwrap();
+    let framed = clash_doc.get_object(framed_id).unwrap().as_stream().unwrap();
+    let framed_xobjects = framed
+        .dict
+        .get(b"Resources")
+        .and_then(Object::as_dict)
+        .and_then(|resources| resources.get(b"XObject"))
+        .and_then(Object::as_dict)
+        .unwrap();
+    assert_eq!(framed_xobjects.get(b"Sub0").unwrap(), &Object::Reference(image_id));
+    assert!(framed_xobjects.get(b"Sub1").is_ok_and(|leaf| leaf != &Object::Reference(image_id)));
+    let framed_ops = Content::decode(&framed.content).unwrap().operations;
+    assert_eq!(framed_ops.iter().find(|op| op.operator == "Do").unwrap().operands[0], Object::Name(b"Sub1".to_vec()));
+
+    // Parent and child fonts are independent, even under the same name
+    clashing.register(
+        Block::new("labelled", rect(5.0, 5.0)).with_resources(dictionary! { "Font" => dictionary! { "F1" => font_a } }),
+    );
+    clashing.register(
+        Block::new("sheet", rect(10.0, 10.0))
+            .with_resources(dictionary! { "Font" => dictionary! { "F1" => font_b } })
+            .with_child(BlockInstance::at("labelled", 1.0, 1.0)),
+    );
+    clashing.create_xobjects(&mut clash_doc).unwrap();
+    let mut sheet_resources = Dictionary::new();
+    clashing.render_instances_as_xobjects(
+        &[BlockInstance::at("sheet", 0.0, 0.0), BlockInstance::at("labelled", 0.0, 0.0)],
+        &mut sheet_resources,
+    );
+    let font_of = |name: &[u8]| {
+        let id = sheet_resources
+            .get(b"XObject")
+            .and_then(Object::as_dict)
+            .and_then(|xobjects| xobjects.get(name))
+            .and_then(Object::as_reference)
+            .unwrap();
+        let form = clash_doc.get_object(id).unwrap().as_stream().unwrap();
+        let resources = form.dict.get(b"Resources").and_then(Object::as_dict).unwrap();
+        resources.get(b"Font").and_then(Object::as_dict).unwrap().get(b"F1").unwrap().clone()
+    };
+    assert_eq!(font_of(b"Blk1"), Object::Reference(font_b));
+    assert_eq!(font_of(b"Blk2"), Object::Reference(font_a));
+
+    let pages_id = doc.new_object_id();
+    let content_id = doc.add_object(Stream::new(dictionary! {}, Content { operations }.encode().unwrap()));
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        "Contents" => content_id,
+        "Resources" => resources,
+    });
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![Object::Reference(page_id)],
+            "Count" => 1,
+        }),
+    );
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => Object::Reference(pages_id),
+    });
+    doc.trailer.set("Root", Object::Reference(catalog_id));
+
+    let output_path = format!("{}/nested_blocks.pdf", TEST_OUTPUT_DIR);
+    doc.save(&output_path).expect("Failed to save PDF");
+    println!("✅ Nested blocks test passed");
+    println!("📄 PDF created: {}", output_path);
+}
@@ -4,7 +4,8 @@
 
 use hipdf::lopdf::{content::Content, dictionary, Dictionary, Document, Object, Stream};
 use hipdf::embed_pdf::{
-    CustomLayoutStrategy, EmbedOptions, GridFillOrder, MultiPageLayout, PageRange, PdfEmbedder,
+    BlendMode, CustomLayoutStrategy, EmbedOptions, EmbedUtils, GridFillOrder, MultiPageLayout,
+    PageBox, PageRange, PdfEmbedder,
 };
 
 use std::collections::HashMap;
@@ -1452,3 +1453,562 @@ fn test_comprehensive_showcase() {
     println!("✅ Comprehensive showcase test completed");
     println!("📄 PDF created: {}", output_path);
 }
+
+#[test]
+fn test_opacity_and_blend_modes() {
+    ensure_output_dir();
+
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.add_object(dictionary! {
+        "Type" => "Pages",
+        "Count" => 1,
+    });
+
+    let (font_id, mut page_ops, _) =
+        create_page_with_title(&mut doc, "Opacity and Blend Mode Examples");
+    let mut resources = dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+    };
+
+    let mut embedder = PdfEmbedder::new();
+    let arxiv_pdf = embedder.load_pdf("tests/assets/2412.07377v3.pdf").unwrap();
+
+    // Coloured backdrop so blend modes have something to composite against
+    page_ops.extend(vec![
+        lopdf::content::Operation::new("rg", vec![1.0.into(), 0.85.into(), 0.3.into()]),
+        lopdf::content::Operation::new("re", vec![40.into(), 420.into(), 515.into(), 320.into()]),
+        lopdf::content::Operation::new("f", vec![]),
+        lopdf::content::Operation::new("g", vec![0.into()]),
+    ]);
+
+    let modes = [
+        ("Normal 50%", BlendMode::Normal, 0.5),
+        ("Multiply", BlendMode::Multiply, 1.0),
+        ("Screen", BlendMode::Screen, 1.0),
+        ("Darken", BlendMode::Darken, 1.0),
+        ("Difference", BlendMode::Difference, 0.8),
+    ];
+
+    let mut x_pos = 50.0;
+    let mut first_gs = None;
+    for (label, mode, opacity) in modes {
+        add_section_label(&mut page_ops, x_pos, 750.0, label);
+
+        let options = EmbedOptions::new()
+            .at_position(x_pos, 440.0)
+            .with_max_size(95.0, 290.0)
+            .with_opacity(opacity)
+            .with_blend_mode(mode);
+
+        let result = embedder.embed_pdf(&mut doc, &arxiv_pdf, &options).unwrap();
+        assert_eq!(result.extgstate_resources.len(), 1);
+        assert!(result.operations.iter().any(|op| op.operator == "gs"));
+
+        let (_, gs_ref) = result.extgstate_resources.iter().next().unwrap();
+        let gs = doc.get_object(gs_ref.as_reference().unwrap()).unwrap().as_dict().unwrap();
+        assert_eq!(gs.get(b"BM").unwrap().as_name().unwrap(), mode.as_name().as_bytes());
+        assert_eq!(gs.get(b"ca").unwrap().as_float().unwrap(), opacity);
+        first_gs.get_or_insert(gs_ref.clone());
+
+        page_ops.extend(result.operations.clone());
+        result.add_to_resources(&mut resources);
+        x_pos += 105.0;
+    }
+
+    // Identical graphics states are shared rather than duplicated
+    let repeat = embedder
+        .embed_pdf(
+            &mut doc,
+            &arxiv_pdf,
+            &EmbedOptions::new().with_opacity(0.5).with_scale(0.1),
+        )
+        .unwrap();
+    assert_eq!(repeat.extgstate_resources.values().next(), first_gs.as_ref());
+
+    // Fully opaque normal compositing needs no graphics state at all
+    let opaque = embedder
+        .embed_pdf(&mut doc, &arxiv_pdf, &EmbedOptions::new().with_scale(0.1))
+        .unwrap();
+    assert!(opaque.extgstate_resources.is_empty());
+
+    // Watermark options now carry their opacity into the output
+    add_section_label(&mut page_ops, 50.0, 380.0, "Watermark (30% opacity)");
+    let watermark = EmbedUtils::watermark_options(0.3, 0.3);
+    let result = embedder.embed_pdf(&mut doc, &arxiv_pdf, &watermark).unwrap();
+    assert!(!result.extgstate_resources.is_empty());
+    page_ops.extend(result.operations.clone());
+    result.add_to_resources(&mut resources);
+
+    let content = Content {
+        operations: page_ops,
+    };
+    let content_stream = Stream::new(dictionary! {}, content.encode().unwrap());
+    let content_id = doc.add_object(content_stream);
+
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        "Contents" => content_id,
+        "Resources" => resources,
+    });
+
+    let pages_dict = doc
+        .get_object_mut(pages_id)
+        .and_then(Object::as_dict_mut)
+        .unwrap();
+    pages_dict.set("Kids", vec![Object::Reference(page_id)]);
+
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => Object::Reference(pages_id),
+    });
+    doc.trailer.set("Root", Object::Reference(catalog_id));
+
+    let output_path = format!("{}/opacity_blend_modes_test.pdf", TEST_OUTPUT_DIR);
+    doc.save(&output_path).unwrap();
+
+    assert!(Path::new(&output_path).exists());
+    println!("✅ Opacity and blend modes test completed");
+    println!("📄 PDF created: {}", output_path);
+}
+
+#[test]
+fn test_shared_objects_imported_once() {
+    let mut doc = Document::with_version("1.5");
+
+    let mut embedder = PdfEmbedder::new();
+    let arxiv_pdf = embedder.load_pdf("tests/assets/2412.07377v3.pdf").unwrap();
+    let source_objects = Document::load("tests/assets/2412.07377v3.pdf")
+        .unwrap()
+        .objects
+        .len();
+    let page_count = embedder.get_pdf_info(&arxiv_pdf).unwrap().page_count;
+
+    let all_pages = EmbedOptions::new()
+        .with_scale(0.1)
+        .with_layout(MultiPageLayout::Grid {
+            columns: 4,
+            gap_x: 5.0,
+            gap_y: 5.0,
+            fill_order: GridFillOrder::RowFirst,
+        });
+
+    let first = embedder.embed_pdf(&mut doc, &arxiv_pdf, &all_pages).unwrap();
+    let after_first = doc.objects.len();
+    assert_eq!(first.xobject_resources.len(), page_count);
+
+    // Each source object is copied at most once, plus one Form XObject per page
+    assert!(
+        after_first <= source_objects + page_count,
+        "imported {} objects from a {} object source",
+        after_first,
+        source_objects
+    );
+
+    // Embedding the same pages again into the same target reuses everything
+    let second = embedder.embed_pdf(&mut doc, &arxiv_pdf, &all_pages).unwrap();
+    assert_eq!(doc.objects.len(), after_first);
+    let mut first_refs: Vec<_> = first.xobject_resources.values().cloned().collect();
+    let mut second_refs: Vec<_> = second.xobject_resources.values().cloned().collect();
+    first_refs.sort_by_key(|obj| obj.as_reference().unwrap());
+    second_refs.sort_by_key(|obj| obj.as_reference().unwrap());
+    assert_eq!(first_refs, second_refs);
+
+    // A fresh target gets fresh copies once the cache is cleared
+    let mut other_doc = Document::with_version("1.5");
+    embedder.clear_import_cache();
+    embedder
+        .embed_pdf(&mut other_doc, &arxiv_pdf, &all_pages)
+        .unwrap();
+    assert_eq!(other_doc.objects.len(), after_first);
+}
+
+#[test]
+fn test_embedded_pages_on_layer() {
+    let mut doc = Document::with_version("1.5");
+    let layer_id = doc.add_object(dictionary! {
+        "Type" => "OCG",
+        "Name" => Object::string_literal("Underlay"),
+    });
+
+    let mut embedder = PdfEmbedder::new();
+    let arxiv_pdf = embedder.load_pdf("tests/assets/2412.07377v3.pdf").unwrap();
+    let first_page = EmbedOptions::new().with_page_range(PageRange::Single(0));
+
+    let plain = embedder.embed_pdf(&mut doc, &arxiv_pdf, &first_page).unwrap();
+    let layered = embedder
+        .embed_pdf(&mut doc, &arxiv_pdf, &first_page.clone().with_optional_content(layer_id))
+        .unwrap();
+    let again = embedder
+        .embed_pdf(&mut doc, &arxiv_pdf, &first_page.with_optional_content(layer_id))
+        .unwrap();
+
+    let plain_id = plain.xobject_resources.values().next().unwrap().as_reference().unwrap();
+    let layered_id = layered.xobject_resources.values().next().unwrap().as_reference().unwrap();
+    assert_ne!(plain_id, layered_id);
+    assert_eq!(
+        again.xobject_resources.values().next().unwrap().as_reference().unwrap(),
+        layered_id
+    );
+
+    // The layer sits on a wrapper that draws the shared, unlayered page import
+    let wrapper = doc.get_object(layered_id).unwrap().as_stream().unwrap();
+    assert_eq!(wrapper.dict.get(b"OC").unwrap(), &Object::Reference(layer_id));
+    let inner = wrapper
+        .dict
+        .get(b"Resources")
+        .and_then(Object::as_dict)
+        .and_then(|resources| resources.get(b"XObject"))
+        .and_then(Object::as_dict)
+        .unwrap();
+    assert!(inner.iter().any(|(_, xobject)| xobject.as_reference().ok() == Some(plain_id)));
+    let page = doc.get_object(plain_id).unwrap().as_stream().unwrap();
+    assert!(page.dict.get(b"OC").is_err());
+}
+
+#[test]
+fn test_embedder_reused_across_targets() {
+    let mut embedder = PdfEmbedder::new();
+    let arxiv_pdf = embedder.load_pdf("tests/assets/2412.07377v3.pdf").unwrap();
+    let first_page = EmbedOptions::new().with_page_range(PageRange::Single(0));
+
+    let mut first_doc = Document::with_version("1.5");
+    let layer_id = first_doc.add_object(dictionary! {
+        "Type" => "OCG",
+        "Name" => Object::string_literal("Underlay"),
+    });
+    embedder
+        .embed_pdf(&mut first_doc, &arxiv_pdf, &first_page.clone().with_optional_content(layer_id))
+        .unwrap();
+
+    // A second target whose object IDs overlap everything written to the first one
+    let mut second_doc = Document::with_version("1.5");
+    while second_doc.max_id < first_doc.max_id {
+        second_doc.add_object(dictionary! { "Filler" => true });
+    }
+    let second_layer = second_doc.add_object(dictionary! {
+        "Type" => "OCG",
+        "Name" => Object::string_literal("Underlay"),
+    });
+    let embedded = embedder
+        .embed_pdf(&mut second_doc, &arxiv_pdf, &first_page.with_optional_content(second_layer))
+        .unwrap();
+
+    // Nothing is taken from the unrelated filler objects
+    let wrapper_id = embedded.xobject_resources.values().next().unwrap().as_reference().unwrap();
+    let wrapper = second_doc.get_object(wrapper_id).unwrap().as_stream().unwrap();
+    assert_eq!(wrapper.dict.get(b"OC").unwrap(), &Object::Reference(second_layer));
+    let page_id = wrapper
+        .dict
+        .get(b"Resources")
+        .and_then(Object::as_dict)
+        .and_then(|resources| resources.get(b"XObject"))
+        .and_then(Object::as_dict)
+        .and_then(|xobjects| xobjects.get(b"Fm0"))
+        .and_then(Object::as_reference)
+        .unwrap();
+    let page = second_doc.get_object(page_id).unwrap().as_stream().unwrap();
+    assert_eq!(page.dict.get(b"Subtype").unwrap(), &Object::Name(b"Form".to_vec()));
+
+    let mut pending = vec![Object::Reference(page_id)];
+    let mut seen = std::collections::HashSet::new();
+    while let Some(obj) = pending.pop() {
+        match obj {
+            Object::Reference(id) if seen.insert(id) => {
+                let target = second_doc.get_object(id).unwrap();
+                if let Ok(dict) = target.as_dict() {
+                    assert!(dict.get(b"Filler").is_err(), "{:?} points at a filler object", id);
+                }
+                pending.push(target.clone());
+            }
+            Object::Array(items) => pending.extend(items),
+            Object::Dictionary(dict) => pending.extend(dict.iter().map(|(_, v)| v.clone())),
+            Object::Stream(stream) => pending.extend(stream.dict.iter().map(|(_, v)| v.clone())),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn test_cyclic_references_import() {
+    // Build a source PDF whose resources point back at the page through a cycle
+    let mut source = Document::with_version("1.5");
+    let pages_id = source.new_object_id();
+    let page_id = source.new_object_id();
+    let font_id = source.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+    let loop_a = source.new_object_id();
+    let loop_b = source.add_object(dictionary! { "Next" => loop_a, "Page" => page_id });
+    source.set_object(loop_a, dictionary! { "Next" => loop_b, "Font" => font_id });
+
+    let content = Content {
+        operations: vec![
+            lopdf::content::Operation::new("BT", vec![]),
+            lopdf::content::Operation::new("Tf", vec![Object::Name(b"F1".to_vec()), 12.into()]),
+            lopdf::content::Operation::new("Tj", vec![Object::string_literal("cycle")]),
+            lopdf::content::Operation::new("ET", vec![]),
+        ],
+    };
+    let content_id = source.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+    source.set_object(
+        page_id,
+        dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 200.into(), 200.into()],
+            "Contents" => content_id,
+            "Resources" => dictionary! {
+                "Font" => dictionary! { "F1" => font_id },
+                "Properties" => dictionary! { "Loop" => loop_a },
+            },
+        },
+    );
+    source.set_object(
+        pages_id,
+        dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+        },
+    );
+    let catalog_id = source.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    source.trailer.set("Root", catalog_id);
+
+    let mut bytes = Vec::new();
+    source.save_to(&mut bytes).unwrap();
+
+    let mut embedder = PdfEmbedder::new();
+    let id = embedder.load_pdf_from_bytes(&bytes, "cyclic").unwrap();
+
+    let mut doc = Document::with_version("1.5");
+    let result = embedder
+        .embed_pdf(&mut doc, &id, &EmbedOptions::new())
+        .unwrap();
+    assert_eq!(result.xobject_resources.len(), 1);
+
+    // The font is reachable twice but copied once
+    let fonts = doc
+        .objects
+        .values()
+        .filter(|obj| {
+            obj.as_dict()
+                .ok()
+                .and_then(|d| d.get(b"Type").ok())
+                .and_then(|t| t.as_name().ok())
+                == Some(b"Font".as_slice())
+        })
+        .count();
+    assert_eq!(fonts, 1);
+}
+
+/// Builds a two page source PDF exercising inherited attributes, /Rotate and page boxes
+fn create_rotated_source() -> Vec<u8> {
+    let mut source = Document::with_version("1.5");
+    let pages_id = source.new_object_id();
+    let font_id = source.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+
+    let label = |text: &str| Content {
+        operations: vec![
+            lopdf::content::Operation::new("rg", vec![0.2.into(), 0.4.into(), 0.8.into()]),
+            lopdf::content::Operation::new("re", vec![20.into(), 30.into(), 180.into(), 380.into()]),
+            lopdf::content::Operation::new("f", vec![]),
+            lopdf::content::Operation::new("g", vec![1.into()]),
+            lopdf::content::Operation::new("BT", vec![]),
+            lopdf::content::Operation::new("Tf", vec![Object::Name(b"F1".to_vec()), 24.into()]),
+            lopdf::content::Operation::new("Td", vec![40.into(), 360.into()]),
+            lopdf::content::Operation::new("Tj", vec![Object::string_literal(text)]),
+            lopdf::content::Operation::new("ET", vec![]),
+        ],
+    };
+
+    // Page 1: rotated 90 degrees, MediaBox and Resources inherited from the Pages node
+    let content_1 = source.add_object(Stream::new(
+        dictionary! {},
+        label("Rotated").encode().unwrap(),
+    ));
+    let page_1 = source.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_1,
+        "Rotate" => 90,
+    });
+
+    // Page 2: CropBox and TrimBox inside the inherited MediaBox
+    let content_2 = source.add_object(Stream::new(
+        dictionary! {},
+        label("Cropped").encode().unwrap(),
+    ));
+    let page_2 = source.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_2,
+        "CropBox" => vec![10.into(), 20.into(), 210.into(), 420.into()],
+        "TrimBox" => vec![20.into(), 30.into(), 200.into(), 410.into()],
+    });
+
+    source.set_object(
+        pages_id,
+        dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_1.into(), page_2.into()],
+            "Count" => 2,
+            "MediaBox" => vec![0.into(), 0.into(), 300.into(), 500.into()],
+            "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+        },
+    );
+    let catalog_id = source.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    source.trailer.set("Root", catalog_id);
+
+    let mut bytes = Vec::new();
+    source.save_to(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn test_page_boxes_and_rotation() {
+    ensure_output_dir();
+
+    let mut embedder = PdfEmbedder::new();
+    let source = embedder
+        .load_pdf_from_bytes(&create_rotated_source(), "rotated")
+        .unwrap();
+
+    // Reported sizes are the visible ones: rotated MediaBox, then the CropBox
+    let info = embedder.get_pdf_info(&source).unwrap();
+    assert_eq!(info.page_dimensions, vec![(500.0, 300.0), (200.0, 400.0)]);
+
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.add_object(dictionary! {
+        "Type" => "Pages",
+        "Count" => 1,
+    });
+    let (font_id, mut page_ops, mut all_xobjects) =
+        create_page_with_title(&mut doc, "Page Boxes and Rotation");
+
+    let form_of = |doc: &Document, result: &hipdf::embed_pdf::EmbedResult| {
+        let id = result
+            .xobject_resources
+            .values()
+            .next()
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        doc.get_object(id).unwrap().as_stream().unwrap().dict.clone()
+    };
+    let floats = |obj: &Object| -> Vec<f32> {
+        obj.as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_float().unwrap())
+            .collect()
+    };
+
+    // The rotated page comes out upright, landscape
+    add_section_label(&mut page_ops, 50.0, 760.0, "Rotate 90, inherited MediaBox");
+    let rotated = embedder
+        .embed_pdf(
+            &mut doc,
+            &source,
+            &EmbedOptions::new()
+                .at_position(50.0, 430.0)
+                .with_scale(0.6)
+                .with_layout(MultiPageLayout::SpecificPage(0)),
+        )
+        .unwrap();
+    let form = form_of(&doc, &rotated);
+    assert_eq!(floats(form.get(b"BBox").unwrap()), vec![0.0, 0.0, 300.0, 500.0]);
+    assert_eq!(
+        floats(form.get(b"Matrix").unwrap()),
+        vec![0.0, -1.0, 1.0, 0.0, 0.0, 300.0]
+    );
+    assert!(form.get(b"Resources").unwrap().as_dict().unwrap().has(b"Font"));
+    page_ops.extend(rotated.operations);
+    all_xobjects.extend(rotated.xobject_resources);
+
+    // Each boundary of the second page is imported with its own size
+    let boxes = [
+        (PageBox::MediaBox, vec![0.0, 0.0, 300.0, 500.0]),
+        (PageBox::CropBox, vec![10.0, 20.0, 210.0, 420.0]),
+        (PageBox::TrimBox, vec![20.0, 30.0, 200.0, 410.0]),
+        (PageBox::ArtBox, vec![10.0, 20.0, 210.0, 420.0]),
+    ];
+    let mut x_pos = 50.0;
+    for (page_box, expected_bbox) in boxes {
+        add_section_label(&mut page_ops, x_pos, 400.0, &format!("{:?}", page_box));
+        let result = embedder
+            .embed_pdf(
+                &mut doc,
+                &source,
+                &EmbedOptions::new()
+                    .at_position(x_pos, 150.0)
+                    .with_scale(0.45)
+                    .with_page_box(page_box)
+                    .with_layout(MultiPageLayout::SpecificPage(1)),
+            )
+            .unwrap();
+        let form = form_of(&doc, &result);
+        assert_eq!(floats(form.get(b"BBox").unwrap()), expected_bbox);
+        assert_eq!(
+            floats(form.get(b"Matrix").unwrap())[4..],
+            [-expected_bbox[0], -expected_bbox[1]]
+        );
+        page_ops.extend(result.operations);
+        all_xobjects.extend(result.xobject_resources);
+        x_pos += 130.0;
+    }
+
+    let content = Content {
+        operations: page_ops,
+    };
+    let content_stream = Stream::new(dictionary! {}, content.encode().unwrap());
+    let content_id = doc.add_object(content_stream);
+
+    let mut xobject_dict = Dictionary::new();
+    for (name, obj_ref) in all_xobjects {
+        xobject_dict.set(name, obj_ref);
+    }
+
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        "Contents" => content_id,
+        "Resources" => dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+            "XObject" => xobject_dict,
+        },
+    });
+
+    let pages_dict = doc
+        .get_object_mut(pages_id)
+        .and_then(Object::as_dict_mut)
+        .unwrap();
+    pages_dict.set("Kids", vec![Object::Reference(page_id)]);
+
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => Object::Reference(pages_id),
+    });
+    doc.trailer.set("Root", Object::Reference(catalog_id));
+
+    let output_path = format!("{}/page_boxes_rotation_test.pdf", TEST_OUTPUT_DIR);
+    doc.save(&output_path).unwrap();
+
+    assert!(Path::new(&output_path).exists());
+    println!("✅ Page boxes and rotation test completed");
+    println!("📄 PDF created: {}", output_path);
+}
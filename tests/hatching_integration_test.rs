@@ -3,10 +3,15 @@ use std::io::Result;
 use std::sync::Arc;
 use std::path::Path;
 
-use hipdf::lopdf::{content::Content, dictionary, Dictionary, Document, Object, Stream};
+use hipdf::lopdf::{
+    content::{Content, Operation},
+    dictionary, Dictionary, Document, Object, Stream,
+};
+use hipdf::blocks::{Block, BlockInstance, BlockManager, Transform as BlockTransform};
 use hipdf::hatching::{
-    CustomPattern, HatchConfig, HatchStyle, HatchingManager, PatternedShapeBuilder,
-    ProceduralPattern, Transform,
+    BoundaryLoop, CoonsPatch, CustomPattern, FillRule, HatchBoundary, HatchConfig, HatchLineFamily, HatchSegment, HatchStyle,
+    HatchingManager, LineCap, LineJoin, PatternLibrary, PatternOperations, PatternedShapeBuilder, ProceduralPattern, Shading, Transform,
+    UNCOLORED_PATTERN_COLORSPACE,
 };
 
 /// Directory for hatching test outputs
@@ -220,24 +225,25 @@ fn test_hatching_patterns_showcase() -> Result<()> {
 
     // Create page content
     let mut shape_builder = PatternedShapeBuilder::new();
-    let mut text_ops = Vec::new();
 
     // Add title
-    text_ops.push(lopdf::content::Operation::new("0 g", vec![])); // Set solid black fill for text
-    text_ops.push(lopdf::content::Operation::new("BT", vec![]));
-    text_ops.push(lopdf::content::Operation::new(
-        "Tf",
-        vec![Object::Name(b"F1".to_vec()), 14.into()],
-    ));
-    text_ops.push(lopdf::content::Operation::new(
-        "Td",
-        vec![200.into(), 800.into()],
-    ));
-    text_ops.push(lopdf::content::Operation::new(
-        "Tj",
-        vec![Object::string_literal("20 Different Hatching Patterns")],
-    ));
-    text_ops.push(lopdf::content::Operation::new("ET", vec![]));
+    let mut text_ops = vec![
+        lopdf::content::Operation::new("0 g", vec![]), // Set solid black fill for text
+        lopdf::content::Operation::new("BT", vec![]),
+        lopdf::content::Operation::new(
+            "Tf",
+            vec![Object::Name(b"F1".to_vec()), 14.into()],
+        ),
+        lopdf::content::Operation::new(
+            "Td",
+            vec![200.into(), 800.into()],
+        ),
+        lopdf::content::Operation::new(
+            "Tj",
+            vec![Object::string_literal("20 Different Hatching Patterns")],
+        ),
+        lopdf::content::Operation::new("ET", vec![]),
+    ];
 
     // Create a 5x4 grid of pattern samples
     let start_x = 50.0;
@@ -497,6 +503,7 @@ fn test_custom_patterns_showcase() -> Result<()> {
             }),
             resolution: 16,
             fill: true,
+            smooth: false,
         },
     )));
     let (pattern_id, pattern_name) = hatching_manager.create_pattern(&mut doc, &sierpinski);
@@ -505,24 +512,25 @@ fn test_custom_patterns_showcase() -> Result<()> {
 
     // Create page content
     let mut shape_builder = PatternedShapeBuilder::new();
-    let mut text_ops = Vec::new();
 
     // Title
-    text_ops.push(lopdf::content::Operation::new("0 g", vec![])); // Set solid black fill for text
-    text_ops.push(lopdf::content::Operation::new("BT", vec![]));
-    text_ops.push(lopdf::content::Operation::new(
-        "Tf",
-        vec![Object::Name(b"F1".to_vec()), 16.into()],
-    ));
-    text_ops.push(lopdf::content::Operation::new(
-        "Td",
-        vec![150.into(), 800.into()],
-    ));
-    text_ops.push(lopdf::content::Operation::new(
-        "Tj",
-        vec![Object::string_literal("Custom Pattern Examples")],
-    ));
-    text_ops.push(lopdf::content::Operation::new("ET", vec![]));
+    let mut text_ops = vec![
+        lopdf::content::Operation::new("0 g", vec![]), // Set solid black fill for text
+        lopdf::content::Operation::new("BT", vec![]),
+        lopdf::content::Operation::new(
+            "Tf",
+            vec![Object::Name(b"F1".to_vec()), 16.into()],
+        ),
+        lopdf::content::Operation::new(
+            "Td",
+            vec![150.into(), 800.into()],
+        ),
+        lopdf::content::Operation::new(
+            "Tj",
+            vec![Object::string_literal("Custom Pattern Examples")],
+        ),
+        lopdf::content::Operation::new("ET", vec![]),
+    ];
 
     // Display all custom patterns
     let patterns = vec![
@@ -612,3 +620,909 @@ fn test_custom_patterns_showcase() -> Result<()> {
 
     Ok(())
 }
+
+/// Saves a single A4 page drawing `operations` with the given resources
+fn save_hatching_page(mut doc: Document, resources: Dictionary, operations: Vec<Operation>, file_name: &str) -> Result<String> {
+    ensure_hatching_output_dir();
+
+    let content = Content { operations };
+    let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+    let pages_id = doc.new_object_id();
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        "Contents" => content_id,
+        "Resources" => resources,
+    });
+    doc.set_object(
+        pages_id,
+        dictionary! { "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1 },
+    );
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+
+    let output_path = format!("{}/{}", HATCHING_TEST_OUTPUT_DIR, file_name);
+    doc.save(&output_path)?;
+    Ok(output_path)
+}
+
+#[test]
+fn test_hatch_arbitrary_boundaries() -> Result<()> {
+    let mut doc = Document::with_version("1.5");
+    let mut manager = HatchingManager::new();
+    let mut resources = dictionary! { "Pattern" => Dictionary::new() };
+    let (pattern_id, pattern_name) = manager.create_pattern(
+        &mut doc,
+        &HatchConfig::new(HatchStyle::DiagonalRight).with_spacing(6.0),
+    );
+    manager.add_pattern_to_resources(&mut resources, &pattern_name, pattern_id);
+
+    // Room outline with a curved bay and a rounded corner, plus two column cutouts
+    let room = BoundaryLoop::new(50.0, 500.0)
+        .line_to(300.0, 500.0)
+        .arc(300.0, 560.0, 60.0, -90.0, 90.0)
+        .line_to(100.0, 620.0)
+        .curve_to(70.0, 620.0, 50.0, 600.0, 50.0, 570.0);
+    assert!(room.signed_area() > 0.0);
+    assert!((room.reversed().signed_area() + room.signed_area()).abs() < 1.0);
+
+    // Islands given in the same direction as the outline are reversed for the nonzero rule
+    let column = BoundaryLoop::rectangle(120.0, 540.0, 30.0, 30.0);
+    let round_column = BoundaryLoop::circle(220.0, 555.0, 15.0);
+    let boundary = HatchBoundary::new(room.clone())
+        .with_island(column.clone())
+        .with_island(round_column);
+    assert!(boundary.loops[1].signed_area() < 0.0 && boundary.loops[2].signed_area() < 0.0);
+    assert!((boundary.loops[1].signed_area() + 900.0).abs() < 0.01);
+    let (min_x, min_y, max_x, max_y) = boundary.bounds();
+    assert!((min_x - 50.0).abs() < 0.01 && (min_y - 500.0).abs() < 0.01);
+    assert!((max_x - 360.0).abs() < 0.1 && (max_y - 620.0).abs() < 0.01);
+
+    // The same shape with the even-odd rule needs no particular loop direction
+    let even_odd = HatchBoundary::new(room.clone())
+        .with_loop(column.clone())
+        .with_loop(BoundaryLoop::circle(220.0, 555.0, 15.0))
+        .with_fill_rule(FillRule::EvenOdd);
+    let shifted = |boundary: &HatchBoundary| {
+        let mut ops = vec![Operation::new("q", vec![]), Operation::new("cm", vec![1.into(), 0.into(), 0.into(), 1.into(), 0.into(), (-250).into()])];
+        let mut builder = PatternedShapeBuilder::new();
+        builder.boundary(boundary, &pattern_name);
+        ops.extend(builder.build());
+        ops.push(Operation::new("Q", vec![]));
+        ops
+    };
+
+    let mut builder = PatternedShapeBuilder::new();
+    builder.boundary(&boundary, &pattern_name);
+    let mut operations = builder.build();
+    assert_eq!(operations.last().unwrap().operator, "f");
+    let even_odd_ops = shifted(&even_odd);
+    assert!(even_odd_ops.iter().any(|op| op.operator == "f*"));
+    operations.extend(even_odd_ops);
+
+    // Outline the boundary on top of the hatch
+    operations.push(Operation::new("w", vec![1.into()]));
+    operations.extend(boundary.to_operations());
+    operations.push(Operation::new("S", vec![]));
+
+    let output_path = save_hatching_page(doc, resources, operations, "hatch_boundaries.pdf")?;
+    println!("✅ Arbitrary boundary hatching test passed");
+    println!("📄 Output: {}", output_path);
+    Ok(())
+}
+
+#[test]
+fn test_vector_hatch_lines() -> Result<()> {
+    let manager = HatchingManager::new();
+    let length = |segments: &[HatchSegment]| {
+        segments
+            .iter()
+            .map(|(a, b)| (b.0 - a.0).hypot(b.1 - a.1))
+            .sum::<f32>()
+    };
+
+    // Horizontal lines 10pt apart through a 100pt square with a 20pt column cutout
+    let room = BoundaryLoop::rectangle(0.0, 0.0, 100.0, 100.0);
+    let column = BoundaryLoop::rectangle(40.0, 40.0, 20.0, 20.0);
+    let horizontal = HatchConfig::new(HatchStyle::Horizontal).with_spacing(10.0);
+    let boundary = HatchBoundary::new(room.clone()).with_island(column.clone());
+    let segments = manager.hatch_line_segments(&boundary, &horizontal).unwrap();
+    assert_eq!(segments.len(), 12);
+    assert!((length(&segments) - 960.0).abs() < 0.01);
+    assert!(segments.iter().all(|(a, b)| a.1 == b.1 && ((a.1 - 5.0) % 10.0).abs() < 1e-3));
+
+    // The fill rule decides whether a same-direction inner loop is a hole
+    let even_odd = HatchBoundary::new(room.clone())
+        .with_loop(column.clone())
+        .with_fill_rule(FillRule::EvenOdd);
+    assert!((length(&manager.hatch_line_segments(&even_odd, &horizontal).unwrap()) - 960.0).abs() < 0.01);
+    let nonzero = HatchBoundary::new(room).with_loop(column);
+    assert!((length(&manager.hatch_line_segments(&nonzero, &horizontal).unwrap()) - 1000.0).abs() < 0.01);
+
+    // Lines clipped to a circle end on the circle
+    let circle = HatchBoundary::new(BoundaryLoop::circle(300.0, 400.0, 80.0));
+    let cross = HatchConfig::new(HatchStyle::DiagonalCross).with_spacing(12.0).with_angle(15.0);
+    let segments = manager.hatch_line_segments(&circle, &cross).unwrap();
+    assert!(!segments.is_empty());
+    for (a, b) in &segments {
+        for (x, y) in [a, b] {
+            assert!(((x - 300.0).hypot(y - 400.0) - 80.0).abs() < 0.1);
+        }
+    }
+
+    // Only line-based styles have vector lines
+    assert!(manager
+        .hatch_line_segments(&circle, &HatchConfig::new(HatchStyle::Dots))
+        .is_err());
+
+    // Dashed lines get a phase per segment
+    let dashed = cross.clone().with_dash(vec![4.0, 2.0], 0.0).with_color(0.0, 0.0, 0.8);
+    let mut operations = manager.create_vector_hatch(&circle, &dashed).unwrap();
+    let phases: Vec<f32> = operations
+        .iter()
+        .filter(|op| op.operator == "d")
+        .map(|op| op.operands[1].as_float().unwrap())
+        .collect();
+    assert_eq!(phases.len(), segments.len());
+    assert!(phases.iter().all(|&phase| (0.0..6.0).contains(&phase)));
+    assert!(!operations.iter().any(|op| op.operator == "scn"));
+
+    let room_with_column = HatchBoundary::new(
+        BoundaryLoop::new(50.0, 500.0)
+            .line_to(300.0, 500.0)
+            .arc(300.0, 560.0, 60.0, -90.0, 90.0)
+            .line_to(50.0, 620.0),
+    )
+    .with_island(BoundaryLoop::circle(200.0, 560.0, 20.0));
+    let brick_red = HatchConfig::new(HatchStyle::DiagonalRight)
+        .with_spacing(8.0)
+        .with_color(0.7, 0.1, 0.1)
+        .with_background(1.0, 0.95, 0.9);
+    operations.extend(manager.create_vector_hatch(&room_with_column, &brick_red).unwrap());
+
+    let output_path = save_hatching_page(Document::with_version("1.5"), Dictionary::new(), operations, "vector_hatch.pdf")?;
+    println!("✅ Vector hatch test passed");
+    println!("📄 Output: {}", output_path);
+    Ok(())
+}
+
+const STANDARD_PATTERNS: &str = "\
+;; Excerpt of a standard pattern file, in inches
+*ANSI31, ANSI Iron, Brick, Stone masonry
+45, 0,0, 0,.125
+*ANSI37, ANSI Lead, Zinc, Magnesium, Sound/Heat/Elec Insulation
+45, 0,0, 0,.125
+135, 0,0, 0,.125
+*BRICK, Brick or masonry-type surface
+0, 0,0, 0,.25
+90, 0,0, .25,.25, .25,-.25
+*DOTS, A series of dots
+0, 0,0, .03125,.0625, 0,-.0625 ; dots need round caps
+*DASHED
+0, 0,0, 0,10, -2,10,-3
+";
+
+#[test]
+fn test_pat_pattern_library() -> Result<()> {
+    let library = PatternLibrary::parse(STANDARD_PATTERNS).unwrap();
+    assert_eq!(library.patterns().len(), 5);
+    let ansi31 = library.get("ansi31").unwrap();
+    assert_eq!(ansi31.description, "ANSI Iron, Brick, Stone masonry");
+    assert_eq!(ansi31.families, vec![HatchLineFamily::new(45.0, (0.0, 0.0), (0.0, 0.125))]);
+    assert_eq!(library.get("ANSI37").unwrap().families.len(), 2);
+    assert_eq!(library.get("BRICK").unwrap().families[1].dashes, vec![0.25, -0.25]);
+    assert!(library.get("DASHED").unwrap().description.is_empty());
+    assert!(library.get("HONEY").is_none());
+
+    // Malformed files report the offending line
+    assert!(PatternLibrary::parse("45, 0,0, 0,.125").is_err());
+    let error = PatternLibrary::parse("*X\n45, 0, zero, 0, 1").unwrap_err();
+    assert!(error.to_string().starts_with("Line 2"));
+    assert!(PatternLibrary::parse("*X\n45, 0, 0").is_err());
+
+    // Inches to points: ANSI31 lines are 9pt apart
+    let manager = HatchingManager::new();
+    let square = HatchBoundary::new(BoundaryLoop::rectangle(0.0, 0.0, 72.0, 72.0));
+    let ansi31 = library.hatch_config("ANSI31").unwrap().with_scale(72.0);
+    assert_eq!(manager.hatch_line_segments(&square, &ansi31).unwrap().len(), 11);
+
+    // Each brick course is shifted by half a brick
+    let inset = HatchBoundary::new(BoundaryLoop::rectangle(1.0, 1.0, 70.0, 70.0));
+    let brick = library.hatch_config("brick").unwrap().with_scale(72.0);
+    assert_eq!(manager.hatch_line_segments(&inset, &brick).unwrap().len(), 6);
+    let operations = manager.create_vector_hatch(&inset, &brick).unwrap();
+    let dashes: Vec<&Operation> = operations.iter().filter(|op| op.operator == "d").collect();
+    assert_eq!(dashes.len(), 3);
+    let mut phases: Vec<f32> = dashes.iter().map(|op| op.operands[1].as_float().unwrap()).collect();
+    phases.sort_by(f32::total_cmp);
+    for (phase, expected) in phases.iter().zip([1.0, 19.0, 19.0]) {
+        assert!((phase - expected).abs() < 0.01);
+    }
+
+    // Dash lists starting with a gap are rotated to start with a dash
+    let strip = HatchBoundary::new(BoundaryLoop::rectangle(0.0, -1.0, 100.0, 2.0));
+    let dashed = library.hatch_config("DASHED").unwrap();
+    let operations = manager.create_vector_hatch(&strip, &dashed).unwrap();
+    let dash = operations.iter().find(|op| op.operator == "d").unwrap();
+    let array: Vec<f32> = dash.operands[0].as_array().unwrap().iter().map(|v| v.as_float().unwrap()).collect();
+    assert_eq!(array, vec![10.0, 5.0]);
+    assert!((dash.operands[1].as_float().unwrap() - 13.0).abs() < 0.01);
+
+    // Dots are drawn as round caps
+    let dots = library.hatch_config("DOTS").unwrap().with_scale(72.0).with_line_width(1.5);
+    assert!(manager
+        .create_vector_hatch(&square, &dots)
+        .unwrap()
+        .iter()
+        .any(|op| op.operator == "J"));
+
+    // Tiling patterns use a tile the families repeat over
+    let mut doc = Document::with_version("1.5");
+    let mut tiling = HatchingManager::new();
+    let mut resources = dictionary! { "Pattern" => Dictionary::new() };
+    let step = |doc: &Document, id| {
+        let dict = &doc.get_object(id).unwrap().as_stream().unwrap().dict;
+        (dict.get(b"XStep").unwrap().as_float().unwrap(), dict.get(b"YStep").unwrap().as_float().unwrap())
+    };
+    let mut operations = Vec::new();
+    let configs = [
+        brick.clone().with_color(0.6, 0.2, 0.1),
+        ansi31.clone().with_angle(45.0),
+        library.hatch_config("ANSI37").unwrap().with_scale(72.0),
+        dots.clone(),
+    ];
+    let expected_steps = [(36.0, 36.0), (9.0, 9.0), (12.728, 12.728), (4.5, 9.0)];
+    for (index, (config, expected)) in configs.iter().zip(expected_steps).enumerate() {
+        let (pattern_id, pattern_name) = tiling.create_pattern(&mut doc, config);
+        let (x_step, y_step) = step(&doc, pattern_id);
+        assert!((x_step - expected.0).abs() < 0.01 && (y_step - expected.1).abs() < 0.01);
+        tiling.add_pattern_to_resources(&mut resources, &pattern_name, pattern_id);
+
+        let mut builder = PatternedShapeBuilder::new();
+        builder.rectangle(50.0 + index as f32 * 130.0, 600.0, 110.0, 110.0, &pattern_name);
+        operations.extend(builder.build());
+    }
+
+    let mut vector = Vec::new();
+    for (index, config) in configs.iter().enumerate() {
+        let x = 50.0 + index as f32 * 130.0;
+        vector.extend(
+            manager
+                .create_vector_hatch(&HatchBoundary::new(BoundaryLoop::circle(x + 55.0, 450.0, 55.0)), config)
+                .unwrap(),
+        );
+    }
+    operations.extend(vector);
+
+    let output_path = save_hatching_page(doc, resources, operations, "pat_patterns.pdf")?;
+    println!("✅ .pat pattern library test passed");
+    println!("📄 Output: {}", output_path);
+    Ok(())
+}
+
+#[test]
+fn test_uncolored_patterns() -> Result<()> {
+    let mut doc = Document::with_version("1.5");
+    let mut manager = HatchingManager::new();
+    let mut resources = dictionary! { "ColorSpace" => dictionary! { "CS0" => "DeviceGray" } };
+
+    // One definition; its color and background never reach the pattern content
+    let config = HatchConfig::new(HatchStyle::DiagonalCross)
+        .with_spacing(8.0)
+        .with_color(1.0, 0.0, 0.0)
+        .with_background(1.0, 1.0, 0.8)
+        .with_uncolored(true);
+    let (pattern_id, pattern_name) = manager.create_pattern(&mut doc, &config);
+    manager.add_pattern_to_resources(&mut resources, &pattern_name, pattern_id);
+    manager.add_uncolored_colorspace_to_resources(&mut resources);
+
+    let pattern = doc.get_object(pattern_id).unwrap().as_stream().unwrap();
+    assert_eq!(pattern.dict.get(b"PaintType").unwrap().as_i64().unwrap(), 2);
+    let content = Content::decode(&pattern.content).unwrap();
+    assert!(content.operations.iter().all(|op| !["rg", "RG", "f"].contains(&op.operator.as_str())));
+
+    // Custom patterns lose their color operators
+    let stamp = HatchConfig::new(HatchStyle::Custom(CustomPattern::Simple(Arc::new(|width, height| {
+        vec![
+            Operation::new("rg", vec![0.into(), 0.5.into(), 0.into()]),
+            Operation::new("re", vec![0.into(), 0.into(), (width / 2.0).into(), (height / 2.0).into()]),
+            Operation::new("f", vec![]),
+        ]
+    }))))
+    .with_spacing(10.0)
+    .with_uncolored(true);
+    let (stamp_id, stamp_name) = manager.create_pattern(&mut doc, &stamp);
+    manager.add_pattern_to_resources(&mut resources, &stamp_name, stamp_id);
+    let stamp_content = Content::decode(&doc.get_object(stamp_id).unwrap().as_stream().unwrap().content).unwrap();
+    let operators: Vec<&str> = stamp_content.operations.iter().map(|op| op.operator.as_str()).collect();
+    assert_eq!(operators, vec!["re", "f"]);
+
+    // The color space sits next to existing ones
+    let color_spaces = resources.get(b"ColorSpace").unwrap().as_dict().unwrap();
+    assert!(color_spaces.has(b"CS0"));
+    let space = color_spaces.get(UNCOLORED_PATTERN_COLORSPACE.as_bytes()).unwrap().as_array().unwrap();
+    assert_eq!(space[0].as_name().unwrap(), b"Pattern");
+    assert_eq!(space[1].as_name().unwrap(), b"DeviceRGB");
+
+    // The same pattern in three discipline colors
+    let colors = [(0.8, 0.1, 0.1), (0.1, 0.5, 0.1), (0.1, 0.2, 0.8)];
+    let mut builder = PatternedShapeBuilder::new();
+    for (index, &color) in colors.iter().enumerate() {
+        builder
+            .set_pattern_color(Some(color))
+            .rectangle(50.0 + index as f32 * 170.0, 600.0, 150.0, 150.0, &pattern_name);
+    }
+    builder
+        .set_pattern_color(Some((0.4, 0.4, 0.4)))
+        .circle(300.0, 400.0, 80.0, &stamp_name);
+    let mut operations = builder.build();
+
+    let selections: Vec<&Operation> = operations.iter().filter(|op| op.operator == "scn").collect();
+    assert_eq!(selections.len(), 4);
+    assert_eq!(selections[1].operands.len(), 4);
+    assert_eq!(selections[1].operands[3].as_name().unwrap(), pattern_name.as_bytes());
+    assert_eq!(operations.iter().filter(|op| op.operator == "cs").count(), 4);
+    assert_eq!(doc.objects.len(), 2);
+
+    // Stroking with an uncolored pattern
+    operations.push(PatternOperations::set_uncolored_pattern_stroke_colorspace());
+    operations.push(PatternOperations::set_stroke_pattern_with_color(&pattern_name, (0.0, 0.0, 0.0)));
+    operations.push(Operation::new("w", vec![12.into()]));
+    operations.push(Operation::new("re", vec![50.into(), 200.into(), 490.into(), 100.into()]));
+    operations.push(Operation::new("S", vec![]));
+
+    let output_path = save_hatching_page(doc, resources, operations, "uncolored_patterns.pdf")?;
+    println!("✅ Uncolored pattern test passed");
+    println!("📄 Output: {}", output_path);
+    Ok(())
+}
+
+#[test]
+fn test_shading_patterns() -> Result<()> {
+    let mut doc = Document::with_version("1.5");
+    let mut manager = HatchingManager::new();
+    let mut resources = Dictionary::new();
+    let dictionary_of = |doc: &Document, id| doc.get_object(id).unwrap().as_dict().unwrap().clone();
+
+    // Sky: a three-stop linear gradient used as a pattern fill
+    let sky = Shading::axial(
+        (0.0, 842.0),
+        (0.0, 450.0),
+        vec![(0.0, (0.2, 0.4, 0.8)), (0.6, (0.6, 0.8, 1.0)), (1.0, (1.0, 1.0, 1.0))],
+    );
+    let (sky_id, sky_name) = manager.create_shading_pattern(&mut doc, &sky).unwrap();
+    manager.add_pattern_to_resources(&mut resources, &sky_name, sky_id);
+    let sky_pattern = dictionary_of(&doc, sky_id);
+    assert_eq!(sky_pattern.get(b"PatternType").unwrap().as_i64().unwrap(), 2);
+    let sky_shading = dictionary_of(&doc, sky_pattern.get(b"Shading").unwrap().as_reference().unwrap());
+    assert_eq!(sky_shading.get(b"ShadingType").unwrap().as_i64().unwrap(), 2);
+    let function = sky_shading.get(b"Function").unwrap().as_dict().unwrap();
+    assert_eq!(function.get(b"FunctionType").unwrap().as_i64().unwrap(), 3);
+    assert_eq!(function.get(b"Functions").unwrap().as_array().unwrap().len(), 2);
+    assert_eq!(function.get(b"Bounds").unwrap().as_array().unwrap().len(), 1);
+    assert_eq!(function.get(b"Encode").unwrap().as_array().unwrap().len(), 4);
+
+    // Ground glow: a radial gradient painted directly inside a boundary
+    let glow = Shading::radial((300.0, 250.0), 200.0, vec![(0.2, (1.0, 0.9, 0.5)), (1.0, (0.3, 0.5, 0.2))])
+        .with_extend(true, false);
+    let (glow_id, glow_name) = manager.create_shading(&mut doc, &glow).unwrap();
+    manager.add_shading_to_resources(&mut resources, &glow_name, glow_id);
+    let glow_shading = dictionary_of(&doc, glow_id);
+    assert_eq!(glow_shading.get(b"ShadingType").unwrap().as_i64().unwrap(), 3);
+    assert_eq!(glow_shading.get(b"Coords").unwrap().as_array().unwrap().len(), 6);
+    let extend = glow_shading.get(b"Extend").unwrap().as_array().unwrap();
+    assert!(extend[0].as_bool().unwrap() && !extend[1].as_bool().unwrap());
+    // A stop is added to hold the first color from the center
+    let glow_function = glow_shading.get(b"Function").unwrap().as_dict().unwrap();
+    assert_eq!(glow_function.get(b"Functions").unwrap().as_array().unwrap().len(), 2);
+
+    // Two stops need no stitching, one stop is a flat color
+    let (fade_id, _) = manager
+        .create_shading(&mut doc, &Shading::axial((0.0, 0.0), (1.0, 0.0), vec![(0.0, (0.0, 0.0, 0.0)), (1.0, (1.0, 1.0, 1.0))]))
+        .unwrap();
+    let fade = dictionary_of(&doc, fade_id);
+    assert_eq!(fade.get(b"Function").unwrap().as_dict().unwrap().get(b"FunctionType").unwrap().as_i64().unwrap(), 2);
+    let (flat_id, _) = manager
+        .create_shading(&mut doc, &Shading::axial((0.0, 0.0), (1.0, 0.0), vec![(0.5, (0.5, 0.5, 0.5))]))
+        .unwrap();
+    let flat = dictionary_of(&doc, flat_id);
+    let flat_function = flat.get(b"Function").unwrap().as_dict().unwrap();
+    assert_eq!(flat_function.get(b"C0").unwrap(), flat_function.get(b"C1").unwrap());
+
+    assert!(manager.create_shading(&mut doc, &Shading::axial((0.0, 0.0), (1.0, 0.0), vec![])).is_err());
+    assert!(manager.create_shading(&mut doc, &Shading::TriangleMesh(vec![])).is_err());
+
+    // Meshes pack a flag, 32-bit coordinates and 16-bit color components per vertex
+    let red = (1.0, 0.0, 0.0);
+    let green = (0.0, 1.0, 0.0);
+    let blue = (0.0, 0.0, 1.0);
+    let triangles = Shading::TriangleMesh(vec![
+        [((50.0, 50.0), red), ((250.0, 50.0), green), ((150.0, 180.0), blue)],
+        [((250.0, 50.0), green), ((350.0, 180.0), red), ((150.0, 180.0), blue)],
+    ]);
+    let (mesh_id, mesh_name) = manager.create_shading(&mut doc, &triangles).unwrap();
+    manager.add_shading_to_resources(&mut resources, &mesh_name, mesh_id);
+    let mesh = doc.get_object(mesh_id).unwrap().as_stream().unwrap();
+    assert_eq!(mesh.dict.get(b"ShadingType").unwrap().as_i64().unwrap(), 4);
+    assert_eq!(mesh.content.len(), 6 * (1 + 8 + 6));
+    assert_eq!(&mesh.content[1..5], &[0, 0, 0, 0]);
+    assert_eq!(&mesh.content[5..9], &[0, 0, 0, 0]);
+
+    let patch = CoonsPatch::rectangle(400.0, 50.0, 150.0, 130.0, [red, green, blue, (1.0, 1.0, 0.0)]);
+    assert_eq!(patch.points[3], (400.0, 180.0));
+    let (patch_id, patch_name) = manager.create_shading(&mut doc, &Shading::CoonsPatches(vec![patch])).unwrap();
+    manager.add_shading_to_resources(&mut resources, &patch_name, patch_id);
+    let patches = doc.get_object(patch_id).unwrap().as_stream().unwrap();
+    assert_eq!(patches.dict.get(b"ShadingType").unwrap().as_i64().unwrap(), 6);
+    assert_eq!(patches.content.len(), 1 + 12 * 8 + 4 * 6);
+
+    let mut builder = PatternedShapeBuilder::new();
+    builder
+        .rectangle(0.0, 450.0, 595.0, 392.0, &sky_name)
+        .shaded_boundary(
+            &HatchBoundary::new(BoundaryLoop::circle(300.0, 300.0, 140.0)).with_island(BoundaryLoop::circle(300.0, 300.0, 40.0)),
+            &glow_name,
+        )
+        .shaded_boundary(&HatchBoundary::new(BoundaryLoop::rectangle(0.0, 0.0, 595.0, 200.0)), &mesh_name)
+        .shaded_boundary(&HatchBoundary::new(BoundaryLoop::rectangle(0.0, 0.0, 595.0, 200.0)), &patch_name);
+    let operations = builder.build();
+    assert_eq!(operations.iter().filter(|op| op.operator == "sh").count(), 3);
+    assert_eq!(operations.iter().filter(|op| op.operator == "W").count(), 3);
+
+    let output_path = save_hatching_page(doc, resources, operations, "shading_patterns.pdf")?;
+    println!("✅ Shading pattern test passed");
+    println!("📄 Output: {}", output_path);
+    Ok(())
+}
+
+#[test]
+fn test_pattern_cache() -> Result<()> {
+    let mut doc = Document::with_version("1.5");
+    let mut manager = HatchingManager::new();
+    let concrete = HatchConfig::new(HatchStyle::DiagonalCross).with_spacing(6.0);
+
+    // Identical configs share one pattern object and name
+    let first = manager.create_pattern(&mut doc, &concrete);
+    let second = manager.create_pattern(&mut doc, &concrete.clone());
+    assert_eq!(first, second);
+    assert_eq!(doc.objects.len(), 1);
+
+    // Any difference in content or dictionary makes a new pattern
+    let red = manager.create_pattern(&mut doc, &concrete.clone().with_color(0.8, 0.0, 0.0));
+    let wide = manager.create_pattern(&mut doc, &concrete.clone().with_scale(2.0));
+    assert_ne!(red.0, first.0);
+    assert_ne!(wide.0, first.0);
+    assert_eq!(
+        manager.create_custom_pattern(&mut doc, 10.0, 10.0, |b| b.rectangle(0.0, 0.0, 5.0, 5.0).fill()),
+        manager.create_custom_pattern(&mut doc, 10.0, 10.0, |b| b.rectangle(0.0, 0.0, 5.0, 5.0).fill()),
+    );
+
+    // Shadings are cached too, including the shading behind a shading pattern
+    let sky = Shading::axial((0.0, 0.0), (0.0, 100.0), vec![(0.0, (0.2, 0.4, 0.8)), (1.0, (1.0, 1.0, 1.0))]);
+    let sky_pattern = manager.create_shading_pattern(&mut doc, &sky).unwrap();
+    assert_eq!(manager.create_shading_pattern(&mut doc, &sky).unwrap(), sky_pattern);
+    let (sky_shading, _) = manager.create_shading(&mut doc, &sky).unwrap();
+    let pattern = doc.get_object(sky_pattern.0).unwrap().as_dict().unwrap();
+    assert_eq!(pattern.get(b"Shading").unwrap().as_reference().unwrap(), sky_shading);
+    assert_eq!(manager.cache().len(), 6);
+    assert_eq!(doc.objects.len(), 6);
+
+    // The cache carries over to the next page's manager
+    let mut page_manager = HatchingManager::with_cache(manager.into_cache());
+    assert_eq!(page_manager.create_pattern(&mut doc, &concrete), first);
+    let (earth_id, earth_name) = page_manager.create_pattern(&mut doc, &HatchConfig::new(HatchStyle::Dots));
+    assert!(![&first.1, &red.1, &wide.1, &sky_pattern.1].contains(&&earth_name));
+
+    let mut resources = Dictionary::new();
+    page_manager.add_cached_to_resources(&mut resources);
+    let patterns = resources.get(b"Pattern").unwrap().as_dict().unwrap();
+    assert_eq!(patterns.len(), 6);
+    assert_eq!(patterns.get(earth_name.as_bytes()).unwrap().as_reference().unwrap(), earth_id);
+    assert_eq!(resources.get(b"Shading").unwrap().as_dict().unwrap().len(), 1);
+
+    let mut builder = PatternedShapeBuilder::new();
+    for index in 0..20 {
+        let (_, name) = page_manager.create_pattern(&mut doc, &concrete);
+        builder.rectangle(50.0 + (index % 5) as f32 * 100.0, 100.0 + (index / 5) as f32 * 100.0, 90.0, 90.0, &name);
+    }
+    builder.rectangle(50.0, 600.0, 490.0, 100.0, &earth_name);
+    assert_eq!(page_manager.cache().len(), 7);
+
+    let output_path = save_hatching_page(doc, resources, builder.build(), "pattern_cache.pdf")?;
+    println!("✅ Pattern cache test passed");
+    println!("📄 Output: {}", output_path);
+    Ok(())
+}
+
+#[test]
+fn test_pattern_matrix_alignment() -> Result<()> {
+    let mut doc = Document::with_version("1.5");
+    let mut manager = HatchingManager::new();
+    let mut resources = Dictionary::new();
+    let matrix_of = |doc: &Document, id| -> Vec<f32> {
+        let pattern = doc.get_object(id).unwrap().as_stream().unwrap();
+        pattern.dict.get(b"Matrix").unwrap().as_array().unwrap().iter().map(|v| v.as_float().unwrap()).collect()
+    };
+
+    // Adjacent rooms hatched from one base point share the pattern and line up
+    let brick = HatchConfig::new(HatchStyle::Brick).with_spacing(6.0).with_origin(100.0, 200.0);
+    let (brick_id, brick_name) = manager.create_pattern(&mut doc, &brick);
+    assert_eq!(manager.create_pattern(&mut doc, &brick.clone()).0, brick_id);
+    assert_eq!(matrix_of(&doc, brick_id), vec![1.0, 0.0, 0.0, 1.0, 100.0, 200.0]);
+    manager.add_pattern_to_resources(&mut resources, &brick_name, brick_id);
+    let (unanchored_id, _) = manager.create_pattern(&mut doc, &HatchConfig::new(HatchStyle::Brick).with_spacing(6.0));
+    assert!(!doc.get_object(unanchored_id).unwrap().as_stream().unwrap().dict.has(b"Matrix"));
+
+    let mut builder = PatternedShapeBuilder::new();
+    builder
+        .rectangle(50.0, 600.0, 120.0, 150.0, &brick_name)
+        .rectangle(170.0, 600.0, 200.0, 150.0, &brick_name);
+
+    // A hatch following a block instance, with its origin given in block coordinates
+    let placement = BlockTransform::full(300.0, 400.0, 2.0, 2.0, 30.0);
+    let follow = HatchConfig::new(HatchStyle::Cross)
+        .with_spacing(5.0)
+        .with_block_transform(&placement)
+        .with_origin(10.0, 0.0);
+    let (follow_id, follow_name) = manager.create_pattern(&mut doc, &follow);
+    manager.add_pattern_to_resources(&mut resources, &follow_name, follow_id);
+    let matrix = matrix_of(&doc, follow_id);
+    let (sin, cos) = 30f32.to_radians().sin_cos();
+    let expected = [2.0 * cos, 2.0 * sin, -2.0 * sin, 2.0 * cos, 300.0 + 20.0 * cos, 400.0 + 20.0 * sin];
+    for (value, expected) in matrix.iter().zip(expected) {
+        assert!((value - expected).abs() < 1e-3);
+    }
+
+    let mut room = PatternedShapeBuilder::new();
+    room.rectangle(0.0, 0.0, 60.0, 40.0, &follow_name);
+    let mut blocks = BlockManager::new();
+    blocks.register(Block::new("room", room.build()));
+    let mut operations = builder.build();
+    operations.extend(blocks.render_instance(&BlockInstance::new("room", placement)));
+
+    // Vector hatches honor the matrix the same way
+    let square = HatchBoundary::new(BoundaryLoop::rectangle(0.0, 0.0, 100.0, 100.0));
+    let horizontal = HatchConfig::new(HatchStyle::Horizontal).with_spacing(10.0);
+    let shifted = manager
+        .hatch_line_segments(&square, &horizontal.clone().with_origin(0.0, 3.0))
+        .unwrap();
+    assert_eq!(shifted.len(), 10);
+    assert!(shifted.iter().all(|(a, b)| a.1 == b.1 && ((a.1 - 8.0).rem_euclid(10.0)).abs() < 1e-3));
+
+    let turned = horizontal
+        .clone()
+        .with_block_transform(&BlockTransform::full(0.0, 0.0, 1.0, 1.0, 90.0));
+    let segments = manager.hatch_line_segments(&square, &turned).unwrap();
+    assert_eq!(segments.len(), 10);
+    assert!(segments.iter().all(|(a, b)| (a.0 - b.0).abs() < 1e-3 && (a.0.rem_euclid(10.0) - 5.0).abs() < 1e-3));
+
+    // Stretching the pattern space widens the line spacing
+    let stretched = horizontal.clone().with_matrix([1.0, 0.0, 0.0, 2.0, 0.0, 0.0]);
+    assert_eq!(manager.hatch_line_segments(&square, &stretched).unwrap().len(), 5);
+    let dashed = HatchConfig::new(HatchStyle::Vertical)
+        .with_spacing(10.0)
+        .with_line_width(0.3)
+        .with_color(0.0, 0.3, 0.6)
+        .with_matrix([1.0, 0.0, 0.0, 2.0, 400.0, 50.0]);
+    operations.extend(
+        manager
+            .create_vector_hatch(&HatchBoundary::new(BoundaryLoop::circle(450.0, 150.0, 60.0)), &dashed)
+            .unwrap(),
+    );
+
+    let output_path = save_hatching_page(doc, resources, operations, "pattern_matrix.pdf")?;
+    println!("✅ Pattern matrix test passed");
+    println!("📄 Output: {}", output_path);
+    Ok(())
+}
+
+/// Outlines drawn by a pattern's content, as (signed area, point count) per subpath
+fn pattern_outlines(doc: &Document, pattern_id: hipdf::lopdf::ObjectId) -> (Vec<(f32, usize)>, Vec<String>) {
+    let content = Content::decode(&doc.get_object(pattern_id).unwrap().as_stream().unwrap().content).unwrap();
+    let mut outlines = Vec::new();
+    let mut points: Vec<(f32, f32)> = Vec::new();
+    for op in &content.operations {
+        let number = |index: usize| op.operands[index].as_float().unwrap();
+        match op.operator.as_str() {
+            "m" | "l" => points.push((number(0), number(1))),
+            "c" => points.push((number(4), number(5))),
+            "h" => {
+                let area = (0..points.len())
+                    .map(|i| {
+                        let (p, q) = (points[i], points[(i + 1) % points.len()]);
+                        p.0 * q.1 - q.0 * p.1
+                    })
+                    .sum::<f32>()
+                    / 2.0;
+                outlines.push((area, points.len()));
+                points.clear();
+            }
+            _ => {}
+        }
+    }
+    let operators = content.operations.iter().map(|op| op.operator.clone()).collect();
+    (outlines, operators)
+}
+
+#[test]
+fn test_procedural_regions() -> Result<()> {
+    let mut doc = Document::with_version("1.5");
+    let mut manager = HatchingManager::new();
+    let mut resources = Dictionary::new();
+    let procedural = |pattern: ProceduralPattern| HatchConfig::new(HatchStyle::Custom(CustomPattern::Procedural(pattern))).with_spacing(80.0);
+    let mut create = |doc: &mut Document, config: &HatchConfig| {
+        let (id, name) = manager.create_pattern(doc, config);
+        manager.add_pattern_to_resources(&mut resources, &name, id);
+        (id, name)
+    };
+
+    // A single sample becomes one diamond between its neighbours (10pt samples)
+    let (single, _) = create(&mut doc, &procedural(ProceduralPattern::new(|x, y, _| x == 30.0 && y == 30.0, 8)));
+    let (outlines, operators) = pattern_outlines(&doc, single);
+    assert_eq!(operators, vec!["m", "l", "l", "l", "h", "f"]);
+    assert!((outlines[0].0 - 50.0).abs() < 1e-3);
+
+    // A solid region is one outline instead of a shape per sample, holes wind the other way
+    let (solid, _) = create(&mut doc, &procedural(ProceduralPattern::new(|_, _, _| true, 8)));
+    let (outlines, operators) = pattern_outlines(&doc, solid);
+    assert_eq!((outlines.len(), operators.len()), (1, 10));
+    let (holed, _) = create(&mut doc, &procedural(ProceduralPattern::new(|x, y, _| (x, y) != (40.0, 40.0), 8)));
+    let (outlines, operators) = pattern_outlines(&doc, holed);
+    assert_eq!(outlines.len(), 2);
+    assert!(outlines.iter().any(|o| o.0 > 0.0) && outlines.iter().any(|o| o.0 < 0.0));
+    assert_eq!(operators.iter().filter(|op| *op == "f").count(), 1);
+
+    // Regions at a tile edge continue on the opposite edge
+    let (edge, _) = create(&mut doc, &procedural(ProceduralPattern::new(|x, y, _| x == 0.0 && y == 30.0, 8)));
+    assert_eq!(pattern_outlines(&doc, edge).0.len(), 2);
+
+    // Noise textures stay compact and can be rounded into curves
+    let terrain = |x: f32, y: f32, _: f32| ((x * 0.21).sin() + (y * 0.17).cos() + (x * 0.05 + y * 0.09).sin()) > 0.4;
+    let filled_samples = (0..64)
+        .flat_map(|i| (0..64).map(move |j| (i, j)))
+        .filter(|&(i, j)| terrain(i as f32 * 1.25, j as f32 * 1.25, 0.0))
+        .count();
+    let (rough_id, rough_name) = create(&mut doc, &procedural(ProceduralPattern::new(terrain, 64)));
+    let (smooth_id, smooth_name) = create(&mut doc, &procedural(ProceduralPattern::new(terrain, 64).with_smoothing(true)));
+    let (again_id, _) = create(&mut doc, &procedural(ProceduralPattern::new(terrain, 64)));
+    assert_eq!(again_id, rough_id, "the same procedural configuration is written once");
+    let rough = pattern_outlines(&doc, rough_id).1;
+    let smooth = pattern_outlines(&doc, smooth_id).1;
+    assert!(rough.len() < filled_samples / 4);
+    assert!(smooth.contains(&"c".to_string()) && !smooth.contains(&"l".to_string()));
+
+    let (dots_id, dots_name) = create(&mut doc, &procedural(ProceduralPattern::new(terrain, 16).with_fill(false)));
+    assert!(pattern_outlines(&doc, dots_id).1.iter().filter(|op| *op == "f").count() > 1);
+
+    let mut builder = PatternedShapeBuilder::new();
+    builder
+        .rectangle(50.0, 500.0, 240.0, 240.0, &rough_name)
+        .rectangle(305.0, 500.0, 240.0, 240.0, &smooth_name)
+        .rectangle(50.0, 200.0, 240.0, 240.0, &dots_name);
+
+    let output_path = save_hatching_page(doc, resources, builder.build(), "procedural_regions.pdf")?;
+    println!("✅ Procedural region test passed");
+    println!("📄 Output: {}", output_path);
+    Ok(())
+}
+
+#[test]
+fn test_line_family_weights() -> Result<()> {
+    let mut doc = Document::with_version("1.5");
+    let mut manager = HatchingManager::new();
+    let mut resources = dictionary! { "Pattern" => Dictionary::new() };
+    let content_of = |doc: &Document, id| {
+        Content::decode(&doc.get_object(id).unwrap().as_stream().unwrap().content).unwrap().operations
+    };
+    let operands = |ops: &[Operation], operator: &str| -> Vec<f32> {
+        ops.iter().filter(|op| op.operator == operator).map(|op| op.operands[0].as_float().unwrap()).collect()
+    };
+
+    // Presets are line families: a cross hatch sets its width and color once
+    let (plain_id, plain_name) = manager.create_pattern(&mut doc, &HatchConfig::new(HatchStyle::Cross).with_spacing(10.0));
+    let plain = content_of(&doc, plain_id);
+    assert_eq!(operands(&plain, "w"), vec![0.5]);
+    assert_eq!(plain.iter().filter(|op| op.operator == "RG").count(), 1);
+    assert!(!plain.iter().any(|op| op.operator == "J" || op.operator == "j"));
+    manager.add_pattern_to_resources(&mut resources, &plain_name, plain_id);
+
+    // Restyled preset: heavy red courses over thin dashed, round-capped joints
+    let mut families = HatchStyle::Cross.line_families(10.0).unwrap();
+    families[0] = families[0].clone().with_width(2.0).with_color(0.8, 0.1, 0.1);
+    families[1] = families[1]
+        .clone()
+        .with_dashes(vec![4.0, -2.0])
+        .with_cap(LineCap::Round)
+        .with_join(LineJoin::Bevel);
+    let weighted = HatchConfig::new(HatchStyle::Cross).with_line_width(0.3).with_line_families(families);
+    assert_eq!(HatchStyle::Cross.line_families(10.0).unwrap().len(), 2);
+
+    let (weighted_id, weighted_name) = manager.create_pattern(&mut doc, &weighted);
+    let ops = content_of(&doc, weighted_id);
+    assert_eq!(operands(&ops, "w"), vec![2.0, 0.3]);
+    assert_eq!(ops.iter().filter(|op| op.operator == "RG").count(), 2);
+    assert_eq!(operands(&ops, "J"), vec![1.0]);
+    assert_eq!(operands(&ops, "j"), vec![2.0]);
+    let dict = &doc.get_object(weighted_id).unwrap().as_stream().unwrap().dict;
+    assert!((dict.get(b"XStep").unwrap().as_float().unwrap() - 10.0).abs() < 0.01);
+    assert!((dict.get(b"YStep").unwrap().as_float().unwrap() - 30.0).abs() < 0.01);
+    manager.add_pattern_to_resources(&mut resources, &weighted_name, weighted_id);
+
+    // Vector hatches style each family the same way, resetting the cap after a family
+    // that set it
+    let mut reversed = HatchStyle::Cross.line_families(10.0).unwrap();
+    reversed[0] = reversed[0].clone().with_cap(LineCap::Square);
+    let vector = manager
+        .create_vector_hatch(
+            &HatchBoundary::new(BoundaryLoop::circle(420.0, 250.0, 90.0)),
+            &HatchConfig::new(HatchStyle::Cross).with_line_families(reversed),
+        )
+        .unwrap();
+    assert_eq!(operands(&vector, "J"), vec![2.0, 0.0]);
+    assert_eq!(operands(&vector, "w"), vec![0.5]);
+
+    // Brick is a solid family of courses and a dashed family of staggered joints
+    let bricks = HatchStyle::Brick.line_families(6.0).unwrap();
+    assert_eq!(bricks.len(), 2);
+    assert!(bricks[0].dashes.is_empty() && bricks[1].dashes == vec![6.0, -6.0]);
+    let (brick_id, _) = manager.create_pattern(&mut doc, &HatchConfig::new(HatchStyle::Brick).with_spacing(6.0));
+    let dict = &doc.get_object(brick_id).unwrap().as_stream().unwrap().dict;
+    assert!((dict.get(b"XStep").unwrap().as_float().unwrap() - 24.0).abs() < 0.01);
+    assert!((dict.get(b"YStep").unwrap().as_float().unwrap() - 12.0).abs() < 0.01);
+    let mut courses = bricks.clone();
+    courses[0] = courses[0].clone().with_width(1.5);
+    let heavy = HatchConfig::new(HatchStyle::Brick).with_line_families(courses);
+    let (heavy_id, heavy_name) = manager.create_pattern(&mut doc, &heavy);
+    assert_eq!(operands(&content_of(&doc, heavy_id), "w"), vec![1.5, 0.5]);
+    manager.add_pattern_to_resources(&mut resources, &heavy_name, heavy_id);
+    let brick_area = HatchBoundary::new(BoundaryLoop::rectangle(0.0, 0.0, 48.0, 12.0));
+    let brick_config = HatchConfig::new(HatchStyle::Brick).with_spacing(6.0);
+    let joints: Vec<f32> = manager
+        .hatch_line_segments(&brick_area, &brick_config)
+        .unwrap()
+        .iter()
+        .filter(|((x1, _), (x2, _))| (x1 - x2).abs() < 1e-3)
+        .map(|((x, _), _)| *x)
+        .collect();
+    assert_eq!(joints.len(), 4);
+    assert!(joints.iter().all(|x| (((x - 6.0) / 12.0).round() * 12.0 + 6.0 - x).abs() < 1e-3));
+    let brick_vector = manager.create_vector_hatch(&brick_area, &brick_config).unwrap();
+    assert!(brick_vector.iter().any(|op| op.operator == "d"));
+
+    // Uncolored patterns leave family colors out
+    let (uncolored_id, _) = manager.create_pattern(&mut doc, &weighted.clone().with_uncolored(true));
+    assert!(!content_of(&doc, uncolored_id).iter().any(|op| op.operator == "RG"));
+
+    let mut builder = PatternedShapeBuilder::new();
+    builder
+        .rectangle(50.0, 500.0, 240.0, 240.0, &plain_name)
+        .rectangle(305.0, 500.0, 240.0, 240.0, &weighted_name)
+        .rectangle(50.0, 50.0, 240.0, 120.0, &heavy_name);
+    let mut operations = builder.build();
+    operations.extend(vector);
+
+    let output_path = save_hatching_page(doc, resources, operations, "line_family_weights.pdf")?;
+    println!("✅ Line family weights test passed");
+    println!("📄 Output: {}", output_path);
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serialized_pattern_descriptions() -> Result<()> {
+    use hipdf::hatching::{PathElement, PatternDescription};
+
+    let mut doc = Document::with_version("1.5");
+    let mut manager = HatchingManager::new();
+    let mut resources = dictionary! { "Pattern" => Dictionary::new() };
+
+    // A data-only brick pattern, with the mortar width as a parameter
+    let bricks = PatternDescription::new()
+        .with_size(24.0, 12.0)
+        .with_param("mortar", 0.8)
+        .with_element(
+            vec![
+                PathElement::LineWidth { width: "mortar".into() },
+                PathElement::MoveTo { x: 0.0.into(), y: 0.0.into() },
+                PathElement::LineTo { x: "width".into(), y: 0.0.into() },
+                PathElement::MoveTo { x: 0.0.into(), y: 6.0.into() },
+                PathElement::LineTo { x: "width".into(), y: 6.0.into() },
+                PathElement::MoveTo { x: 6.0.into(), y: 0.0.into() },
+                PathElement::LineTo { x: 6.0.into(), y: 6.0.into() },
+                PathElement::MoveTo { x: 18.0.into(), y: 6.0.into() },
+                PathElement::LineTo { x: 18.0.into(), y: "height".into() },
+                PathElement::Stroke,
+            ],
+            None,
+        )
+        .with_element(
+            vec![
+                PathElement::FillColor { color: (0.7, 0.3, 0.2) },
+                PathElement::Circle { cx: 0.0.into(), cy: 0.0.into(), r: 1.0.into() },
+                PathElement::Fill,
+            ],
+            Some(Transform { translate: (12.0, 3.0), ..Transform::new() }),
+        );
+    bricks.validate().unwrap();
+    let config = HatchConfig::new(HatchStyle::Described(bricks.clone())).with_color(0.4, 0.2, 0.1);
+
+    // Saved and loaded again, the config draws the very same pattern
+    let json = serde_json::to_string(&config).unwrap();
+    let loaded: HatchConfig = serde_json::from_str(&json).unwrap();
+    let (bricks_id, bricks_name) = manager.create_pattern(&mut doc, &config);
+    assert_eq!(manager.create_pattern(&mut doc, &loaded).0, bricks_id);
+    let stream = doc.get_object(bricks_id).unwrap().as_stream().unwrap();
+    assert_eq!(stream.dict.get(b"XStep").unwrap().as_float().unwrap(), 24.0);
+    let operations = Content::decode(&stream.content).unwrap().operations;
+    assert_eq!(operations[0].operator, "w");
+    assert_eq!(operations[0].operands[0].as_float().unwrap(), 0.8);
+    assert!(operations.iter().any(|op| op.operator == "cm"));
+    manager.add_pattern_to_resources(&mut resources, &bricks_name, bricks_id);
+
+    // A hand-written library mixing presets, line families and descriptions
+    let library: HashMap<String, HatchConfig> = serde_json::from_str(
+        r#"{
+            "grid": { "style": "Cross", "spacing": 8 },
+            "insulation": {
+                "style": { "LineFamilies": [
+                    { "angle": 45, "origin": [0, 0], "offset": [0, 4], "width": 1.5, "cap": "Round" },
+                    { "angle": 135, "origin": [0, 0], "offset": [0, 4], "dashes": [2, -2] }
+                ] },
+                "color": [0.2, 0.2, 0.6]
+            },
+            "terrazzo": {
+                "style": { "Described": {
+                    "size": [10, 10],
+                    "params": { "data": { "r": 1.5 } },
+                    "elements": [
+                        { "path": [ { "op": "circle", "cx": 3, "cy": 4, "r": "r" }, { "op": "fill" } ] },
+                        { "path": [ { "op": "rectangle", "x": 6, "y": 7, "width": 2, "height": "r" }, { "op": "fill" } ] }
+                    ]
+                } }
+            }
+        }"#,
+    )
+    .unwrap();
+
+    let grid = &library["grid"];
+    assert_eq!(grid.spacing, 8.0);
+    assert_eq!(grid.line_width, 0.5);
+    let (insulation_id, insulation_name) = manager.create_pattern(&mut doc, &library["insulation"]);
+    let insulation = Content::decode(&doc.get_object(insulation_id).unwrap().as_stream().unwrap().content).unwrap();
+    assert!(insulation
+        .operations
+        .iter()
+        .any(|op| op.operator == "w" && op.operands[0].as_float().unwrap() == 1.5));
+    manager.add_pattern_to_resources(&mut resources, &insulation_name, insulation_id);
+    let (terrazzo_id, terrazzo_name) = manager.create_pattern(&mut doc, &library["terrazzo"]);
+    manager.add_pattern_to_resources(&mut resources, &terrazzo_name, terrazzo_id);
+
+    // Function-based patterns and unknown parameters are reported
+    let custom = HatchConfig::new(HatchStyle::Custom(CustomPattern::Simple(Arc::new(|_, _| Vec::new()))));
+    assert!(serde_json::to_string(&custom).is_err());
+    let broken = PatternDescription::new().with_element(
+        vec![PathElement::Circle { cx: 0.0.into(), cy: 0.0.into(), r: "radius".into() }, PathElement::Fill],
+        None,
+    );
+    assert!(broken.validate().is_err());
+    let edited = r#"{ "style": { "Described": {
+        "elements": [ { "path": [ { "op": "circle", "cx": 0, "cy": 0, "r": "radius" } ] } ]
+    } } }"#;
+    let error = serde_json::from_str::<HatchConfig>(edited).unwrap_err().to_string();
+    assert!(error.contains("radius"), "{}", error);
+    let written = manager.cache().len();
+    assert!(manager.try_create_pattern(&mut doc, &HatchConfig::new(HatchStyle::Described(broken))).is_err());
+    assert_eq!(manager.cache().len(), written, "A broken description must not be cached");
+
+    let mut builder = PatternedShapeBuilder::new();
+    builder
+        .rectangle(50.0, 500.0, 240.0, 240.0, &bricks_name)
+        .rectangle(305.0, 500.0, 240.0, 240.0, &insulation_name)
+        .rectangle(50.0, 200.0, 240.0, 240.0, &terrazzo_name);
+
+    let output_path = save_hatching_page(doc, resources, builder.build(), "serialized_patterns.pdf")?;
+    println!("✅ Serialized pattern test passed");
+    println!("📄 Output: {}", output_path);
+    Ok(())
+}
@@ -0,0 +1,238 @@
+//! Imposition Integration Tests
+//!
+//! These tests validate N-up and booklet imposition with printer marks
+
+use hipdf::embed_pdf::{GridFillOrder, PageBox, PdfEmbedder};
+use hipdf::imposition::{impose, ImpositionOptions};
+use hipdf::lopdf::{content::Content, dictionary, Document, Object, ObjectId, Stream};
+
+use std::fs;
+use std::path::Path;
+
+/// Directory for test outputs
+const TEST_OUTPUT_DIR: &str = "tests/outputs/imposition_integration_test";
+
+fn ensure_output_dir() {
+    if !Path::new(TEST_OUTPUT_DIR).exists() {
+        fs::create_dir_all(TEST_OUTPUT_DIR).expect("Failed to create test output directory");
+    }
+}
+
+/// Builds an A5 source PDF whose pages are labelled "Page 1", "Page 2", ...
+fn create_numbered_source(page_count: usize) -> Vec<u8> {
+    let mut source = Document::with_version("1.5");
+    let pages_id = source.new_object_id();
+    let font_id = source.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+
+    let kids: Vec<Object> = (1..=page_count)
+        .map(|number| {
+            let content = Content {
+                operations: vec![
+                    lopdf::content::Operation::new("re", vec![10.into(), 10.into(), 400.into(), 575.into()]),
+                    lopdf::content::Operation::new("S", vec![]),
+                    lopdf::content::Operation::new("BT", vec![]),
+                    lopdf::content::Operation::new("Tf", vec![Object::Name(b"F1".to_vec()), 48.into()]),
+                    lopdf::content::Operation::new("Td", vec![120.into(), 280.into()]),
+                    lopdf::content::Operation::new(
+                        "Tj",
+                        vec![Object::string_literal(format!("Page {}", number))],
+                    ),
+                    lopdf::content::Operation::new("ET", vec![]),
+                ],
+            };
+            let content_id =
+                source.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+            source
+                .add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "Contents" => content_id,
+                })
+                .into()
+        })
+        .collect();
+
+    source.set_object(
+        pages_id,
+        dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => page_count as i64,
+            "MediaBox" => vec![0.into(), 0.into(), 420.into(), 595.into()],
+            "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+        },
+    );
+    let catalog_id = source.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    source.trailer.set("Root", catalog_id);
+
+    let mut bytes = Vec::new();
+    source.save_to(&mut bytes).unwrap();
+    bytes
+}
+
+/// Source page labels drawn on a sheet, with the x offset of each placement, left to right
+fn placed_pages(doc: &Document, sheet_id: ObjectId) -> Vec<(String, f32)> {
+    let sheet = doc.get_dictionary(sheet_id).unwrap();
+    let xobjects = sheet
+        .get(b"Resources")
+        .and_then(Object::as_dict)
+        .and_then(|resources| resources.get(b"XObject"))
+        .and_then(Object::as_dict)
+        .unwrap();
+    let content_id = sheet.get(b"Contents").and_then(Object::as_reference).unwrap();
+    let content = Content::decode(&doc.get_object(content_id).unwrap().as_stream().unwrap().content).unwrap();
+
+    let mut placed = Vec::new();
+    let mut x = 0.0;
+    for op in &content.operations {
+        match op.operator.as_str() {
+            "cm" => x = op.operands[4].as_float().unwrap(),
+            "Do" => {
+                let name = op.operands[0].as_name().unwrap();
+                let xobject_id = xobjects.get(name).and_then(Object::as_reference).unwrap();
+                let stream = doc.get_object(xobject_id).unwrap().as_stream().unwrap();
+                let label = Content::decode(&stream.content)
+                    .unwrap()
+                    .operations
+                    .iter()
+                    .find(|op| op.operator == "Tj")
+                    .map(|op| String::from_utf8_lossy(op.operands[0].as_str().unwrap()).to_string())
+                    .unwrap();
+                placed.push((label, x));
+            }
+            _ => {}
+        }
+    }
+    placed.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    placed
+}
+
+fn labels(placed: &[(String, f32)]) -> Vec<&str> {
+    placed.iter().map(|(label, _)| label.as_str()).collect()
+}
+
+#[test]
+fn test_four_up_imposition() {
+    ensure_output_dir();
+
+    let mut embedder = PdfEmbedder::new();
+    let source = embedder
+        .load_pdf_from_bytes(&create_numbered_source(10), "numbered")
+        .unwrap();
+
+    let mut doc = Document::with_version("1.5");
+    let options = ImpositionOptions::n_up(2, 2)
+        .with_sheet_size(842.0, 1191.0) // A3 portrait
+        .with_gutter(18.0, 18.0)
+        .with_crop_marks(true)
+        .with_registration_marks(true);
+    let sheets = impose(&mut embedder, &mut doc, &source, &options).unwrap();
+
+    // Ten pages on four-up sheets need three sheets, the last one half empty
+    assert_eq!(sheets.len(), 3);
+    assert_eq!(doc.get_pages().len(), 3);
+    assert_eq!(placed_pages(&doc, sheets[2]).len(), 2);
+
+    // Row-first fill: the left column holds pages 1 and 3
+    let first_sheet = placed_pages(&doc, sheets[0]);
+    assert_eq!(labels(&first_sheet[..2]), vec!["Page 1", "Page 3"]);
+
+    // Marks are drawn in the registration colour
+    let resources = doc
+        .get_dictionary(sheets[0])
+        .unwrap()
+        .get(b"Resources")
+        .and_then(Object::as_dict)
+        .unwrap();
+    assert!(resources.get(b"ColorSpace").is_ok());
+
+    let path = format!("{}/four_up.pdf", TEST_OUTPUT_DIR);
+    doc.save(&path).unwrap();
+
+    println!("✅ Four-up imposition test passed");
+    println!("📄 Output: {}", path);
+}
+
+#[test]
+fn test_column_first_imposition() {
+    let mut embedder = PdfEmbedder::new();
+    let source = embedder
+        .load_pdf_from_bytes(&create_numbered_source(4), "numbered")
+        .unwrap();
+
+    let mut doc = Document::with_version("1.5");
+    let options = ImpositionOptions::n_up(2, 2)
+        .with_sheet_size(842.0, 1191.0)
+        .with_fill_order(GridFillOrder::ColumnFirst);
+    let sheets = impose(&mut embedder, &mut doc, &source, &options).unwrap();
+
+    // Column-first fill: the left column holds pages 1 and 2
+    let placed = placed_pages(&doc, sheets[0]);
+    let mut left_column = labels(&placed[..2]);
+    left_column.sort();
+    assert_eq!(left_column, vec!["Page 1", "Page 2"]);
+}
+
+#[test]
+fn test_booklet_imposition() {
+    ensure_output_dir();
+
+    let mut embedder = PdfEmbedder::new();
+    let source = embedder
+        .load_pdf_from_bytes(&create_numbered_source(10), "numbered")
+        .unwrap();
+
+    let mut doc = Document::with_version("1.5");
+    let options = ImpositionOptions::booklet()
+        .with_page_box(PageBox::MediaBox)
+        .with_creep(2.0)
+        .with_crop_marks(true);
+    let sheets = impose(&mut embedder, &mut doc, &source, &options).unwrap();
+
+    // Ten pages pad to twelve: three sheets, printed on both sides
+    assert_eq!(sheets.len(), 6);
+
+    let sides: Vec<Vec<(String, f32)>> = sheets.iter().map(|&id| placed_pages(&doc, id)).collect();
+    assert_eq!(labels(&sides[0]), vec!["Page 1"]);
+    assert_eq!(labels(&sides[1]), vec!["Page 2"]);
+    assert_eq!(labels(&sides[2]), vec!["Page 10", "Page 3"]);
+    assert_eq!(labels(&sides[3]), vec!["Page 4", "Page 9"]);
+    assert_eq!(labels(&sides[4]), vec!["Page 8", "Page 5"]);
+    assert_eq!(labels(&sides[5]), vec!["Page 6", "Page 7"]);
+
+    // Creep moves pages on inner sheets toward the spine
+    let outer_right = sides[0][0].1;
+    let inner_right = sides[4][1].1;
+    assert!((outer_right - inner_right - 4.0).abs() < 0.01);
+    let middle_left = sides[2][0].1;
+    let inner_left = sides[4][0].1;
+    assert!((inner_left - middle_left - 2.0).abs() < 0.01);
+
+    let path = format!("{}/booklet.pdf", TEST_OUTPUT_DIR);
+    doc.save(&path).unwrap();
+
+    println!("✅ Booklet imposition test passed");
+    println!("📄 Output: {}", path);
+}
+
+#[test]
+fn test_imposition_rejects_empty_cells() {
+    let mut embedder = PdfEmbedder::new();
+    let source = embedder
+        .load_pdf_from_bytes(&create_numbered_source(2), "numbered")
+        .unwrap();
+
+    let mut doc = Document::with_version("1.5");
+    let too_small = ImpositionOptions::n_up(2, 1).with_sheet_size(60.0, 60.0);
+    assert!(impose(&mut embedder, &mut doc, &source, &too_small).is_err());
+
+    let no_columns = ImpositionOptions::n_up(0, 1);
+    assert!(impose(&mut embedder, &mut doc, &source, &no_columns).is_err());
+}
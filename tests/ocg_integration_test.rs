@@ -10,8 +10,16 @@
 //! - Full PDF generation with OCG support
 //! - Performance and edge cases
 
-use hipdf::ocg::{Layer, LayerContentBuilder, LayerOperations as Ops, OCGConfig, OCGManager};
-use hipdf::lopdf::{content::Content, dictionary, Document, Object, Stream};
+use hipdf::ocg::{
+    Layer, LayerConfiguration, LayerContentBuilder, LayerGroup, LayerMembership,
+    LayerOperations as Ops, LayerUsage, OCGConfig, OCGManager, OrderItem, UserKind,
+    VisibilityExpression as VE, VisibilityPolicy,
+};
+use hipdf::lopdf::{
+    content::{Content, Operation},
+    dictionary, Document, Object, Stream,
+};
+use hipdf::pages;
 
 use std::fs;
 use std::path::Path;
@@ -72,11 +80,62 @@ fn test_layer_content_builder() {
         .add_operation(Ops::rectangle(0.0, 0.0, 100.0, 100.0))
         .end_layer();
 
-    let operations = builder.build();
+    let operations = builder.build().unwrap();
     assert!(!operations.is_empty());
     assert_eq!(operations.len(), 3); // BDC, Operation, EMC
 }
 
+#[test]
+fn test_nested_layer_content() {
+    let mut builder = LayerContentBuilder::new();
+    builder
+        .with_layer("L0", |b| {
+            b.add_operation(Ops::rectangle(0.0, 0.0, 100.0, 100.0))
+                .with_layer("L1", |b| {
+                    assert_eq!(b.current_layer(), Some("L1"));
+                    assert_eq!(b.depth(), 2);
+                    b.add_operation(Ops::fill());
+                });
+            assert_eq!(b.current_layer(), Some("L0"));
+        })
+        .with_marked_content("Artifact", |b| {
+            b.add_operation(Ops::stroke());
+        })
+        .begin_marked_content_with_properties("Span", dictionary! { "ActualText" => Object::string_literal("A") })
+        .begin_layer("L2")
+        .add_operation(Ops::show_text("A"))
+        .end_layer()
+        .end_marked_content();
+    assert_eq!(builder.depth(), 0);
+
+    let operators: Vec<String> = builder
+        .build()
+        .unwrap()
+        .iter()
+        .map(|op| op.operator.clone())
+        .collect();
+    assert_eq!(
+        operators,
+        vec!["BDC", "re", "BDC", "f", "EMC", "EMC", "BMC", "S", "EMC", "BDC", "BDC", "Tj", "EMC", "EMC"]
+    );
+
+    // Sections left open, closed twice or closed with the wrong call are reported
+    let mut open = LayerContentBuilder::new();
+    open.begin_layer("L0").begin_layer("L1").end_layer();
+    let error = open.build().unwrap_err().to_string();
+    assert!(error.contains("/L0"), "{}", error);
+
+    let mut closed_twice = LayerContentBuilder::new();
+    closed_twice.begin_layer("L0").end_layer().end_layer();
+    assert!(closed_twice.build().is_err());
+
+    let mut mismatched = LayerContentBuilder::new();
+    mismatched.begin_marked_content("Artifact").end_layer();
+    assert!(mismatched.build().is_err());
+
+    println!("✅ Nested layer content test passed");
+}
+
 #[test]
 fn test_layer_operations() {
     // Test individual layer operations
@@ -103,9 +162,6 @@ fn test_layer_operations() {
         format!("{:?}", text_op),
         format!("{:?}", Ops::show_text("Test Text"))
     );
-
-    // Operations created successfully
-    assert!(true);
 }
 
 #[test]
@@ -208,7 +264,7 @@ fn test_complex_content_building() {
         .add_operation(Ops::stroke())
         .end_layer();
 
-    let operations = builder.build();
+    let operations = builder.build().unwrap();
     assert!(!operations.is_empty());
 
     // Verify operations structure
@@ -275,11 +331,11 @@ fn test_layer_tags_and_resources() {
     // Verify tags
     assert_eq!(layer_tags.len(), 2);
     assert_eq!(
-        layer_tags.get(&"Layer A".to_string()),
+        layer_tags.get("Layer A"),
         Some(&"L0".to_string())
     );
     assert_eq!(
-        layer_tags.get(&"Layer B".to_string()),
+        layer_tags.get("Layer B"),
         Some(&"L1".to_string())
     );
 
@@ -347,7 +403,7 @@ fn test_ocg_integration() {
     let mut builder = LayerContentBuilder::new();
 
     // Background layer - a light blue background
-    if let Some(bg_tag) = layer_tags.get(&"Background".to_string()) {
+    if let Some(bg_tag) = layer_tags.get("Background") {
         builder
             .begin_layer(bg_tag)
             .add_operation(Ops::set_fill_color_rgb(0.9, 0.95, 1.0))
@@ -357,7 +413,7 @@ fn test_ocg_integration() {
     }
 
     // Main content layer
-    if let Some(content_tag) = layer_tags.get(&"Main Content".to_string()) {
+    if let Some(content_tag) = layer_tags.get("Main Content") {
         builder
             .begin_layer(content_tag)
             // Title
@@ -383,7 +439,7 @@ fn test_ocg_integration() {
     }
 
     // Annotations layer - some red annotations
-    if let Some(anno_tag) = layer_tags.get(&"Annotations".to_string()) {
+    if let Some(anno_tag) = layer_tags.get("Annotations") {
         builder
             .begin_layer(anno_tag)
             // Red circle (approximated with rectangle for simplicity)
@@ -401,7 +457,7 @@ fn test_ocg_integration() {
     }
 
     // Watermark layer
-    if let Some(watermark_tag) = layer_tags.get(&"Watermark".to_string()) {
+    if let Some(watermark_tag) = layer_tags.get("Watermark") {
         builder
             .begin_layer(watermark_tag)
             .add_operation(Ops::begin_text())
@@ -414,7 +470,7 @@ fn test_ocg_integration() {
     }
 
     // Debug info layer
-    if let Some(debug_tag) = layer_tags.get(&"Debug Info".to_string()) {
+    if let Some(debug_tag) = layer_tags.get("Debug Info") {
         builder
             .begin_layer(debug_tag)
             .add_operation(Ops::begin_text())
@@ -442,7 +498,7 @@ fn test_ocg_integration() {
         .add_operation(Ops::end_text());
 
     // 6. Create the content stream from the builder
-    let operations = builder.build();
+    let operations = builder.build().unwrap();
     let content = Content { operations };
     let content_stream = Stream::new(dictionary! {}, content.encode().unwrap());
     let content_id = doc.add_object(content_stream);
@@ -493,6 +549,1233 @@ fn test_ocg_integration() {
     );
 }
 
+/// Builds and reloads a one-page document with the given layers
+fn create_layered_document(layers: Vec<Layer>) -> Document {
+    let mut doc = Document::with_version("1.5");
+    let mut manager = OCGManager::new();
+    for layer in layers {
+        manager.add_layer(layer);
+    }
+    manager.initialize(&mut doc);
+
+    let mut resources = dictionary! {};
+    let tags = manager.setup_page_resources(&mut resources);
+    let mut builder = LayerContentBuilder::new();
+    for tag in tags.values() {
+        builder
+            .begin_layer(tag)
+            .add_operation(Ops::rectangle(100.0, 100.0, 200.0, 200.0))
+            .add_operation(Ops::fill())
+            .end_layer();
+    }
+    let content = Content { operations: builder.build().unwrap() };
+    let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+
+    let pages_id = doc.new_object_id();
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        "Contents" => content_id,
+        "Resources" => resources,
+    });
+    doc.set_object(
+        pages_id,
+        dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+        },
+    );
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+    manager.update_catalog(&mut doc);
+
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).unwrap();
+    Document::load_from(bytes.as_slice()).unwrap()
+}
+
+#[test]
+fn test_read_layers_from_document() {
+    let doc = create_layered_document(vec![
+        Layer::new("Walls", true),
+        Layer::new("Furniture", false),
+        Layer::new("Dimensions", true).with_intent(vec!["Design".to_string()]),
+    ]);
+
+    let manager = OCGManager::from_document(&doc).unwrap();
+    assert!(manager.has_oc_properties());
+    assert_eq!(manager.len(), 3);
+
+    let names: Vec<&str> = manager.layers().iter().map(|layer| layer.name.as_str()).collect();
+    assert_eq!(names, vec!["Walls", "Furniture", "Dimensions"]);
+    assert!(manager.get_layer("Walls").unwrap().default_visible);
+    assert!(!manager.get_layer("Furniture").unwrap().default_visible);
+    assert_eq!(manager.get_layer("Dimensions").unwrap().intent, vec!["Design"]);
+    assert_eq!(manager.config.intent, vec!["View"]);
+
+    let order: Vec<OrderItem> = manager
+        .layers()
+        .iter()
+        .map(|layer| OrderItem::Layer(layer.id))
+        .collect();
+    assert_eq!(manager.order(), Some(order.as_slice()));
+
+    // Documents without layers give an empty manager
+    let mut plain = Document::with_version("1.5");
+    let catalog_id = plain.add_object(dictionary! { "Type" => "Catalog" });
+    plain.trailer.set("Root", catalog_id);
+    assert!(OCGManager::from_document(&plain).unwrap().is_empty());
+}
+
+#[test]
+fn test_read_nested_order_and_unicode_names() {
+    let mut doc = Document::with_version("1.5");
+    let utf16_name: Vec<u8> = [0xFE, 0xFF]
+        .into_iter()
+        .chain("Étage".encode_utf16().flat_map(|unit| unit.to_be_bytes()))
+        .collect();
+    let floor = doc.add_object(dictionary! {
+        "Type" => "OCG",
+        "Name" => Object::String(utf16_name, hipdf::lopdf::StringFormat::Hexadecimal),
+    });
+    let rooms = doc.add_object(dictionary! {
+        "Type" => "OCG",
+        "Name" => Object::string_literal("Rooms"),
+    });
+    let doors = doc.add_object(dictionary! {
+        "Type" => "OCG",
+        "Name" => Object::string_literal("Doors"),
+    });
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "OCProperties" => dictionary! {
+            "OCGs" => vec![floor.into(), rooms.into(), doors.into()],
+            "D" => dictionary! {
+                "BaseState" => "OFF",
+                "ON" => vec![rooms.into()],
+                "Order" => vec![
+                    floor.into(),
+                    vec![rooms.into()].into(),
+                    vec![Object::string_literal("Openings"), doors.into()].into(),
+                ],
+            },
+        },
+    });
+    doc.trailer.set("Root", catalog_id);
+
+    let manager = OCGManager::from_document(&doc).unwrap();
+    assert!(manager.get_layer("Étage").is_some());
+    assert!(!manager.get_layer("Étage").unwrap().default_visible);
+    assert!(manager.get_layer("Rooms").unwrap().default_visible);
+    assert_eq!(manager.config.base_state, "OFF");
+    assert_eq!(
+        manager.order().unwrap(),
+        &[
+            OrderItem::Layer(floor),
+            OrderItem::Group {
+                label: None,
+                items: vec![OrderItem::Layer(rooms)],
+            },
+            OrderItem::Group {
+                label: Some("Openings".to_string()),
+                items: vec![OrderItem::Layer(doors)],
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_lenient_loading_and_reparenting() {
+    let mut doc = Document::with_version("1.5");
+    let floor = doc.add_object(dictionary! { "Type" => "OCG", "Name" => Object::string_literal("Floor") });
+    let rooms = doc.add_object(dictionary! { "Type" => "OCG", "Name" => Object::string_literal("Rooms") });
+    let doors = doc.add_object(dictionary! { "Type" => "OCG", "Name" => Object::string_literal("Doors") });
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "OCProperties" => dictionary! {
+            "OCGs" => vec![floor.into(), Object::Reference((999, 0)), 5.into(), rooms.into(), doors.into()],
+            "D" => dictionary! {
+                "Order" => vec![
+                    floor.into(),
+                    vec![rooms.into()].into(),
+                    vec![Object::string_literal("Openings"), doors.into()].into(),
+                ],
+            },
+        },
+    });
+    doc.trailer.set("Root", catalog_id);
+
+    // Stale and malformed OCG entries are skipped instead of failing the load
+    let mut manager = OCGManager::from_document(&doc).unwrap();
+    let names: Vec<&str> = manager.layers().iter().map(|layer| layer.name.as_str()).collect();
+    assert_eq!(names, vec!["Floor", "Rooms", "Doors"]);
+    assert_eq!(manager.get_layer("Rooms").unwrap().parent.as_deref(), Some("Floor"));
+
+    // Parents changed after loading move the layers in the written order
+    manager.get_layer_mut("Rooms").unwrap().parent = Some("Openings".to_string());
+    manager.get_layer_mut("Doors").unwrap().parent = Some("Floor".to_string());
+    manager.write_to_document(&mut doc);
+    assert_eq!(
+        manager.order().unwrap(),
+        &[
+            OrderItem::Layer(floor),
+            OrderItem::Group {
+                label: None,
+                items: vec![OrderItem::Layer(doors)],
+            },
+            OrderItem::Group {
+                label: Some("Openings".to_string()),
+                items: vec![OrderItem::Layer(rooms)],
+            },
+        ]
+    );
+    let reloaded = OCGManager::from_document(&doc).unwrap();
+    assert_eq!(reloaded.get_layer("Rooms").unwrap().parent.as_deref(), Some("Openings"));
+    assert_eq!(reloaded.get_layer("Doors").unwrap().parent.as_deref(), Some("Floor"));
+
+    // A missing /OCGs list reads as no layers
+    let mut bare = Document::with_version("1.5");
+    let catalog_id = bare.add_object(dictionary! { "Type" => "Catalog", "OCProperties" => dictionary! {} });
+    bare.trailer.set("Root", catalog_id);
+    assert!(OCGManager::from_document(&bare).unwrap().is_empty());
+}
+
+#[test]
+fn test_edit_layers_and_write_back() {
+    ensure_output_dir();
+
+    let mut doc = create_layered_document(vec![
+        Layer::new("Walls", true),
+        Layer::new("Furniture", false),
+        Layer::new("Scratch", true),
+    ]);
+
+    let mut manager = OCGManager::from_document(&doc).unwrap();
+    let scratch_id = manager.get_layer("Scratch").unwrap().id;
+
+    assert!(manager.rename_layer("Walls", "Structure"));
+    assert!(!manager.rename_layer("Furniture", "Structure"));
+    assert!(!manager.rename_layer("Missing", "Anything"));
+    assert_eq!(manager.toggle_layer("Furniture"), Some(true));
+    assert!(manager.set_layer_visibility("Structure", false));
+    assert!(manager.remove_layer("Scratch").is_some());
+    manager.add_layer(Layer::new("Annotations", true));
+    manager.write_to_document(&mut doc);
+
+    // The removed OCG and the page's reference to it are gone
+    assert!(!doc.has_object(scratch_id));
+    let page_id = *doc.get_pages().values().next().unwrap();
+    let properties = doc
+        .get_dictionary(page_id)
+        .unwrap()
+        .get(b"Resources")
+        .and_then(Object::as_dict)
+        .and_then(|resources| resources.get(b"Properties"))
+        .and_then(Object::as_dict)
+        .unwrap();
+    assert_eq!(properties.len(), 2);
+
+    let output_path = format!("{}/ocg_edit_test.pdf", TEST_OUTPUT_DIR);
+    doc.save(&output_path).unwrap();
+
+    let reloaded = OCGManager::from_document(&Document::load(&output_path).unwrap()).unwrap();
+    let names: Vec<&str> = reloaded.layers().iter().map(|layer| layer.name.as_str()).collect();
+    assert_eq!(names, vec!["Structure", "Furniture", "Annotations"]);
+    assert!(!reloaded.get_layer("Structure").unwrap().default_visible);
+    assert!(reloaded.get_layer("Furniture").unwrap().default_visible);
+    assert_eq!(reloaded.order().unwrap().len(), 3);
+
+    println!("✅ Layer editing test passed");
+    println!("📄 PDF created: {}", output_path);
+}
+
+#[test]
+fn test_duplicate_names_and_removed_layer_content() {
+    let mut doc = Document::with_version("1.5");
+    let first = doc.add_object(dictionary! {
+        "Type" => "OCG",
+        "Name" => Object::string_literal("Dup"),
+    });
+    let second = doc.add_object(dictionary! {
+        "Type" => "OCG",
+        "Name" => Object::string_literal("Dup"),
+    });
+    // Properties shared through an indirect dictionary
+    let properties_id = doc.add_object(dictionary! { "A" => first, "B" => second });
+    let form_id = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+            "OC" => first,
+        },
+        Content { operations: vec![Ops::rectangle(0.0, 0.0, 10.0, 10.0), Ops::fill()] }
+            .encode()
+            .unwrap(),
+    ));
+    let mut builder = LayerContentBuilder::new();
+    builder
+        .begin_layer("A")
+        .add_operation(Ops::rectangle(100.0, 100.0, 50.0, 50.0))
+        .add_operation(Ops::fill())
+        .end_layer()
+        .begin_layer("B")
+        .add_operation(Ops::rectangle(200.0, 100.0, 50.0, 50.0))
+        .add_operation(Ops::fill())
+        .end_layer()
+        .add_operation(Operation::new("Do", vec![Object::Name(b"Fm0".to_vec())]));
+    let content = Content { operations: builder.build().unwrap() };
+    let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+
+    let pages_id = doc.new_object_id();
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        "Contents" => content_id,
+        "Resources" => dictionary! {
+            "Properties" => properties_id,
+            "XObject" => dictionary! { "Fm0" => form_id },
+        },
+    });
+    doc.set_object(
+        pages_id,
+        dictionary! { "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1 },
+    );
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+        "OCProperties" => dictionary! {
+            "OCGs" => vec![first.into(), second.into()],
+            "D" => dictionary! { "Order" => vec![first.into(), second.into()] },
+        },
+    });
+    doc.trailer.set("Root", catalog_id);
+
+    // Both layers are kept; names only pick the first of them
+    let mut manager = OCGManager::from_document(&doc).unwrap();
+    assert_eq!(manager.len(), 2);
+    assert_eq!(manager.get_layer("Dup").unwrap().id, first);
+    assert_eq!(manager.get_layer_by_id(second).unwrap().name, "Dup");
+
+    let mut fresh_resources = dictionary! {};
+    manager.setup_page_resources(&mut fresh_resources);
+    assert_ne!(manager.layers()[0].tag, manager.layers()[1].tag);
+
+    assert!(manager.remove_layer_by_id(first).is_some());
+    manager.write_to_document(&mut doc);
+    assert!(!doc.has_object(first));
+
+    // The indirect Properties entry is dropped and the content it marked stays, unlayered
+    let properties = doc.get_dictionary(properties_id).unwrap();
+    assert!(!properties.has(b"A"));
+    assert!(properties.has(b"B"));
+
+    let operations = Content::decode(&doc.get_page_content(page_id).unwrap()).unwrap().operations;
+    let markers: Vec<&Operation> = operations.iter().filter(|op| op.operator == "BDC").collect();
+    assert_eq!(markers.len(), 1);
+    assert_eq!(markers[0].operands[1], Object::Name(b"B".to_vec()));
+    assert_eq!(operations.iter().filter(|op| op.operator == "EMC").count(), 1);
+    assert_eq!(operations.iter().filter(|op| op.operator == "f").count(), 2);
+
+    let form = doc.get_object(form_id).unwrap().as_stream().unwrap();
+    assert!(form.dict.get(b"OC").is_err());
+
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).unwrap();
+    let reloaded = OCGManager::from_document(&Document::load_from(bytes.as_slice()).unwrap()).unwrap();
+    assert_eq!(reloaded.len(), 1);
+    assert_eq!(reloaded.layers()[0].name, "Dup");
+}
+
+#[test]
+fn test_layer_tree_radio_groups_and_locking() {
+    ensure_output_dir();
+
+    let mut doc = Document::with_version("1.5");
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog" });
+    doc.trailer.set("Root", catalog_id);
+
+    let mut manager = OCGManager::new();
+    manager.add_group(LayerGroup::new("Discipline"));
+    manager.add_layer(Layer::new("Electrical", true).with_parent("Discipline"));
+    manager.add_layer(Layer::new("Lighting", true).with_parent("Electrical"));
+    manager.add_layer(Layer::new("Power", true).with_parent("Electrical"));
+    manager.add_layer(Layer::new("Plumbing", true).with_parent("Discipline"));
+    manager.add_layer(Layer::new("Title Block A", true).with_locked(true));
+    manager.add_layer(Layer::new("Title Block B", true));
+    manager.add_radio_group(&["Title Block A", "Title Block B"]);
+
+    // Only the first visible member of a radio group stays on
+    assert!(!manager.get_layer("Title Block B").unwrap().default_visible);
+
+    manager.initialize(&mut doc);
+    manager.update_catalog(&mut doc);
+
+    let id = |name: &str| manager.get_layer(name).unwrap().id;
+    let oc_properties = doc
+        .get_dictionary(doc.catalog().unwrap().get(b"OCProperties").unwrap().as_reference().unwrap())
+        .unwrap();
+    let default_config = oc_properties.get(b"D").and_then(Object::as_dict).unwrap();
+    assert_eq!(
+        default_config.get(b"Order").unwrap(),
+        &Object::Array(vec![
+            Object::Array(vec![
+                Object::string_literal("Discipline"),
+                id("Electrical").into(),
+                Object::Array(vec![id("Lighting").into(), id("Power").into()]),
+                id("Plumbing").into(),
+            ]),
+            id("Title Block A").into(),
+            id("Title Block B").into(),
+        ])
+    );
+    assert_eq!(
+        default_config.get(b"RBGroups").unwrap(),
+        &Object::Array(vec![Object::Array(vec![
+            id("Title Block A").into(),
+            id("Title Block B").into(),
+        ])])
+    );
+    assert_eq!(
+        default_config.get(b"Locked").unwrap(),
+        &Object::Array(vec![id("Title Block A").into()])
+    );
+
+    let output_path = format!("{}/ocg_tree_test.pdf", TEST_OUTPUT_DIR);
+    doc.save(&output_path).unwrap();
+
+    // The tree, radio groups and locks survive a round trip
+    let mut reloaded = OCGManager::from_document(&Document::load(&output_path).unwrap()).unwrap();
+    assert_eq!(reloaded.get_layer("Lighting").unwrap().parent.as_deref(), Some("Electrical"));
+    assert_eq!(reloaded.get_layer("Electrical").unwrap().parent.as_deref(), Some("Discipline"));
+    assert_eq!(reloaded.groups().len(), 1);
+    assert!(reloaded.get_layer("Title Block A").unwrap().locked);
+    assert_eq!(reloaded.radio_groups(), &[vec!["Title Block A".to_string(), "Title Block B".to_string()]]);
+
+    // Showing one title block hides the other
+    assert_eq!(reloaded.toggle_layer("Title Block B"), Some(true));
+    assert!(!reloaded.get_layer("Title Block A").unwrap().default_visible);
+
+    // Removing a parent layer moves its children up
+    reloaded.remove_layer("Electrical");
+    assert_eq!(reloaded.get_layer("Lighting").unwrap().parent.as_deref(), Some("Discipline"));
+
+    println!("✅ Layer tree test passed");
+    println!("📄 PDF created: {}", output_path);
+}
+
+#[test]
+fn test_layer_memberships() {
+    ensure_output_dir();
+
+    let mut doc = Document::with_version("1.6");
+    let mut manager = OCGManager::new();
+    manager.add_layer(Layer::new("Dimensions", true));
+    manager.add_layer(Layer::new("Floor 1", true));
+    manager.add_layer(Layer::new("Floor 2", true));
+
+    // Memberships need initialized layers
+    let early = LayerMembership::new(VisibilityPolicy::AllOn, &["Dimensions"]);
+    assert!(manager.add_membership(&mut doc, &early).is_err());
+    manager.initialize(&mut doc);
+
+    let both = manager
+        .add_membership(
+            &mut doc,
+            &LayerMembership::expression(VE::layer("Dimensions") & VE::layer("Floor 2")),
+        )
+        .unwrap();
+    let neither = manager
+        .add_membership(
+            &mut doc,
+            &LayerMembership::new(VisibilityPolicy::AllOff, &["Floor 1", "Floor 2"]),
+        )
+        .unwrap();
+    let not_first = manager
+        .add_membership(
+            &mut doc,
+            &LayerMembership::expression(!(VE::layer("Floor 1") | VE::layer("Floor 2")) | VE::layer("Dimensions")),
+        )
+        .unwrap();
+    assert_eq!((both.as_str(), neither.as_str(), not_first.as_str()), ("M0", "M1", "M2"));
+
+    let id = |name: &str| Object::Reference(manager.get_layer(name).unwrap().id);
+    let ocmd = doc.get_dictionary(manager.get_membership(&both).unwrap()).unwrap();
+    assert_eq!(ocmd.get(b"Type").unwrap(), &Object::Name(b"OCMD".to_vec()));
+    assert_eq!(
+        ocmd.get(b"VE").unwrap(),
+        &Object::Array(vec![Object::Name(b"And".to_vec()), id("Dimensions"), id("Floor 2")])
+    );
+    assert_eq!(
+        ocmd.get(b"OCGs").unwrap(),
+        &Object::Array(vec![id("Dimensions"), id("Floor 2")])
+    );
+
+    let ocmd = doc.get_dictionary(manager.get_membership(&neither).unwrap()).unwrap();
+    assert_eq!(ocmd.get(b"P").unwrap(), &Object::Name(b"AllOff".to_vec()));
+
+    let ocmd = doc.get_dictionary(manager.get_membership(&not_first).unwrap()).unwrap();
+    assert_eq!(
+        ocmd.get(b"VE").unwrap(),
+        &Object::Array(vec![
+            Object::Name(b"Or".to_vec()),
+            Object::Array(vec![
+                Object::Name(b"Not".to_vec()),
+                Object::Array(vec![Object::Name(b"Or".to_vec()), id("Floor 1"), id("Floor 2")]),
+            ]),
+            id("Dimensions"),
+        ])
+    );
+
+    // Invalid expressions are rejected
+    assert!(manager
+        .add_membership(&mut doc, &LayerMembership::expression(VE::layer("Missing")))
+        .is_err());
+    assert!(manager
+        .add_membership(&mut doc, &LayerMembership::expression(VE::all(vec![])))
+        .is_err());
+
+    // Membership tags are registered with the page and usable like layer tags
+    let mut resources = dictionary! {};
+    manager.setup_page_resources(&mut resources);
+    let properties = resources.get(b"Properties").and_then(Object::as_dict).unwrap();
+    assert!(properties.has(b"M0") && properties.has(b"M1") && properties.has(b"M2"));
+
+    let mut builder = LayerContentBuilder::new();
+    builder
+        .begin_layer(&both)
+        .add_operation(Ops::rectangle(50.0, 50.0, 100.0, 20.0))
+        .add_operation(Ops::fill())
+        .end_layer();
+    let operations = builder.build().unwrap();
+    assert_eq!(operations[0].operands[1], Object::Name(b"M0".to_vec()));
+
+    let pages_id = doc.new_object_id();
+    let content_id = doc.add_object(Stream::new(
+        dictionary! {},
+        Content { operations }.encode().unwrap(),
+    ));
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        "Contents" => content_id,
+        "Resources" => resources,
+    });
+    doc.set_object(
+        pages_id,
+        dictionary! { "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1 },
+    );
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+    manager.update_catalog(&mut doc);
+
+    let output_path = format!("{}/ocg_membership_test.pdf", TEST_OUTPUT_DIR);
+    doc.save(&output_path).unwrap();
+
+    println!("✅ Layer membership test passed");
+    println!("📄 PDF created: {}", output_path);
+}
+
+#[test]
+fn test_usage_and_alternate_configurations() {
+    ensure_output_dir();
+
+    let mut doc = Document::with_version("1.5");
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog" });
+    doc.trailer.set("Root", catalog_id);
+
+    let mut manager = OCGManager::new();
+    manager.add_layer(Layer::new("Plan", true));
+    manager.add_layer(Layer::new("Markup", true).with_usage(LayerUsage::screen_only()));
+    manager.add_layer(
+        Layer::new("Details", true).with_usage(LayerUsage::new().with_zoom(Some(2.0), None)),
+    );
+    manager.add_layer(
+        Layer::new("Notes", true).with_usage(
+            LayerUsage::new()
+                .with_language("fr-CA", true)
+                .with_user(UserKind::Organisation, vec!["Site team".to_string()]),
+        ),
+    );
+    manager.add_configuration(LayerConfiguration::new("Construction set").hide("Markup"));
+    manager.add_configuration(
+        LayerConfiguration::new("Client review")
+            .with_base_state("OFF")
+            .show("Plan")
+            .show("Details"),
+    );
+    manager.initialize(&mut doc);
+    manager.update_catalog(&mut doc);
+
+    let id = |name: &str| Object::Reference(manager.get_layer(name).unwrap().id);
+
+    // Usage dictionaries on the OCGs
+    let markup = doc.get_dictionary(manager.get_layer("Markup").unwrap().id).unwrap();
+    let usage = markup.get(b"Usage").and_then(Object::as_dict).unwrap();
+    let print = usage.get(b"Print").and_then(Object::as_dict).unwrap();
+    assert_eq!(print.get(b"PrintState").unwrap(), &Object::Name(b"OFF".to_vec()));
+    assert!(!doc
+        .get_dictionary(manager.get_layer("Plan").unwrap().id)
+        .unwrap()
+        .has(b"Usage"));
+
+    // Auto-state entries name the layers of each category
+    let oc_properties = doc
+        .get_dictionary(doc.catalog().unwrap().get(b"OCProperties").unwrap().as_reference().unwrap())
+        .unwrap();
+    let default_config = oc_properties.get(b"D").and_then(Object::as_dict).unwrap();
+    let auto_state = default_config.get(b"AS").and_then(Object::as_array).unwrap();
+    let entry = |category: &[u8]| {
+        auto_state
+            .iter()
+            .map(|entry| entry.as_dict().unwrap())
+            .find(|entry| entry.get(b"Category").unwrap().as_array().unwrap()[0].as_name().unwrap() == category)
+            .unwrap()
+    };
+    assert_eq!(entry(b"Print").get(b"Event").unwrap(), &Object::Name(b"Print".to_vec()));
+    assert_eq!(entry(b"Print").get(b"OCGs").unwrap(), &Object::Array(vec![id("Markup")]));
+    assert_eq!(entry(b"Zoom").get(b"OCGs").unwrap(), &Object::Array(vec![id("Details")]));
+    assert_eq!(entry(b"Language").get(b"OCGs").unwrap(), &Object::Array(vec![id("Notes")]));
+
+    // Named configurations
+    let configs = oc_properties.get(b"Configs").and_then(Object::as_array).unwrap();
+    assert_eq!(configs.len(), 2);
+    let review = configs[1].as_dict().unwrap();
+    assert_eq!(review.get(b"BaseState").unwrap(), &Object::Name(b"OFF".to_vec()));
+    assert_eq!(review.get(b"ON").unwrap(), &Object::Array(vec![id("Plan"), id("Details")]));
+    assert!(review.has(b"Order") && review.has(b"AS"));
+
+    let output_path = format!("{}/ocg_usage_test.pdf", TEST_OUTPUT_DIR);
+    doc.save(&output_path).unwrap();
+
+    // Usage and configurations survive a round trip and follow renames
+    let mut reloaded = OCGManager::from_document(&Document::load(&output_path).unwrap()).unwrap();
+    assert_eq!(reloaded.get_layer("Markup").unwrap().usage, LayerUsage::screen_only());
+    assert_eq!(reloaded.get_layer("Details").unwrap().usage.zoom_min, Some(2.0));
+    assert_eq!(
+        reloaded.get_layer("Notes").unwrap().usage.language,
+        Some(("fr-CA".to_string(), true))
+    );
+    assert_eq!(reloaded.configurations().len(), 2);
+    assert_eq!(reloaded.configurations()[0].name, "Construction set");
+
+    reloaded.rename_layer("Markup", "Redlines");
+    assert_eq!(
+        reloaded.configurations()[0].layer_states,
+        vec![("Redlines".to_string(), false)]
+    );
+
+    println!("✅ Usage and configuration test passed");
+    println!("📄 PDF created: {}", output_path);
+}
+
+#[test]
+fn test_alternate_configurations_round_trip() {
+    let mut doc = create_layered_document(vec![Layer::new("Plan", true), Layer::new("Notes", true)]);
+    let loaded = OCGManager::from_document(&doc).unwrap();
+    let plan = Object::Reference(loaded.get_layer("Plan").unwrap().id);
+    let notes = Object::Reference(loaded.get_layer("Notes").unwrap().id);
+
+    let review = dictionary! {
+        "Name" => Object::string_literal("Review"),
+        "BaseState" => "OFF",
+        "ON" => vec![notes.clone()],
+        "Order" => vec![notes.clone(), plan.clone()],
+        "Locked" => vec![plan.clone()],
+        "RBGroups" => vec![Object::Array(vec![plan.clone(), notes.clone()])],
+        "AS" => vec![Object::Dictionary(dictionary! {
+            "Event" => "Print",
+            "Category" => vec![Object::Name(b"Print".to_vec())],
+            "OCGs" => vec![notes.clone()],
+        })],
+        "Intent" => "Design",
+        "ListMode" => "VisiblePages",
+    };
+    let oc_properties_id = doc
+        .catalog()
+        .unwrap()
+        .get(b"OCProperties")
+        .and_then(Object::as_reference)
+        .unwrap();
+    doc.get_dictionary_mut(oc_properties_id)
+        .unwrap()
+        .set("Configs", vec![Object::Dictionary(review.clone())]);
+
+    // Editing the default configuration leaves the alternate one as it was
+    let mut manager = OCGManager::from_document(&doc).unwrap();
+    assert_eq!(manager.configurations()[0].layer_states, vec![("Notes".to_string(), true)]);
+    manager.set_layer_visibility("Plan", false);
+    manager.write_to_document(&mut doc);
+
+    let configs = |doc: &Document| {
+        doc.get_dictionary(oc_properties_id)
+            .unwrap()
+            .get(b"Configs")
+            .and_then(Object::as_array)
+            .unwrap()[0]
+            .as_dict()
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(configs(&doc), review);
+
+    // Removing a layer drops it from the configuration's own entries
+    let mut manager = OCGManager::from_document(&doc).unwrap();
+    manager.remove_layer("Plan");
+    manager.write_to_document(&mut doc);
+    let config = configs(&doc);
+    assert_eq!(config.get(b"Order").unwrap(), &Object::Array(vec![notes.clone()]));
+    assert_eq!(config.get(b"Locked").unwrap(), &Object::Array(vec![]));
+    assert_eq!(config.get(b"Intent").unwrap(), &Object::Name(b"Design".to_vec()));
+    assert_eq!(config.get(b"ON").unwrap(), &Object::Array(vec![notes]));
+}
+
+#[test]
+fn test_annotations_and_xobjects_on_layers() {
+    ensure_output_dir();
+
+    let mut doc = create_layered_document(vec![
+        Layer::new("Markup", true),
+        Layer::new("Photos", true),
+        Layer::new("Plan", true),
+    ]);
+    let mut manager = OCGManager::from_document(&doc).unwrap();
+    let photos_or_plan = manager
+        .add_membership(&mut doc, &LayerMembership::new(VisibilityPolicy::AnyOn, &["Photos", "Plan"]))
+        .unwrap();
+
+    let annotation_id = doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Text",
+        "Rect" => vec![100.into(), 700.into(), 120.into(), 720.into()],
+        "Contents" => Object::string_literal("Check this dimension"),
+    });
+    let image_id = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => 1,
+            "Height" => 1,
+            "ColorSpace" => "DeviceGray",
+            "BitsPerComponent" => 8,
+        },
+        vec![128],
+    ));
+    let page_id = doc.page_iter().next().unwrap();
+    let page = doc.get_dictionary_mut(page_id).unwrap();
+    page.set("Annots", vec![annotation_id.into()]);
+
+    manager.assign_layer(&mut doc, annotation_id, "Markup").unwrap();
+    manager.assign_membership(&mut doc, image_id, &photos_or_plan).unwrap();
+
+    let oc = |doc: &Document, id| match doc.get_object(id).unwrap() {
+        Object::Stream(stream) => stream.dict.get(b"OC").unwrap().clone(),
+        object => object.as_dict().unwrap().get(b"OC").unwrap().clone(),
+    };
+    assert_eq!(oc(&doc, annotation_id), Object::Reference(manager.get_layer("Markup").unwrap().id));
+    assert_eq!(oc(&doc, image_id), Object::Reference(manager.get_membership(&photos_or_plan).unwrap()));
+
+    // Only annotations and image or form XObjects can carry optional content
+    let font_id = doc.add_object(dictionary! { "Type" => "Font", "Subtype" => "Type1" });
+    assert!(manager.assign_layer(&mut doc, font_id, "Markup").is_err());
+    assert!(manager.assign_layer(&mut doc, annotation_id, "Missing").is_err());
+    assert!(manager.assign_membership(&mut doc, annotation_id, "M9").is_err());
+    assert!(hipdf::ocg::set_optional_content(&mut doc, annotation_id, font_id).is_err());
+
+    let output_path = format!("{}/ocg_annotations_test.pdf", TEST_OUTPUT_DIR);
+    doc.save(&output_path).unwrap();
+
+    println!("✅ Annotation and XObject layer test passed");
+    println!("📄 PDF created: {}", output_path);
+}
+
+/// Builds a page with unlayered content, nested layers, a membership, a layered form
+/// XObject and a layered annotation. Each filled square is identified by its x offset.
+fn create_split_document() -> (Document, OCGManager) {
+    let mut doc = Document::with_version("1.6");
+    let mut manager = OCGManager::new();
+    manager.add_layer(Layer::new("Structure", true));
+    manager.add_layer(Layer::new("Electrical", true));
+    manager.add_layer(Layer::new("Plumbing", true));
+    manager.initialize(&mut doc);
+    let services = manager
+        .add_membership(&mut doc, &LayerMembership::new(VisibilityPolicy::AnyOn, &["Electrical", "Plumbing"]))
+        .unwrap();
+
+    let mut resources = dictionary! {};
+    let tags = manager.setup_page_resources(&mut resources);
+    let square = |x: f32| vec![Ops::rectangle(x, 100.0, 10.0, 10.0), Ops::fill()];
+
+    let form_content = Content { operations: square(60.0) };
+    let form_id = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        },
+        form_content.encode().unwrap(),
+    ));
+    manager.assign_layer(&mut doc, form_id, "Plumbing").unwrap();
+    resources.set("XObject", dictionary! { "Fm0" => form_id });
+
+    let name = |name: &str| Object::Name(name.as_bytes().to_vec());
+    let layer = |tag: &str| Operation::new("BDC", vec![name("OC"), name(tag)]);
+    let end = || Operation::new("EMC", vec![]);
+    let mut operations = square(10.0);
+    // Electrical nested inside Structure
+    operations.push(layer(&tags["Structure"]));
+    operations.extend(square(20.0));
+    operations.push(layer(&tags["Electrical"]));
+    operations.extend(square(30.0));
+    operations.extend([end(), end()]);
+    operations.push(Operation::new("BMC", vec![name("Span")]));
+    operations.extend(square(40.0));
+    operations.push(end());
+    operations.push(layer(&services));
+    operations.extend(square(50.0));
+    operations.push(end());
+    operations.push(Operation::new("Do", vec![name("Fm0")]));
+    let content = Content { operations };
+    let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+
+    let annotation_id = doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Text",
+        "Rect" => vec![10.into(), 10.into(), 30.into(), 30.into()],
+    });
+    manager.assign_layer(&mut doc, annotation_id, "Electrical").unwrap();
+
+    let pages_id = doc.new_object_id();
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        "Contents" => content_id,
+        "Resources" => resources,
+        "Annots" => vec![annotation_id.into()],
+    });
+    doc.set_object(
+        pages_id,
+        dictionary! { "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1 },
+    );
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+    manager.update_catalog(&mut doc);
+
+    (doc, manager)
+}
+
+/// X offsets of the squares a document paints, following form XObjects, and the
+/// marked-content operators left in the page content
+fn painted_squares(doc: &Document) -> (Vec<f32>, Vec<String>) {
+    fn walk(doc: &Document, operations: &[Operation], resources: &hipdf::lopdf::Dictionary, painted: &mut Vec<f32>) {
+        let mut x = 0.0;
+        for op in operations {
+            match op.operator.as_str() {
+                "re" => x = op.operands[0].as_float().unwrap(),
+                "f" => painted.push(x),
+                "Do" => {
+                    let name = op.operands[0].as_name().unwrap();
+                    let xobject_id = resources
+                        .get(b"XObject")
+                        .and_then(Object::as_dict)
+                        .and_then(|xobjects| xobjects.get(name))
+                        .and_then(Object::as_reference)
+                        .unwrap();
+                    let form = doc.get_object(xobject_id).unwrap().as_stream().unwrap();
+                    let content = Content::decode(&form.get_plain_content().unwrap()).unwrap();
+                    walk(doc, &content.operations, &hipdf::lopdf::Dictionary::new(), painted);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let page_id = doc.page_iter().next().unwrap();
+    let resources = doc
+        .get_dictionary(page_id)
+        .unwrap()
+        .get(b"Resources")
+        .and_then(Object::as_dict)
+        .unwrap();
+    let content = Content::decode(&doc.get_page_content(page_id).unwrap()).unwrap();
+    let mut painted = Vec::new();
+    walk(doc, &content.operations, resources, &mut painted);
+    let markers = content
+        .operations
+        .iter()
+        .filter(|op| ["BMC", "BDC", "EMC"].contains(&op.operator.as_str()))
+        .map(|op| op.operator.clone())
+        .collect();
+    (painted, markers)
+}
+
+#[test]
+fn test_split_layers_into_documents() {
+    ensure_output_dir();
+
+    let (mut doc, _) = create_split_document();
+    let path = format!("{}/ocg_split_source.pdf", TEST_OUTPUT_DIR);
+    doc.save(&path).unwrap();
+
+    // Split a reloaded copy, as a client would with a PDF from elsewhere
+    let loaded = Document::load(&path).unwrap();
+    let manager = OCGManager::from_document(&loaded).unwrap();
+    let parts = manager.split_by_layer(&loaded).unwrap();
+    let names: Vec<&str> = parts.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["Structure", "Electrical", "Plumbing"]);
+
+    // Nested sections are hidden with their parent; memberships follow their policy
+    let painted: Vec<Vec<f32>> = parts.iter().map(|(_, part)| painted_squares(part).0).collect();
+    assert_eq!(painted[0], vec![20.0]);
+    assert_eq!(painted[1], vec![50.0]);
+    assert_eq!(painted[2], vec![50.0, 60.0]);
+
+    for (name, part) in &parts {
+        let catalog = part.catalog().unwrap();
+        assert!(!catalog.has(b"OCProperties"));
+        assert!(part.objects.values().all(|obj| match obj {
+            Object::Stream(stream) => !stream.dict.has(b"OC"),
+            obj => obj.as_dict().map(|dict| !dict.has(b"OC")).unwrap_or(true),
+        }));
+
+        let mut part = part.clone();
+        let output_path = format!("{}/ocg_split_{}.pdf", TEST_OUTPUT_DIR, name.to_lowercase());
+        part.save(&output_path).unwrap();
+        println!("📄 PDF created: {}", output_path);
+    }
+
+    // Only the Electrical part keeps the Electrical annotation
+    let annotations = |part: &Document| {
+        let page_id = part.page_iter().next().unwrap();
+        part.get_page_annotations(page_id).unwrap().len()
+    };
+    assert_eq!(annotations(&parts[0].1), 0);
+    assert_eq!(annotations(&parts[1].1), 1);
+
+    assert!(manager.extract_layer(&loaded, "Missing").is_err());
+
+    println!("✅ Layer split test passed");
+}
+
+#[test]
+fn test_extract_layered_form_drawn_on_several_pages() {
+    let mut doc = Document::with_version("1.5");
+    let mut manager = OCGManager::new();
+    manager.add_layer(Layer::new("L", true));
+    manager.add_layer(Layer::new("Other", true));
+    manager.initialize(&mut doc);
+
+    let form_id = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => vec![0.into(), 0.into(), 100.into(), 100.into()],
+        },
+        Content { operations: vec![Ops::rectangle(30.0, 30.0, 10.0, 10.0), Ops::fill()] }
+            .encode()
+            .unwrap(),
+    ));
+    manager.assign_layer(&mut doc, form_id, "L").unwrap();
+
+    let pages_id = doc.new_object_id();
+    let page_ids: Vec<_> = (0..2)
+        .map(|_| {
+            let content = Content {
+                operations: vec![
+                    Ops::rectangle(5.0, 5.0, 10.0, 10.0),
+                    Ops::fill(),
+                    Operation::new("Do", vec![Object::Name(b"Fm0".to_vec())]),
+                ],
+            };
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+            doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "MediaBox" => vec![0.into(), 0.into(), 200.into(), 200.into()],
+                "Contents" => content_id,
+                "Resources" => dictionary! { "XObject" => dictionary! { "Fm0" => form_id } },
+            })
+        })
+        .collect();
+    doc.set_object(
+        pages_id,
+        dictionary! {
+            "Type" => "Pages",
+            "Kids" => page_ids.iter().map(|&id| id.into()).collect::<Vec<Object>>(),
+            "Count" => 2,
+        },
+    );
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+    manager.update_catalog(&mut doc);
+
+    // Squares painted per page, following forms
+    let painted = |part: &Document| -> Vec<Vec<f32>> {
+        part.page_iter()
+            .map(|page_id| {
+                let page = part.get_dictionary(page_id).unwrap();
+                let xobjects = page
+                    .get(b"Resources")
+                    .and_then(Object::as_dict)
+                    .and_then(|resources| resources.get(b"XObject"))
+                    .and_then(Object::as_dict)
+                    .unwrap();
+                let mut x = 0.0;
+                let mut squares = Vec::new();
+                let operations = Content::decode(&part.get_page_content(page_id).unwrap()).unwrap().operations;
+                for op in operations {
+                    match op.operator.as_str() {
+                        "re" => x = op.operands[0].as_float().unwrap(),
+                        "f" => squares.push(x),
+                        "Do" => {
+                            let form_id = xobjects.get(op.operands[0].as_name().unwrap()).unwrap();
+                            let form = part.get_object(form_id.as_reference().unwrap()).unwrap();
+                            let form = Content::decode(&form.as_stream().unwrap().get_plain_content().unwrap()).unwrap();
+                            squares.extend(form.operations.iter().filter(|op| op.operator == "f").map(|_| 30.0));
+                        }
+                        _ => {}
+                    }
+                }
+                squares
+            })
+            .collect()
+    };
+
+    let extracted = manager.extract_layer(&doc, "L").unwrap();
+    assert_eq!(painted(&extracted), vec![vec![30.0], vec![30.0]]);
+
+    let parts = manager.split_by_layer(&doc).unwrap();
+    assert_eq!(painted(&parts[0].1), vec![vec![30.0], vec![30.0]]);
+    assert_eq!(painted(&parts[1].1), vec![Vec::<f32>::new(), Vec::new()]);
+}
+
+#[test]
+fn test_flatten_layers() {
+    ensure_output_dir();
+
+    let (mut doc, manager) = create_split_document();
+    manager.flatten_layers(&mut doc, &["Structure", "Electrical"]).unwrap();
+
+    // Unlayered content and the chosen layers stay; only the /Span section keeps markers
+    let (painted, markers) = painted_squares(&doc);
+    assert_eq!(painted, vec![10.0, 20.0, 30.0, 40.0, 50.0]);
+    assert_eq!(markers, vec!["BMC", "EMC"]);
+    assert!(!doc.catalog().unwrap().has(b"OCProperties"));
+    assert!(OCGManager::from_document(&doc).unwrap().is_empty());
+
+    // Turning everything off keeps only unlayered content
+    let (mut hidden, manager) = create_split_document();
+    manager.flatten_layers(&mut hidden, &[]).unwrap();
+    assert_eq!(painted_squares(&hidden).0, vec![10.0, 40.0]);
+
+    let output_path = format!("{}/ocg_flatten_test.pdf", TEST_OUTPUT_DIR);
+    doc.save(&output_path).unwrap();
+
+    println!("✅ Layer flattening test passed");
+    println!("📄 PDF created: {}", output_path);
+}
+
+#[test]
+fn test_hidden_text_keeps_following_text_in_place() {
+    let mut doc = Document::with_version("1.5");
+    let mut manager = OCGManager::new();
+    manager.add_layer(Layer::new("Notes", true));
+    manager.initialize(&mut doc);
+    let mut resources = dictionary! {};
+    let tags = manager.setup_page_resources(&mut resources);
+
+    // Hidden text and visible text sharing one text object, in stroke mode
+    let name = |name: &str| Object::Name(name.as_bytes().to_vec());
+    let operations = vec![
+        Operation::new("BT", vec![]),
+        Operation::new("Tf", vec![name("F1"), 12.into()]),
+        Operation::new("Td", vec![100.into(), 700.into()]),
+        Operation::new("Tr", vec![1.into()]),
+        Operation::new("BDC", vec![name("OC"), name(&tags["Notes"])]),
+        Operation::new("Tj", vec![Object::string_literal("Hidden ")]),
+        Operation::new("TJ", vec![vec![Object::string_literal("note "), 120.into()].into()]),
+        Operation::new("EMC", vec![]),
+        Operation::new("Tj", vec![Object::string_literal("Visible")]),
+        Operation::new("ET", vec![]),
+    ];
+    let content_id = doc.add_object(Stream::new(dictionary! {}, Content { operations }.encode().unwrap()));
+    let pages_id = doc.new_object_id();
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        "Contents" => content_id,
+        "Resources" => resources,
+    });
+    doc.set_object(pages_id, dictionary! { "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1 });
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+    manager.update_catalog(&mut doc);
+
+    // The hidden text still advances the text position, drawn in invisible mode
+    manager.flatten_layers(&mut doc, &[]).unwrap();
+    let content = Content::decode(&doc.get_page_content(page_id).unwrap()).unwrap();
+    let shown: Vec<(&str, Option<f32>)> = content
+        .operations
+        .iter()
+        .filter(|op| matches!(op.operator.as_str(), "Tr" | "Tj" | "TJ" | "Td"))
+        .map(|op| (op.operator.as_str(), op.operands[0].as_float().ok()))
+        .collect();
+    assert_eq!(
+        shown,
+        vec![
+            ("Td", Some(100.0)),
+            ("Tr", Some(1.0)),
+            ("Tr", Some(3.0)),
+            ("Tj", None),
+            ("Tr", Some(1.0)),
+            ("Tr", Some(3.0)),
+            ("TJ", None),
+            ("Tr", Some(1.0)),
+            ("Tj", None),
+        ]
+    );
+    let last = content.operations.iter().rfind(|op| op.operator == "Tj").unwrap();
+    assert_eq!(last.operands[0], Object::string_literal("Visible"));
+}
+
+#[test]
+fn test_merge_resources_and_append_layered_content() {
+    ensure_output_dir();
+
+    // Two pages sharing inherited, indirect resources that already use marked content:
+    // the existing "Plan" layer under /oc1, and an unrelated property list under /L1
+    let mut doc = Document::with_version("1.6");
+    let plan_id = doc.add_object(dictionary! {
+        "Type" => "OCG",
+        "Name" => Object::string_literal("Plan"),
+    });
+    let properties_id = doc.add_object(dictionary! {
+        "oc1" => plan_id,
+        "L1" => dictionary! { "Lang" => Object::string_literal("en-US") },
+    });
+    let resources_id = doc.add_object(dictionary! { "Properties" => properties_id });
+
+    let pages_id = doc.new_object_id();
+    let name = |name: &str| Object::Name(name.as_bytes().to_vec());
+    let mut kids = Vec::new();
+    for _ in 0..2 {
+        let mut operations = vec![Operation::new("BDC", vec![name("OC"), name("oc1")])];
+        operations.extend([Ops::rectangle(10.0, 100.0, 10.0, 10.0), Ops::fill()]);
+        operations.push(Operation::new("EMC", vec![]));
+        // Leaves a transform behind that must not apply to appended content
+        operations.push(Operation::new("cm", vec![2.into(), 0.into(), 0.into(), 2.into(), 0.into(), 0.into()]));
+        let content = Content { operations };
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        kids.push(Object::from(doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        })));
+    }
+    doc.set_object(
+        pages_id,
+        dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => 2,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            "Resources" => resources_id,
+        },
+    );
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+        "OCProperties" => dictionary! {
+            "OCGs" => vec![plan_id.into()],
+            "D" => dictionary! { "Order" => vec![plan_id.into()], "ON" => vec![plan_id.into()] },
+        },
+    });
+    doc.trailer.set("Root", catalog_id);
+
+    // Add a "Notes" layer and draw on every page
+    let mut manager = OCGManager::from_document(&doc).unwrap();
+    manager.add_layer(Layer::new("Notes", true));
+    manager.write_to_document(&mut doc);
+
+    let page_ids: Vec<_> = doc.page_iter().collect();
+    for (index, &page_id) in page_ids.iter().enumerate() {
+        let tags = manager.merge_page_resources(&mut doc, page_id).unwrap();
+        assert_eq!(tags["Plan"], "oc1");
+        assert_eq!(tags["Notes"], "L1_1");
+
+        let mut builder = LayerContentBuilder::new();
+        builder
+            .begin_layer(&tags["Notes"])
+            .add_operations(vec![Ops::rectangle(30.0 + index as f32, 100.0, 10.0, 10.0), Ops::fill()])
+            .end_layer();
+        pages::append_content(&mut doc, page_id, builder.build().unwrap()).unwrap();
+
+        let page = doc.get_dictionary(page_id).unwrap();
+        assert_eq!(page.get(b"Contents").and_then(Object::as_array).unwrap().len(), 3);
+        let properties = page
+            .get(b"Resources")
+            .and_then(Object::as_dict)
+            .and_then(|resources| resources.get(b"Properties"))
+            .and_then(Object::as_dict)
+            .unwrap();
+        assert!(properties.has(b"L1") && properties.has(b"oc1") && properties.has(b"L1_1"));
+    }
+
+    // The shared dictionaries are untouched
+    assert!(!doc.get_dictionary(properties_id).unwrap().has(b"L1_1"));
+
+    // The appended content starts from a restored graphics state
+    let content = Content::decode(&doc.get_page_content(page_ids[0]).unwrap()).unwrap();
+    let operators: Vec<&str> = content.operations.iter().map(|op| op.operator.as_str()).collect();
+    assert_eq!(operators.first(), Some(&"q"));
+    assert_eq!(operators.iter().position(|&op| op == "Q"), Some(6));
+
+    // Each layer's content ends up where it belongs
+    let output_path = format!("{}/ocg_merged_resources_test.pdf", TEST_OUTPUT_DIR);
+    doc.save(&output_path).unwrap();
+    let reloaded = Document::load(&output_path).unwrap();
+    let manager = OCGManager::from_document(&reloaded).unwrap();
+    let notes = manager.extract_layer(&reloaded, "Notes").unwrap();
+    let plan = manager.extract_layer(&reloaded, "Plan").unwrap();
+    assert_eq!(painted_squares(&notes).0, vec![30.0]);
+    assert_eq!(painted_squares(&plan).0, vec![10.0]);
+
+    // New pages keep their existing properties too
+    let mut resources = dictionary! {
+        "Properties" => dictionary! { "L0" => dictionary! { "MCID" => 0 } },
+    };
+    let mut fresh = OCGManager::new();
+    fresh.add_layer(Layer::new("Only", true));
+    let mut fresh_doc = Document::with_version("1.5");
+    fresh.initialize(&mut fresh_doc);
+    let tags = fresh.setup_page_resources(&mut resources);
+    assert_eq!(tags["Only"], "L0_1");
+    assert_eq!(fresh.get_layer("Only").unwrap().tag.as_deref(), Some("L0_1"));
+    let properties = resources.get(b"Properties").and_then(Object::as_dict).unwrap();
+    assert!(properties.has(b"L0") && properties.has(b"L0_1"));
+
+    // An indirect /Properties dictionary is read through the document, not replaced
+    let shared = fresh_doc.add_object(dictionary! { "L0" => dictionary! { "MCID" => 1 } });
+    let mut resources = dictionary! { "Properties" => shared };
+    let tags = fresh.setup_page_resources_in(&fresh_doc, &mut resources);
+    assert_eq!(tags["Only"], "L0_1");
+    let properties = resources.get(b"Properties").and_then(Object::as_dict).unwrap();
+    assert_eq!(properties.get(b"L0").unwrap(), &Object::Dictionary(dictionary! { "MCID" => 1 }));
+    assert!(properties.has(b"L0_1"));
+
+    println!("✅ Resource merging test passed");
+    println!("📄 PDF created: {}", output_path);
+}
+
 /// Clean up fixture
 #[test]
 fn cleanup() {
@@ -0,0 +1,396 @@
+//! Page Operations Integration Tests
+//!
+//! These tests validate merging, splitting, reordering, deleting and inserting pages
+
+use hipdf::embed_pdf::{PageRange, PdfEmbedder};
+use hipdf::lopdf::{content::Content, dictionary, Document, Object, ObjectId, Stream};
+use hipdf::pages;
+
+use std::fs;
+use std::path::Path;
+
+/// Directory for test outputs
+const TEST_OUTPUT_DIR: &str = "tests/outputs/pages_integration_test";
+
+fn ensure_output_dir() {
+    if !Path::new(TEST_OUTPUT_DIR).exists() {
+        fs::create_dir_all(TEST_OUTPUT_DIR).expect("Failed to create test output directory");
+    }
+}
+
+/// Builds a three page source PDF with a nested page tree, links and an outline.
+///
+/// Page 1 links to page 2 with an explicit destination and to page 3 through a
+/// named destination. The outline has one entry per page, page 3 nested under page 2.
+fn create_linked_source(label: &str) -> Vec<u8> {
+    let mut source = Document::with_version("1.5");
+    let root_pages_id = source.new_object_id();
+    let inner_pages_id = source.new_object_id();
+    let font_id = source.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+
+    let mut page_ids = Vec::new();
+    for index in 0..3 {
+        let content = Content {
+            operations: vec![
+                lopdf::content::Operation::new("BT", vec![]),
+                lopdf::content::Operation::new("Tf", vec![Object::Name(b"F1".to_vec()), 24.into()]),
+                lopdf::content::Operation::new("Td", vec![50.into(), 700.into()]),
+                lopdf::content::Operation::new(
+                    "Tj",
+                    vec![Object::string_literal(format!("{} page {}", label, index + 1))],
+                ),
+                lopdf::content::Operation::new("ET", vec![]),
+            ],
+        };
+        let content_id = source.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        // The first page hangs off the root, the others off an intermediate node
+        let parent = if index == 0 { root_pages_id } else { inner_pages_id };
+        page_ids.push(source.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => parent,
+            "Contents" => content_id,
+        }));
+    }
+
+    let explicit_link = source.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Link",
+        "Rect" => vec![50.into(), 600.into(), 200.into(), 620.into()],
+        "Dest" => vec![page_ids[1].into(), "Fit".into()],
+    });
+    let named_link = source.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Link",
+        "Rect" => vec![50.into(), 560.into(), 200.into(), 580.into()],
+        "A" => dictionary! {
+            "S" => "GoTo",
+            "D" => Object::string_literal("third"),
+        },
+    });
+    source
+        .get_dictionary_mut(page_ids[0])
+        .unwrap()
+        .set("Annots", vec![explicit_link.into(), named_link.into()]);
+
+    source.set_object(
+        inner_pages_id,
+        dictionary! {
+            "Type" => "Pages",
+            "Parent" => root_pages_id,
+            "Kids" => vec![page_ids[1].into(), page_ids[2].into()],
+            "Count" => 2,
+        },
+    );
+    source.set_object(
+        root_pages_id,
+        dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_ids[0].into(), inner_pages_id.into()],
+            "Count" => 3,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+        },
+    );
+
+    // Outline: Page 1, Page 2 > Page 3
+    let outlines_id = source.new_object_id();
+    let item_1 = source.new_object_id();
+    let item_2 = source.new_object_id();
+    let item_3 = source.new_object_id();
+    source.set_object(
+        item_1,
+        dictionary! {
+            "Title" => Object::string_literal(format!("{} 1", label)),
+            "Parent" => outlines_id,
+            "Next" => item_2,
+            "Dest" => vec![page_ids[0].into(), "Fit".into()],
+        },
+    );
+    source.set_object(
+        item_2,
+        dictionary! {
+            "Title" => Object::string_literal(format!("{} 2", label)),
+            "Parent" => outlines_id,
+            "Prev" => item_1,
+            "First" => item_3,
+            "Last" => item_3,
+            "Count" => 1,
+            "Dest" => vec![page_ids[1].into(), "Fit".into()],
+        },
+    );
+    source.set_object(
+        item_3,
+        dictionary! {
+            "Title" => Object::string_literal(format!("{} 3", label)),
+            "Parent" => item_2,
+            "Dest" => Object::string_literal("third"),
+        },
+    );
+    source.set_object(
+        outlines_id,
+        dictionary! {
+            "Type" => "Outlines",
+            "First" => item_1,
+            "Last" => item_2,
+            "Count" => 3,
+        },
+    );
+
+    let catalog_id = source.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => root_pages_id,
+        "Outlines" => outlines_id,
+        "Names" => dictionary! {
+            "Dests" => dictionary! {
+                "Names" => vec![
+                    Object::string_literal("third"),
+                    vec![page_ids[2].into(), "Fit".into()].into(),
+                ],
+            },
+        },
+    });
+    source.trailer.set("Root", catalog_id);
+
+    let mut bytes = Vec::new();
+    source.save_to(&mut bytes).unwrap();
+    bytes
+}
+
+/// Explicit destinations of the Link annotations on a page
+fn link_destinations(doc: &Document, page_id: ObjectId) -> Vec<ObjectId> {
+    let annots = match doc.get_dictionary(page_id).unwrap().get(b"Annots") {
+        Ok(annots) => annots.as_array().unwrap().clone(),
+        Err(_) => return Vec::new(),
+    };
+    annots
+        .iter()
+        .map(|annot| doc.get_dictionary(annot.as_reference().unwrap()).unwrap())
+        .map(|annot| match annot.get(b"Dest") {
+            Ok(dest) => dest.clone(),
+            Err(_) => annot.get(b"A").unwrap().as_dict().unwrap().get(b"D").unwrap().clone(),
+        })
+        .map(|dest| dest.as_array().unwrap()[0].as_reference().unwrap())
+        .collect()
+}
+
+/// Titles of the top-level outline entries and their number of children
+fn outline_entries(doc: &Document) -> Vec<(String, usize)> {
+    let outlines = doc
+        .catalog()
+        .unwrap()
+        .get(b"Outlines")
+        .and_then(Object::as_reference)
+        .unwrap();
+    let mut entries = Vec::new();
+    let mut current = doc.get_dictionary(outlines).unwrap().get(b"First").and_then(Object::as_reference).ok();
+    while let Some(item_id) = current {
+        let item = doc.get_dictionary(item_id).unwrap();
+        let title = String::from_utf8_lossy(item.get(b"Title").unwrap().as_str().unwrap()).to_string();
+
+        let mut children = 0;
+        let mut child = item.get(b"First").and_then(Object::as_reference).ok();
+        while let Some(child_id) = child {
+            children += 1;
+            child = doc.get_dictionary(child_id).unwrap().get(b"Next").and_then(Object::as_reference).ok();
+        }
+
+        entries.push((title, children));
+        current = item.get(b"Next").and_then(Object::as_reference).ok();
+    }
+    entries
+}
+
+#[test]
+fn test_merge_pdfs() {
+    ensure_output_dir();
+
+    let mut embedder = PdfEmbedder::new();
+    let first = embedder.load_pdf_from_bytes(&create_linked_source("A"), "a").unwrap();
+    let second = embedder.load_pdf_from_bytes(&create_linked_source("B"), "b").unwrap();
+
+    let mut merged = embedder.merge_pdfs(&[&first, &second]).unwrap();
+    let pages = pages::page_ids(&merged);
+    assert_eq!(pages.len(), 6);
+
+    // Links on each copy point at pages of the same copy
+    assert_eq!(link_destinations(&merged, pages[0]), vec![pages[1], pages[2]]);
+    assert_eq!(link_destinations(&merged, pages[3]), vec![pages[4], pages[5]]);
+
+    // Both outlines are carried over, keeping their nesting
+    assert_eq!(
+        outline_entries(&merged),
+        vec![
+            ("A 1".to_string(), 0),
+            ("A 2".to_string(), 1),
+            ("B 1".to_string(), 0),
+            ("B 2".to_string(), 1),
+        ]
+    );
+
+    // Inherited resources survive flattening of the page tree
+    for &page_id in &pages {
+        assert!(merged.get_dictionary(page_id).unwrap().has(b"Resources"));
+        assert!(merged.get_dictionary(page_id).unwrap().has(b"MediaBox"));
+    }
+
+    let path = format!("{}/merged.pdf", TEST_OUTPUT_DIR);
+    merged.save(&path).unwrap();
+    let reloaded = Document::load(&path).unwrap();
+    assert_eq!(reloaded.get_pages().len(), 6);
+
+    println!("✅ Merge test passed");
+    println!("📄 Output: {}", path);
+}
+
+#[test]
+fn test_split_pdf() {
+    ensure_output_dir();
+
+    let mut embedder = PdfEmbedder::new();
+    let source = embedder.load_pdf_from_bytes(&create_linked_source("S"), "split").unwrap();
+
+    let mut parts = embedder
+        .split_pdf(&source, &[PageRange::Range(0, 1), PageRange::Single(2)])
+        .unwrap();
+    assert_eq!(parts.len(), 2);
+
+    // First part: the link to page 3 is dropped, the link to page 2 kept
+    let first_pages = pages::page_ids(&parts[0]);
+    assert_eq!(first_pages.len(), 2);
+    assert_eq!(link_destinations(&parts[0], first_pages[0]), vec![first_pages[1]]);
+    assert_eq!(
+        outline_entries(&parts[0]),
+        vec![("S 1".to_string(), 0), ("S 2".to_string(), 0)]
+    );
+
+    // Second part: page 3's entry is promoted in place of its dropped parent
+    assert_eq!(pages::page_ids(&parts[1]).len(), 1);
+    assert_eq!(outline_entries(&parts[1]), vec![("S 3".to_string(), 0)]);
+
+    for (index, part) in parts.iter_mut().enumerate() {
+        let path = format!("{}/split_{}.pdf", TEST_OUTPUT_DIR, index + 1);
+        part.save(&path).unwrap();
+        println!("📄 Output: {}", path);
+    }
+
+    println!("✅ Split test passed");
+}
+
+#[test]
+fn test_append_duplicate_pages() {
+    ensure_output_dir();
+
+    let mut embedder = PdfEmbedder::new();
+    let source = embedder.load_pdf_from_bytes(&create_linked_source("D"), "dup").unwrap();
+
+    let mut doc = Document::with_version("1.5");
+    let first = embedder.append_pages(&mut doc, &source, &PageRange::Single(0)).unwrap();
+    let second = embedder
+        .append_pages(&mut doc, &source, &PageRange::Pages(vec![0, 1]))
+        .unwrap();
+    assert_eq!(pages::page_ids(&doc).len(), 3);
+
+    // The second copy of page 1 shares its content but not its annotations
+    let original = doc.get_dictionary(first[0]).unwrap();
+    let copy = doc.get_dictionary(second[0]).unwrap();
+    assert_eq!(original.get(b"Contents").unwrap(), copy.get(b"Contents").unwrap());
+    assert!(!copy.has(b"Annots"));
+
+    let path = format!("{}/append_duplicates.pdf", TEST_OUTPUT_DIR);
+    doc.save(&path).unwrap();
+
+    println!("✅ Duplicate append test passed");
+    println!("📄 Output: {}", path);
+}
+
+#[test]
+fn test_reorder_delete_insert() {
+    ensure_output_dir();
+
+    let mut embedder = PdfEmbedder::new();
+    let source = embedder.load_pdf_from_bytes(&create_linked_source("R"), "reorder").unwrap();
+    let mut doc = embedder.merge_pdfs(&[&source]).unwrap();
+    let original = pages::page_ids(&doc);
+
+    pages::reorder_pages(&mut doc, &[2, 0, 1]).unwrap();
+    assert_eq!(pages::page_ids(&doc), vec![original[2], original[0], original[1]]);
+
+    // Orders that drop or repeat pages are rejected
+    assert!(pages::reorder_pages(&mut doc, &[0, 1]).is_err());
+    assert!(pages::reorder_pages(&mut doc, &[0, 0, 1]).is_err());
+    assert!(pages::reorder_pages(&mut doc, &[0, 1, 5]).is_err());
+
+    let blank = pages::insert_blank_page(&mut doc, 1, 300.0, 400.0).unwrap();
+    assert_eq!(pages::page_ids(&doc)[1], blank);
+    assert_eq!(pages::page_ids(&doc).len(), 4);
+
+    let removed = pages::delete_pages(&mut doc, &PageRange::Pages(vec![0, 3])).unwrap();
+    assert_eq!(removed, 2);
+    assert_eq!(pages::page_ids(&doc), vec![blank, original[0]]);
+
+    let path = format!("{}/reordered.pdf", TEST_OUTPUT_DIR);
+    doc.save(&path).unwrap();
+    let reloaded = Document::load(&path).unwrap();
+    assert_eq!(reloaded.get_pages().len(), 2);
+
+    println!("✅ Reorder, delete and insert test passed");
+    println!("📄 Output: {}", path);
+}
+
+#[test]
+fn test_delete_pages_drops_dangling_links_and_outlines() {
+    ensure_output_dir();
+
+    let mut embedder = PdfEmbedder::new();
+    let source = embedder.load_pdf_from_bytes(&create_linked_source("X"), "delete").unwrap();
+    let mut doc = embedder.merge_pdfs(&[&source]).unwrap();
+    let original = pages::page_ids(&doc);
+
+    // Ranges past the end or reversed are rejected and leave the document untouched
+    assert!(pages::delete_pages(&mut doc, &PageRange::Pages(vec![1, 5])).is_err());
+    let error = pages::delete_pages(&mut doc, &PageRange::Range(1, 10)).unwrap_err().to_string();
+    assert!(error.contains("Page 10 does not exist"), "{}", error);
+    let error = pages::delete_pages(&mut doc, &PageRange::Range(2, 1)).unwrap_err().to_string();
+    assert!(error.contains("reversed"), "{}", error);
+    assert!(embedder.split_pdf(&source, &[PageRange::Range(2, 1)]).is_err());
+    assert_eq!(PageRange::Range(1, 2).to_indices(original.len()).unwrap(), vec![1, 2]);
+    assert_eq!(pages::page_ids(&doc), original);
+
+    // Deleting page 2 drops the link to it; its outline entry keeps its child as a heading
+    pages::delete_pages(&mut doc, &PageRange::Single(1)).unwrap();
+    assert_eq!(link_destinations(&doc, original[0]), vec![original[2]]);
+    assert_eq!(
+        outline_entries(&doc),
+        vec![("X 1".to_string(), 0), ("X 2".to_string(), 1)]
+    );
+    let outlines = doc.catalog().unwrap().get(b"Outlines").and_then(Object::as_reference).unwrap();
+    let heading = doc
+        .get_dictionary(outlines)
+        .unwrap()
+        .get(b"Last")
+        .and_then(Object::as_reference)
+        .unwrap();
+    assert!(!doc.get_dictionary(heading).unwrap().has(b"Dest"));
+
+    // Deleting page 3 as well removes its link and its entry under the heading
+    pages::delete_pages(&mut doc, &PageRange::Single(1)).unwrap();
+    assert!(link_destinations(&doc, original[0]).is_empty());
+    assert_eq!(
+        outline_entries(&doc),
+        vec![("X 1".to_string(), 0), ("X 2".to_string(), 0)]
+    );
+    let root = doc.get_dictionary(outlines).unwrap();
+    assert_eq!(root.get(b"Count").and_then(Object::as_i64).unwrap(), 2);
+
+    let path = format!("{}/deleted_links.pdf", TEST_OUTPUT_DIR);
+    doc.save(&path).unwrap();
+    let reloaded = Document::load(&path).unwrap();
+    assert_eq!(reloaded.get_pages().len(), 1);
+
+    println!("✅ Delete cleanup test passed");
+    println!("📄 Output: {}", path);
+}